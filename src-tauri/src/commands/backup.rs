@@ -0,0 +1,33 @@
+use crate::db::backup::{BackupInfo, BackupStore, RestorePreview};
+use crate::db::{Database, SettingsRepository};
+use chrono::Utc;
+use tauri::State;
+
+/// Every backup, newest first
+#[tauri::command]
+pub fn list_backups(backups: State<'_, BackupStore>) -> Result<Vec<BackupInfo>, String> {
+    backups.list().map_err(|e| e.to_string())
+}
+
+/// Replaces every entry with the contents of a backup. With `dry_run` the
+/// backup is only checked and the preview shows what would come back, for
+/// the user to confirm before running again without it. The current data is
+/// backed up first, so a restore can be undone by restoring that copy.
+#[tauri::command]
+pub fn restore_backup(
+    db: State<'_, Database>,
+    backups: State<'_, BackupStore>,
+    file_name: String,
+    dry_run: bool,
+) -> Result<RestorePreview, String> {
+    if dry_run {
+        return backups.preview(&file_name).map_err(|e| e.to_string());
+    }
+
+    let keep = SettingsRepository::new(db.conn.lock().map_err(|e| e.to_string())?)
+        .get_backup_count();
+    let mut conn = db.conn.lock().map_err(|e| e.to_string())?;
+    backups
+        .restore(&mut conn, &file_name, Utc::now().timestamp(), keep)
+        .map_err(|e| e.to_string())
+}
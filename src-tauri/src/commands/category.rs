@@ -0,0 +1,62 @@
+use crate::db::{Category, CategoryRepository, Database};
+use tauri::State;
+
+#[tauri::command]
+pub fn get_categories(
+    db: State<'_, Database>,
+    include_archived: Option<bool>,
+) -> Result<Vec<Category>, String> {
+    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+    let repo = CategoryRepository::new(conn);
+
+    repo.find_all(include_archived.unwrap_or(false))
+        .map_err(Into::into)
+}
+
+#[tauri::command]
+pub fn create_category(
+    db: State<'_, Database>,
+    name: String,
+    color: String,
+    icon: Option<String>,
+    is_productive: Option<bool>,
+) -> Result<i64, String> {
+    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+    let repo = CategoryRepository::new(conn);
+
+    repo.create(&name, &color, icon.as_deref(), is_productive.unwrap_or(true))
+        .map_err(Into::into)
+}
+
+#[tauri::command]
+pub fn rename_category(db: State<'_, Database>, id: i64, name: String) -> Result<(), String> {
+    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+    let repo = CategoryRepository::new(conn);
+
+    repo.rename(id, &name).map_err(Into::into)
+}
+
+#[tauri::command]
+pub fn archive_category(
+    db: State<'_, Database>,
+    id: i64,
+    archived: Option<bool>,
+) -> Result<(), String> {
+    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+    let repo = CategoryRepository::new(conn);
+
+    repo.set_archived(id, archived.unwrap_or(true))
+        .map_err(Into::into)
+}
+
+#[tauri::command]
+pub fn merge_categories(
+    db: State<'_, Database>,
+    source_id: i64,
+    target_id: i64,
+) -> Result<usize, String> {
+    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+    let repo = CategoryRepository::new(conn);
+
+    repo.merge(source_id, target_id).map_err(Into::into)
+}
@@ -0,0 +1,49 @@
+use crate::db::connection::{self, DbLocation, DB_PATH_ENV};
+use std::path::PathBuf;
+use tauri::State;
+
+/// Where the running app keeps its database, managed as app state
+pub struct DatabaseLocation {
+    pub data_dir: PathBuf,
+    pub active: DbLocation,
+}
+
+#[tauri::command]
+pub fn get_database_location(location: State<'_, DatabaseLocation>) -> DbLocation {
+    location.active.clone()
+}
+
+/// Saves the database path to use from the next start, or goes back to the
+/// default when `path` is omitted. If there is no file at the new location
+/// yet, the current database is copied there at the next start, before it is
+/// opened, so entries recorded until then come along; an existing file is
+/// used as it is. Returns the location the next start will use, which
+/// `TIME_TRACKER_DB` still overrides.
+#[tauri::command]
+pub fn set_database_location(
+    location: State<'_, DatabaseLocation>,
+    path: Option<String>,
+) -> Result<DbLocation, String> {
+    let path = path.map(PathBuf::from);
+    if let Some(path) = &path {
+        if !path.is_absolute() {
+            return Err(format!("Database path must be absolute: {}", path.display()));
+        }
+        if path.is_dir() {
+            return Err(format!("{} is a folder, not a database file", path.display()));
+        }
+    }
+    let target = path
+        .clone()
+        .unwrap_or_else(|| connection::default_db_path(&location.data_dir));
+    let copy_from = (!target.exists() && target != location.active.path)
+        .then_some(location.active.path.as_path());
+    connection::write_pending_copy(&location.data_dir, copy_from)
+        .map_err(|e| format!("Cannot save the database location: {}", e))?;
+    connection::write_location_setting(&location.data_dir, path.as_deref())
+        .map_err(|e| format!("Cannot save the database location: {}", e))?;
+    Ok(connection::resolve_db_location(
+        std::env::var_os(DB_PATH_ENV),
+        &location.data_dir,
+    ))
+}
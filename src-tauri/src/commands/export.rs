@@ -1,6 +1,111 @@
-use crate::db::{Database, TimeEntryRepository};
-use chrono::{DateTime, Utc};
-use tauri::State;
+use crate::db::{Database, EntryFilter, ExportEntry, ExportRow, TimeEntry, TimeEntryRepository};
+use chrono::{DateTime, FixedOffset, Local, NaiveDateTime, TimeZone, Utc};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Emitter, State};
+
+pub(crate) const CSV_HEADER: &str =
+    "Date,Time,UTC Offset,Category,Duration (minutes),Is Away,Is Retroactive,Notes,Project,Client\n";
+
+/// Emit an `export-progress` event after this many rows
+const PROGRESS_EVERY_ROWS: usize = 500;
+
+/// Entries read per database lock while exporting
+const EXPORT_PAGE_ROWS: usize = 500;
+
+/// Zone that exported timestamps are rendered in
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExportZone {
+    Local,
+    Named(Tz),
+    Fixed(FixedOffset),
+}
+
+impl ExportZone {
+    /// Parses `None`/"local", an IANA name such as "Europe/Berlin", or a UTC
+    /// offset such as "UTC", "Z", "+05:30", "-0800" or "UTC+2"
+    pub fn parse(zone: Option<&str>) -> Result<Self, String> {
+        let zone = match zone.map(str::trim) {
+            None | Some("") => return Ok(ExportZone::Local),
+            Some(zone) => zone,
+        };
+        if zone.eq_ignore_ascii_case("local") {
+            return Ok(ExportZone::Local);
+        }
+        if let Some(offset) = parse_utc_offset(zone) {
+            return Ok(ExportZone::Fixed(offset));
+        }
+        zone.parse::<Tz>()
+            .map(ExportZone::Named)
+            .map_err(|_| format!("Unknown timezone '{}'", zone))
+    }
+
+    /// Converts a unix timestamp to a wall-clock time carrying its offset
+    pub fn localize(&self, timestamp: i64) -> Option<DateTime<FixedOffset>> {
+        let utc = DateTime::<Utc>::from_timestamp(timestamp, 0)?;
+        Some(match self {
+            ExportZone::Local => utc.with_timezone(&Local).fixed_offset(),
+            ExportZone::Named(tz) => utc.with_timezone(tz).fixed_offset(),
+            ExportZone::Fixed(offset) => utc.with_timezone(offset),
+        })
+    }
+
+    /// Converts a wall-clock time in this zone back to a unix timestamp. A
+    /// time repeated by a DST change resolves to its first occurrence; a time
+    /// skipped by one has no timestamp.
+    pub fn timestamp_of(&self, local: NaiveDateTime) -> Option<i64> {
+        match self {
+            ExportZone::Local => Local.from_local_datetime(&local).earliest().map(|d| d.timestamp()),
+            ExportZone::Named(tz) => tz.from_local_datetime(&local).earliest().map(|d| d.timestamp()),
+            ExportZone::Fixed(offset) => {
+                offset.from_local_datetime(&local).single().map(|d| d.timestamp())
+            }
+        }
+    }
+
+    /// Name recorded alongside exports, e.g. "Europe/Berlin" or "+05:30"
+    pub fn name(&self) -> String {
+        match self {
+            ExportZone::Local => Local::now().offset().to_string(),
+            ExportZone::Named(tz) => tz.name().to_string(),
+            ExportZone::Fixed(offset) => offset.to_string(),
+        }
+    }
+}
+
+pub(crate) fn parse_utc_offset(zone: &str) -> Option<FixedOffset> {
+    let upper = zone.to_ascii_uppercase();
+    let rest = upper
+        .strip_prefix("UTC")
+        .or_else(|| upper.strip_prefix("GMT"))
+        .unwrap_or(&upper);
+    if rest.is_empty() || rest == "Z" {
+        return FixedOffset::east_opt(0);
+    }
+
+    let (sign, digits) = match rest.as_bytes()[0] {
+        b'+' => (1, &rest[1..]),
+        b'-' => (-1, &rest[1..]),
+        _ => return None,
+    };
+    let (hours, minutes) = match digits.split_once(':') {
+        Some((h, m)) => (h, m),
+        None if digits.len() == 4 => digits.split_at(2),
+        None => (digits, "0"),
+    };
+    if hours.is_empty() || hours.len() > 2 || minutes.len() > 2 {
+        return None;
+    }
+    let hours: i32 = hours.parse().ok()?;
+    let minutes: i32 = minutes.parse().ok()?;
+    if hours > 14 || minutes > 59 {
+        return None;
+    }
+    FixedOffset::east_opt(sign * (hours * 3600 + minutes * 60))
+}
 
 /// Escapes a string for CSV format (handles quotes and commas)
 fn escape_csv_field(field: &str) -> String {
@@ -11,48 +116,377 @@ fn escape_csv_field(field: &str) -> String {
     }
 }
 
+/// Renders one entry as a CSV line, with date and time in `zone`
+fn csv_line(row: &ExportRow, zone: &ExportZone) -> Result<String, String> {
+    let datetime = zone.localize(row.timestamp).ok_or("Invalid timestamp")?;
+
+    Ok(format!(
+        "{},{},{},{},{},{},{},{},{},{}\n",
+        datetime.format("%Y-%m-%d"),
+        datetime.format("%H:%M"),
+        datetime.format("%:z"),
+        escape_csv_field(&row.category),
+        row.duration_minutes,
+        row.is_away,
+        row.is_retroactive,
+        escape_csv_field(row.notes.as_deref().unwrap_or_default()),
+        escape_csv_field(row.project.as_deref().unwrap_or_default()),
+        escape_csv_field(row.client.as_deref().unwrap_or_default())
+    ))
+}
+
+/// Wire format for exports
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    Csv,
+    Json,
+    Ndjson,
+}
+
+/// Describes an export; leads JSON documents and the first NDJSON line
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportMetadata {
+    pub format: ExportFormat,
+    pub start_timestamp: i64,
+    pub end_timestamp: i64,
+    pub timezone: String,
+    pub app_version: String,
+    pub exported_at: i64,
+}
+
+/// Payload of the `export-progress` event
+#[derive(Debug, Clone, Serialize)]
+pub struct ExportProgress {
+    pub written: usize,
+    pub total: usize,
+}
+
+/// Result of an export written to a file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportSummary {
+    pub path: String,
+    pub rows: usize,
+    pub bytes: u64,
+}
+
+/// Passes writes through while counting the bytes that went out
+struct CountingWriter<W: Write> {
+    inner: W,
+    bytes: u64,
+}
+
+impl<W: Write> Write for CountingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.bytes += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// A `TimeEntry` as exported, with its timestamp also rendered in the export
+/// zone and its project and client named, so an import elsewhere can link them
+#[derive(Serialize)]
+struct JsonEntry<'a> {
+    #[serde(flatten)]
+    entry: &'a TimeEntry,
+    local_time: String,
+    project: Option<&'a str>,
+    client: Option<&'a str>,
+}
+
+/// Streams the entries selected by `start`, `end` and `filter` to `out`,
+/// reading them `EXPORT_PAGE_ROWS` at a time and holding the database lock
+/// only while a page is read, so the timer and other commands are not
+/// blocked behind the writes. `on_row` is called with the running count
+/// after each entry. Returns the number of entries written.
+///
+/// JSON is a single `{"metadata": ..., "entries": [...]}` document. NDJSON
+/// writes `{"metadata": ...}` on the first line and one entry per line after it.
+#[allow(clippy::too_many_arguments)]
+fn write_export<W: Write, P: FnMut(usize)>(
+    db: &Database,
+    out: &mut W,
+    format: ExportFormat,
+    zone: &ExportZone,
+    start: i64,
+    end: i64,
+    filter: &EntryFilter,
+    mut on_row: P,
+) -> Result<usize, String> {
+    let metadata = ExportMetadata {
+        format,
+        start_timestamp: start,
+        end_timestamp: end,
+        timezone: zone.name(),
+        app_version: env!("CARGO_PKG_VERSION").to_string(),
+        exported_at: Utc::now().timestamp(),
+    };
+    let io_err = |e: std::io::Error| e.to_string();
+    let json_err = |e: serde_json::Error| e.to_string();
+
+    match format {
+        ExportFormat::Csv => out.write_all(CSV_HEADER.as_bytes()).map_err(io_err)?,
+        ExportFormat::Json => {
+            out.write_all(b"{\"metadata\":").map_err(io_err)?;
+            serde_json::to_writer(&mut *out, &metadata).map_err(json_err)?;
+            out.write_all(b",\"entries\":[").map_err(io_err)?;
+        }
+        ExportFormat::Ndjson => {
+            serde_json::to_writer(&mut *out, &serde_json::json!({ "metadata": metadata }))
+                .map_err(json_err)?;
+            out.write_all(b"\n").map_err(io_err)?;
+        }
+    }
+
+    let mut count = 0;
+    let mut after = None;
+    loop {
+        let page = {
+            let conn = db.conn.lock().map_err(|e| e.to_string())?;
+            TimeEntryRepository::new(conn)
+                .export_page(start, end, filter, after, EXPORT_PAGE_ROWS)
+                .map_err(|e| e.to_string())?
+        };
+
+        for row in &page {
+            match format {
+                ExportFormat::Csv => {
+                    out.write_all(csv_line(&row.to_row(), zone)?.as_bytes()).map_err(io_err)?;
+                }
+                ExportFormat::Json => {
+                    if count > 0 {
+                        out.write_all(b",").map_err(io_err)?;
+                    }
+                    serde_json::to_writer(&mut *out, &json_entry(row, zone)?)
+                        .map_err(json_err)?;
+                }
+                ExportFormat::Ndjson => {
+                    serde_json::to_writer(&mut *out, &json_entry(row, zone)?)
+                        .map_err(json_err)?;
+                    out.write_all(b"\n").map_err(io_err)?;
+                }
+            }
+            count += 1;
+            on_row(count);
+        }
+
+        after = match page.last() {
+            Some(last) if page.len() == EXPORT_PAGE_ROWS => {
+                last.entry.id.map(|id| (last.entry.timestamp, id))
+            }
+            _ => None,
+        };
+        if after.is_none() {
+            break;
+        }
+    }
+
+    if format == ExportFormat::Json {
+        out.write_all(b"]}").map_err(io_err)?;
+    }
+    out.flush().map_err(io_err)?;
+    Ok(count)
+}
+
+fn json_entry<'a>(row: &'a ExportEntry, zone: &ExportZone) -> Result<JsonEntry<'a>, String> {
+    let local_time = zone
+        .localize(row.entry.timestamp)
+        .ok_or("Invalid timestamp")?
+        .to_rfc3339();
+    Ok(JsonEntry {
+        entry: &row.entry,
+        local_time,
+        project: row.project.as_deref(),
+        client: row.client.as_deref(),
+    })
+}
+
+/// Exports entries in the requested format. Times are rendered in the local
+/// timezone unless `timezone` names an IANA zone or a UTC offset.
 #[tauri::command]
-pub fn export_entries_to_csv(
+#[allow(clippy::too_many_arguments)]
+pub fn export_entries(
     db: State<'_, Database>,
     start_timestamp: i64,
     end_timestamp: i64,
+    format: ExportFormat,
+    timezone: Option<String>,
+    project_id: Option<i64>,
+    client_id: Option<i64>,
 ) -> Result<String, String> {
-    let conn = db.conn.lock().map_err(|e| e.to_string())?;
-    let repo = TimeEntryRepository::new(conn);
+    let zone = ExportZone::parse(timezone.as_deref())?;
+    let filter = EntryFilter {
+        project_id,
+        client_id,
+    };
+    let mut out = Vec::new();
+    write_export(
+        &db,
+        &mut out,
+        format,
+        &zone,
+        start_timestamp,
+        end_timestamp,
+        &filter,
+        |_| {},
+    )?;
 
-    let entries = repo
-        .find_raw_by_date_range(start_timestamp, end_timestamp)
-        .map_err(|e| e.to_string())?;
+    String::from_utf8(out).map_err(|e| e.to_string())
+}
 
-    let mut csv = String::from("Date,Time,Category,Duration (minutes),Is Away,Is Retroactive,Notes\n");
+#[tauri::command]
+pub fn export_entries_to_csv(
+    db: State<'_, Database>,
+    start_timestamp: i64,
+    end_timestamp: i64,
+    project_id: Option<i64>,
+    client_id: Option<i64>,
+    timezone: Option<String>,
+) -> Result<String, String> {
+    export_entries(
+        db,
+        start_timestamp,
+        end_timestamp,
+        ExportFormat::Csv,
+        timezone,
+        project_id,
+        client_id,
+    )
+}
 
-    for (timestamp, category, duration_minutes, is_away, is_retroactive, notes) in entries {
-        // Convert timestamp to date and time strings
-        let datetime = DateTime::<Utc>::from_timestamp(timestamp, 0)
-            .ok_or("Invalid timestamp")?;
-        let date = datetime.format("%Y-%m-%d").to_string();
-        let time = datetime.format("%H:%M").to_string();
+/// Writes an export straight to `path` (normally chosen with the save
+/// dialog) instead of returning it over IPC. Rows are streamed from the
+/// database into a temporary file next to `path`, which replaces `path` only
+/// once the export is complete. Emits `export-progress` events while writing.
+#[tauri::command(async)]
+#[allow(clippy::too_many_arguments)]
+pub fn export_entries_to_file(
+    app: AppHandle,
+    db: State<'_, Database>,
+    path: String,
+    start_timestamp: i64,
+    end_timestamp: i64,
+    format: ExportFormat,
+    timezone: Option<String>,
+    project_id: Option<i64>,
+    client_id: Option<i64>,
+) -> Result<ExportSummary, String> {
+    let zone = ExportZone::parse(timezone.as_deref())?;
+    let filter = EntryFilter {
+        project_id,
+        client_id,
+    };
+    let total = {
+        let conn = db.conn.lock().map_err(|e| e.to_string())?;
+        TimeEntryRepository::new(conn)
+            .count_by_date_range(start_timestamp, end_timestamp, &filter)
+            .map_err(|e| e.to_string())?
+    };
 
-        let notes_escaped = escape_csv_field(&notes.unwrap_or_default());
+    let emit_progress = |written: usize| {
+        let _ = app.emit("export-progress", ExportProgress { written, total });
+    };
+    emit_progress(0);
 
-        csv.push_str(&format!(
-            "{},{},{},{},{},{},{}\n",
-            date,
-            time,
-            category,
-            duration_minutes,
-            is_away,
-            is_retroactive,
-            notes_escaped
-        ));
+    let partial = PathBuf::from(format!("{}.part", path));
+    let on_row = |n: usize| {
+        if n.is_multiple_of(PROGRESS_EVERY_ROWS) {
+            emit_progress(n);
+        }
+    };
+    let result = write_file(
+        &db,
+        &partial,
+        format,
+        &zone,
+        start_timestamp,
+        end_timestamp,
+        &filter,
+        on_row,
+    )
+    .and_then(|summary| {
+        fs::rename(&partial, &path).map_err(|e| e.to_string())?;
+        Ok(summary)
+    });
+
+    match result {
+        Ok((rows, bytes)) => {
+            emit_progress(rows);
+            Ok(ExportSummary { path, rows, bytes })
+        }
+        Err(e) => {
+            let _ = fs::remove_file(&partial);
+            Err(e)
+        }
     }
+}
 
-    Ok(csv)
+/// Runs `write_export` into a buffered file, returning rows and bytes written
+#[allow(clippy::too_many_arguments)]
+fn write_file<P: FnMut(usize)>(
+    db: &Database,
+    path: &Path,
+    format: ExportFormat,
+    zone: &ExportZone,
+    start: i64,
+    end: i64,
+    filter: &EntryFilter,
+    on_row: P,
+) -> Result<(usize, u64), String> {
+    let file = File::create(path).map_err(|e| e.to_string())?;
+    let mut out = CountingWriter {
+        inner: BufWriter::new(file),
+        bytes: 0,
+    };
+    let rows = write_export(db, &mut out, format, zone, start, end, filter, on_row)?;
+    Ok((rows, out.bytes))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::db::{connection::Database, migrations};
+
+    fn setup_db() -> Database {
+        let db = Database::new_in_memory().unwrap();
+        {
+            let conn = db.conn.lock().unwrap();
+            migrations::run_migrations(&conn).unwrap();
+            conn.execute_batch(
+                "INSERT INTO clients (id, name) VALUES (1, 'Acme');
+                 INSERT INTO projects (id, client_id, name) VALUES (1, 1, 'Website');
+                 INSERT INTO time_entries (timestamp, category, duration_minutes, notes, project_id)
+                     VALUES (1714554000, 'deep_work', 15, 'first', 1);
+                 INSERT INTO time_entries (timestamp, category, duration_minutes, notes)
+                     VALUES (1714554900, 'meetings', 30, 'second \"quoted\"');
+                 INSERT INTO time_entries (timestamp, category, duration_minutes)
+                     VALUES (1714640400, 'admin', 15);",
+            )
+            .unwrap();
+        }
+        db
+    }
+
+    fn export(db: &Database, format: ExportFormat, zone: &ExportZone) -> (usize, String) {
+        let mut out = Vec::new();
+        let count = write_export(
+            db,
+            &mut out,
+            format,
+            zone,
+            1714521600,
+            1714608000,
+            &EntryFilter::default(),
+            |_| {},
+        )
+        .unwrap();
+        (count, String::from_utf8(out).unwrap())
+    }
 
     #[test]
     fn test_escape_csv_field_plain() {
@@ -78,4 +512,217 @@ mod tests {
     fn test_escape_csv_field_empty() {
         assert_eq!(escape_csv_field(""), "");
     }
+
+    fn build_csv(rows: &[ExportRow], zone: &ExportZone) -> Result<String, String> {
+        let mut csv = String::from(CSV_HEADER);
+        for row in rows {
+            csv.push_str(&csv_line(row, zone)?);
+        }
+        Ok(csv)
+    }
+
+    fn row(timestamp: i64, notes: &str) -> ExportRow {
+        ExportRow {
+            timestamp,
+            category: "deep_work".to_string(),
+            duration_minutes: 15,
+            is_away: false,
+            is_retroactive: false,
+            notes: Some(notes.to_string()),
+            project: None,
+            client: None,
+        }
+    }
+
+    fn zone(name: &str) -> ExportZone {
+        ExportZone::parse(Some(name)).unwrap()
+    }
+
+    #[test]
+    fn test_parse_zone_defaults_to_local() {
+        assert_eq!(ExportZone::parse(None).unwrap(), ExportZone::Local);
+        assert_eq!(ExportZone::parse(Some(" ")).unwrap(), ExportZone::Local);
+        assert_eq!(ExportZone::parse(Some("Local")).unwrap(), ExportZone::Local);
+    }
+
+    #[test]
+    fn test_parse_zone_iana_name() {
+        assert_eq!(zone("Europe/Berlin"), ExportZone::Named(chrono_tz::Europe::Berlin));
+        assert!(ExportZone::parse(Some("Mars/Olympus_Mons")).is_err());
+    }
+
+    #[test]
+    fn test_parse_zone_offsets() {
+        let east = |secs| ExportZone::Fixed(FixedOffset::east_opt(secs).unwrap());
+        assert_eq!(zone("UTC"), east(0));
+        assert_eq!(zone("z"), east(0));
+        assert_eq!(zone("+05:30"), east(5 * 3600 + 1800));
+        assert_eq!(zone("-0800"), east(-8 * 3600));
+        assert_eq!(zone("UTC+2"), east(2 * 3600));
+        assert_eq!(zone("GMT-03:00"), east(-3 * 3600));
+        assert!(ExportZone::parse(Some("+25:00")).is_err());
+        assert!(ExportZone::parse(Some("+5:3:0")).is_err());
+    }
+
+    #[test]
+    fn test_csv_header_and_row() {
+        // 2024-05-01 09:00 UTC
+        let csv = build_csv(&[row(1714554000, "a, b")], &zone("UTC")).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines[0], CSV_HEADER.trim_end());
+        assert_eq!(lines[1], "2024-05-01,09:00,+00:00,deep_work,15,false,false,\"a, b\",,");
+    }
+
+    #[test]
+    fn test_csv_near_midnight_uses_requested_zone() {
+        // 2024-05-02 03:30 UTC is still May 1st in New York
+        let rows = [row(1714620600, "late")];
+        let utc = build_csv(&rows, &zone("UTC")).unwrap();
+        let new_york = build_csv(&rows, &zone("America/New_York")).unwrap();
+        assert!(utc.lines().nth(1).unwrap().starts_with("2024-05-02,03:30,+00:00,"));
+        assert!(new_york.lines().nth(1).unwrap().starts_with("2024-05-01,23:30,-04:00,"));
+    }
+
+    #[test]
+    fn test_csv_dst_fall_back_keeps_repeated_hour_distinct() {
+        // 05:30 and 06:30 UTC on 2024-11-03 are both 01:30 in New York
+        let rows = [row(1730611800, "first"), row(1730615400, "second")];
+        let csv = build_csv(&rows, &zone("America/New_York")).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert!(lines[1].starts_with("2024-11-03,01:30,-04:00,"));
+        assert!(lines[2].starts_with("2024-11-03,01:30,-05:00,"));
+    }
+
+    #[test]
+    fn test_csv_dst_spring_forward_skips_missing_hour() {
+        // 00:45 and 01:00 UTC on 2024-03-31 straddle Berlin's 02:00 -> 03:00 jump
+        let rows = [row(1711845900, "before"), row(1711846800, "after")];
+        let csv = build_csv(&rows, &zone("Europe/Berlin")).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert!(lines[1].starts_with("2024-03-31,01:45,+01:00,"));
+        assert!(lines[2].starts_with("2024-03-31,03:00,+02:00,"));
+    }
+
+    #[test]
+    fn test_zone_name() {
+        assert_eq!(zone("Europe/Berlin").name(), "Europe/Berlin");
+        assert_eq!(zone("+05:30").name(), "+05:30");
+    }
+
+    #[test]
+    fn test_write_export_csv() {
+        let db = setup_db();
+        let (count, csv) = export(&db, ExportFormat::Csv, &zone("UTC"));
+        assert_eq!(count, 2);
+        assert_eq!(csv.lines().count(), 3);
+        assert!(csv.starts_with(CSV_HEADER));
+    }
+
+    #[test]
+    fn test_write_export_json() {
+        let db = setup_db();
+        let (count, json) = export(&db, ExportFormat::Json, &zone("Europe/Berlin"));
+        assert_eq!(count, 2);
+
+        let doc: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(doc["metadata"]["format"], "json");
+        assert_eq!(doc["metadata"]["timezone"], "Europe/Berlin");
+        assert_eq!(doc["metadata"]["start_timestamp"], 1714521600);
+        assert_eq!(doc["metadata"]["end_timestamp"], 1714608000);
+        assert_eq!(doc["metadata"]["app_version"], env!("CARGO_PKG_VERSION"));
+        let entries = doc["entries"].as_array().unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0]["category"], "deep_work");
+        assert_eq!(entries[0]["timestamp"], 1714554000);
+        assert_eq!(entries[0]["local_time"], "2024-05-01T11:00:00+02:00");
+        assert_eq!(entries[0]["project"], "Website");
+        assert_eq!(entries[0]["client"], "Acme");
+        assert!(entries[1]["project"].is_null());
+        assert_eq!(entries[1]["notes"], "second \"quoted\"");
+        assert!(entries[1]["id"].is_i64());
+    }
+
+    #[test]
+    fn test_write_export_json_empty_range() {
+        let db = Database::new_in_memory().unwrap();
+        {
+            let conn = db.conn.lock().unwrap();
+            migrations::run_migrations(&conn).unwrap();
+        }
+        let (count, json) = export(&db, ExportFormat::Json, &zone("UTC"));
+        assert_eq!(count, 0);
+        let doc: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(doc["entries"].as_array().unwrap().len(), 0);
+    }
+
+    #[test]
+    fn test_write_export_ndjson() {
+        let db = setup_db();
+        let (count, ndjson) = export(&db, ExportFormat::Ndjson, &zone("UTC"));
+        assert_eq!(count, 2);
+
+        let lines: Vec<serde_json::Value> = ndjson
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0]["metadata"]["format"], "ndjson");
+        assert_eq!(lines[1]["local_time"], "2024-05-01T09:00:00+00:00");
+        assert_eq!(lines[2]["category"], "meetings");
+    }
+
+    #[test]
+    fn test_write_export_spans_pages() {
+        let db = Database::new_in_memory().unwrap();
+        {
+            let conn = db.conn.lock().unwrap();
+            migrations::run_migrations(&conn).unwrap();
+            for i in 0..=EXPORT_PAGE_ROWS as i64 {
+                conn.execute(
+                    "INSERT INTO time_entries (timestamp, category, duration_minutes)
+                     VALUES (?1, 'admin', 1)",
+                    [1714554000 + i * 60],
+                )
+                .unwrap();
+            }
+        }
+        let (count, ndjson) = export(&db, ExportFormat::Ndjson, &zone("UTC"));
+        assert_eq!(count, EXPORT_PAGE_ROWS + 1);
+
+        let timestamps: Vec<i64> = ndjson
+            .lines()
+            .skip(1)
+            .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap())
+            .map(|entry| entry["timestamp"].as_i64().unwrap())
+            .collect();
+        assert_eq!(timestamps.len(), count);
+        assert!(timestamps.windows(2).all(|pair| pair[0] < pair[1]));
+    }
+
+    #[test]
+    fn test_write_file_reports_rows_and_bytes() {
+        let db = setup_db();
+        let path = std::env::temp_dir().join(format!("export-test-{}.csv", std::process::id()));
+        let mut progress = Vec::new();
+
+        let (rows, bytes) = write_file(
+            &db,
+            &path,
+            ExportFormat::Csv,
+            &zone("UTC"),
+            1714521600,
+            1714608000,
+            &EntryFilter::default(),
+            |n| progress.push(n),
+        )
+        .unwrap();
+
+        let written = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(rows, 2);
+        assert_eq!(progress, vec![1, 2]);
+        assert_eq!(bytes, written.len() as u64);
+        assert!(written.starts_with(CSV_HEADER));
+        assert_eq!(written.lines().count(), 3);
+    }
 }
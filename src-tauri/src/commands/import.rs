@@ -0,0 +1,319 @@
+use super::export::{parse_utc_offset, ExportFormat, ExportZone};
+use crate::db::{ConflictPolicy, Database, ExportRow, ImportReport, ImportRepository};
+use chrono::{NaiveDate, NaiveDateTime, NaiveTime, TimeZone};
+use serde::Deserialize;
+use std::collections::HashMap;
+use tauri::State;
+
+const DEFAULT_DURATION_MINUTES: i32 = 15;
+
+/// One entry of a JSON or NDJSON export. `id`, `project_id` and the rendered
+/// `local_time` are ignored: ids only mean something in the database they
+/// were exported from, so the project is matched by its and its client's name.
+#[derive(Deserialize)]
+struct JsonImportEntry {
+    timestamp: i64,
+    category: String,
+    duration_minutes: Option<i32>,
+    #[serde(default)]
+    is_away: bool,
+    #[serde(default)]
+    is_retroactive: bool,
+    notes: Option<String>,
+    project: Option<String>,
+    client: Option<String>,
+}
+
+type ParsedRows = Vec<Result<ExportRow, String>>;
+
+/// Splits CSV text into records, honouring quoted fields that contain
+/// commas, doubled quotes or line breaks
+fn split_csv_records(text: &str) -> Result<Vec<Vec<String>>, String> {
+    let mut records = Vec::new();
+    let mut record = Vec::new();
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if in_quotes {
+            match c {
+                '"' if chars.peek() == Some(&'"') => {
+                    field.push('"');
+                    chars.next();
+                }
+                '"' => in_quotes = false,
+                _ => field.push(c),
+            }
+            continue;
+        }
+        match c {
+            '"' => in_quotes = true,
+            ',' => record.push(std::mem::take(&mut field)),
+            '\r' if chars.peek() == Some(&'\n') => {}
+            '\n' => {
+                record.push(std::mem::take(&mut field));
+                records.push(std::mem::take(&mut record));
+            }
+            _ => field.push(c),
+        }
+    }
+    if in_quotes {
+        return Err("Unterminated quoted field".to_string());
+    }
+    if !field.is_empty() || !record.is_empty() {
+        record.push(field);
+        records.push(record);
+    }
+
+    // Blank lines carry no entry
+    records.retain(|r| !(r.len() == 1 && r[0].trim().is_empty()));
+    Ok(records)
+}
+
+/// Parses CSV in the layout `export_entries_to_csv` writes. Columns are
+/// matched by header name, so older exports without the UTC Offset, Project
+/// or Client columns still load; their wall-clock times are read in `zone`.
+fn parse_csv(text: &str, zone: &ExportZone) -> Result<ParsedRows, String> {
+    let text = text.strip_prefix('\u{feff}').unwrap_or(text);
+    let mut records = split_csv_records(text)?.into_iter();
+    let header = records.next().ok_or("The file is empty")?;
+    let columns: HashMap<String, usize> = header
+        .iter()
+        .enumerate()
+        .map(|(i, name)| (name.trim().to_lowercase(), i))
+        .collect();
+    for required in ["date", "time", "category"] {
+        if !columns.contains_key(required) {
+            return Err(format!("Missing required column '{}'", required));
+        }
+    }
+
+    Ok(records
+        .map(|record| {
+            let field = |name: &str| {
+                columns
+                    .get(name)
+                    .and_then(|&i| record.get(i))
+                    .map(|value| value.trim())
+                    .filter(|value| !value.is_empty())
+            };
+            parse_csv_row(&field, zone)
+        })
+        .collect())
+}
+
+fn parse_csv_row<'r>(
+    field: &impl Fn(&str) -> Option<&'r str>,
+    zone: &ExportZone,
+) -> Result<ExportRow, String> {
+    let date = field("date").ok_or("Missing date")?;
+    let date = NaiveDate::parse_from_str(date, "%Y-%m-%d")
+        .map_err(|_| format!("Invalid date '{}'", date))?;
+    let time = field("time").ok_or("Missing time")?;
+    let time = NaiveTime::parse_from_str(time, "%H:%M")
+        .or_else(|_| NaiveTime::parse_from_str(time, "%H:%M:%S"))
+        .map_err(|_| format!("Invalid time '{}'", time))?;
+    let local = NaiveDateTime::new(date, time);
+
+    let timestamp = match field("utc offset") {
+        Some(offset) => parse_utc_offset(offset)
+            .ok_or_else(|| format!("Invalid UTC offset '{}'", offset))?
+            .from_local_datetime(&local)
+            .single()
+            .map(|d| d.timestamp()),
+        None => zone.timestamp_of(local),
+    }
+    .ok_or_else(|| format!("{} does not exist in {}", local, zone.name()))?;
+
+    let duration_minutes = match field("duration (minutes)") {
+        Some(duration) => duration
+            .parse()
+            .map_err(|_| format!("Invalid duration '{}'", duration))?,
+        None => DEFAULT_DURATION_MINUTES,
+    };
+
+    Ok(ExportRow {
+        timestamp,
+        category: field("category").ok_or("Missing category")?.to_string(),
+        duration_minutes,
+        is_away: parse_bool(field("is away"))?,
+        is_retroactive: parse_bool(field("is retroactive"))?,
+        notes: field("notes").map(str::to_string),
+        project: field("project").map(str::to_string),
+        client: field("client").map(str::to_string),
+    })
+}
+
+fn parse_bool(value: Option<&str>) -> Result<bool, String> {
+    match value.map(str::to_lowercase).as_deref() {
+        None | Some("false") | Some("0") | Some("no") => Ok(false),
+        Some("true") | Some("1") | Some("yes") => Ok(true),
+        Some(other) => Err(format!("Invalid boolean '{}'", other)),
+    }
+}
+
+/// Parses a JSON export document, a bare array of entries, or NDJSON with an
+/// optional leading metadata line
+fn parse_json(text: &str) -> Result<ParsedRows, String> {
+    let values: Vec<serde_json::Value> = match serde_json::from_str::<serde_json::Value>(text) {
+        Ok(serde_json::Value::Array(entries)) => entries,
+        Ok(serde_json::Value::Object(mut document)) if document.contains_key("entries") => {
+            match document.remove("entries") {
+                Some(serde_json::Value::Array(entries)) => entries,
+                _ => return Err("'entries' must be an array".to_string()),
+            }
+        }
+        _ => text
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| serde_json::from_str(line).map_err(|e| e.to_string()))
+            .collect::<Result<Vec<serde_json::Value>, String>>()?
+            .into_iter()
+            .filter(|value| value.get("metadata").is_none())
+            .collect(),
+    };
+
+    Ok(values
+        .into_iter()
+        .map(|value| {
+            let entry: JsonImportEntry =
+                serde_json::from_value(value).map_err(|e| e.to_string())?;
+            Ok(ExportRow {
+                timestamp: entry.timestamp,
+                category: entry.category,
+                duration_minutes: entry.duration_minutes.unwrap_or(DEFAULT_DURATION_MINUTES),
+                is_away: entry.is_away,
+                is_retroactive: entry.is_retroactive,
+                notes: entry.notes.filter(|notes| !notes.is_empty()),
+                project: entry.project.filter(|project| !project.is_empty()),
+                client: entry.client.filter(|client| !client.is_empty()),
+            })
+        })
+        .collect())
+}
+
+/// Imports entries from the text of a CSV, JSON or NDJSON export. With
+/// `dry_run` nothing is written and the report previews which rows would be
+/// inserted, skipped, overwritten or rejected. `timezone` is only used for
+/// CSV files that have no UTC Offset column.
+#[tauri::command]
+pub fn import_entries(
+    db: State<'_, Database>,
+    content: String,
+    format: ExportFormat,
+    on_conflict: ConflictPolicy,
+    dry_run: bool,
+    timezone: Option<String>,
+) -> Result<ImportReport, String> {
+    let zone = ExportZone::parse(timezone.as_deref())?;
+    let rows = match format {
+        ExportFormat::Csv => parse_csv(&content, &zone)?,
+        ExportFormat::Json | ExportFormat::Ndjson => parse_json(&content)?,
+    };
+
+    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+    let repo = ImportRepository::new(conn);
+    repo.import(rows, on_conflict, dry_run).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::export::CSV_HEADER;
+
+    fn utc() -> ExportZone {
+        ExportZone::parse(Some("UTC")).unwrap()
+    }
+
+    #[test]
+    fn test_split_csv_records_handles_quotes() {
+        let records = split_csv_records("a,\"b, \"\"c\"\"\",\"multi\nline\"\r\n\r\nd,,e\n").unwrap();
+        assert_eq!(
+            records,
+            vec![
+                vec!["a".to_string(), "b, \"c\"".to_string(), "multi\nline".to_string()],
+                vec!["d".to_string(), String::new(), "e".to_string()],
+            ]
+        );
+        assert!(split_csv_records("a,\"open").is_err());
+    }
+
+    #[test]
+    fn test_parse_csv_current_export() {
+        let csv = format!(
+            "{}2024-05-01,11:00,+02:00,deep_work,30,false,true,\"a, b\",Website,Acme\n",
+            CSV_HEADER
+        );
+        let rows = parse_csv(&csv, &utc()).unwrap();
+        let row = rows[0].as_ref().unwrap();
+        // 2024-05-01 09:00 UTC
+        assert_eq!(row.timestamp, 1714554000);
+        assert_eq!(row.category, "deep_work");
+        assert_eq!(row.duration_minutes, 30);
+        assert!(!row.is_away);
+        assert!(row.is_retroactive);
+        assert_eq!(row.notes.as_deref(), Some("a, b"));
+        assert_eq!(row.project.as_deref(), Some("Website"));
+        assert_eq!(row.client.as_deref(), Some("Acme"));
+    }
+
+    #[test]
+    fn test_parse_csv_without_offset_uses_zone() {
+        let csv = "Date,Time,Category,Duration (minutes),Is Away,Is Retroactive,Notes\n\
+                   2024-05-01,05:00,meetings,15,false,false,\n";
+        let zone = ExportZone::parse(Some("America/New_York")).unwrap();
+        let row = parse_csv(csv, &zone).unwrap().remove(0).unwrap();
+        assert_eq!(row.timestamp, 1714554000);
+        assert!(row.notes.is_none());
+    }
+
+    #[test]
+    fn test_parse_csv_reports_bad_rows() {
+        let csv = format!(
+            "{}2024-13-01,09:00,+00:00,deep_work,15,false,false,,,\n\
+             2024-05-01,09:00,+00:00,deep_work,soon,false,false,,,\n\
+             2024-03-31,02:30,,deep_work,15,false,false,,,\n",
+            CSV_HEADER
+        );
+        let zone = ExportZone::parse(Some("Europe/Berlin")).unwrap();
+        let rows = parse_csv(&csv, &zone).unwrap();
+        assert_eq!(rows[0].as_ref().unwrap_err(), "Invalid date '2024-13-01'");
+        assert_eq!(rows[1].as_ref().unwrap_err(), "Invalid duration 'soon'");
+        assert!(rows[2].as_ref().unwrap_err().contains("does not exist"));
+    }
+
+    #[test]
+    fn test_parse_csv_requires_columns() {
+        assert!(parse_csv("Date,Category\n", &utc()).is_err());
+        assert!(parse_csv("", &utc()).is_err());
+    }
+
+    #[test]
+    fn test_parse_json_document_and_ndjson() {
+        let document = r#"{"metadata":{"format":"json"},"entries":[
+            {"id":7,"timestamp":900,"category":"admin","duration_minutes":30,"is_away":false,
+             "is_retroactive":true,"notes":"x","project_id":3,"local_time":"...",
+             "project":"Website","client":"Acme"}]}"#;
+        let rows = parse_json(document).unwrap();
+        let row = rows[0].as_ref().unwrap();
+        assert_eq!((row.timestamp, row.duration_minutes), (900, 30));
+        assert!(row.is_retroactive);
+        assert_eq!(row.project.as_deref(), Some("Website"));
+        assert_eq!(row.client.as_deref(), Some("Acme"));
+
+        let ndjson = "{\"metadata\":{\"format\":\"ndjson\"}}\n\
+                      {\"timestamp\":900,\"category\":\"admin\"}\n\
+                      {\"category\":\"admin\"}\n";
+        let rows = parse_json(ndjson).unwrap();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].as_ref().unwrap().duration_minutes, DEFAULT_DURATION_MINUTES);
+        assert!(rows[1].is_err());
+    }
+
+    #[test]
+    fn test_parse_json_rejects_garbage() {
+        assert!(parse_json("not json").is_err());
+        assert!(parse_json(r#"{"entries": 3}"#).is_err());
+    }
+}
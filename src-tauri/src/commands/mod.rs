@@ -1,7 +1,25 @@
+pub mod backup;
+pub mod category;
+pub mod database;
 pub mod export;
+pub mod import;
+pub mod project;
+pub mod report;
+pub mod schedule;
 pub mod settings;
+pub mod tag;
 pub mod time_entry;
+pub mod timer;
 
+pub use backup::*;
+pub use category::*;
+pub use database::*;
 pub use export::*;
+pub use import::*;
+pub use project::*;
+pub use report::*;
+pub use schedule::*;
 pub use settings::*;
+pub use tag::*;
 pub use time_entry::*;
+pub use timer::*;
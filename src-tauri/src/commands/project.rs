@@ -0,0 +1,118 @@
+use crate::db::{
+    Client, ClientRepository, ClientTotal, Database, Project, ProjectRepository, ProjectTotal,
+    TimeEntryRepository,
+};
+use tauri::State;
+
+#[tauri::command]
+pub fn get_clients(
+    db: State<'_, Database>,
+    include_archived: Option<bool>,
+) -> Result<Vec<Client>, String> {
+    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+    let repo = ClientRepository::new(conn);
+
+    repo.find_all(include_archived.unwrap_or(false))
+        .map_err(Into::into)
+}
+
+#[tauri::command]
+pub fn create_client(db: State<'_, Database>, name: String) -> Result<i64, String> {
+    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+    let repo = ClientRepository::new(conn);
+
+    repo.create(&name).map_err(Into::into)
+}
+
+#[tauri::command]
+pub fn rename_client(db: State<'_, Database>, id: i64, name: String) -> Result<(), String> {
+    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+    let repo = ClientRepository::new(conn);
+
+    repo.rename(id, &name).map_err(Into::into)
+}
+
+#[tauri::command]
+pub fn archive_client(
+    db: State<'_, Database>,
+    id: i64,
+    archived: Option<bool>,
+) -> Result<(), String> {
+    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+    let repo = ClientRepository::new(conn);
+
+    repo.set_archived(id, archived.unwrap_or(true))
+        .map_err(Into::into)
+}
+
+#[tauri::command]
+pub fn get_projects(
+    db: State<'_, Database>,
+    client_id: Option<i64>,
+    include_archived: Option<bool>,
+) -> Result<Vec<Project>, String> {
+    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+    let repo = ProjectRepository::new(conn);
+
+    repo.find_all(client_id, include_archived.unwrap_or(false))
+        .map_err(Into::into)
+}
+
+#[tauri::command]
+pub fn create_project(
+    db: State<'_, Database>,
+    name: String,
+    client_id: Option<i64>,
+) -> Result<i64, String> {
+    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+    let repo = ProjectRepository::new(conn);
+
+    repo.create(client_id, &name).map_err(Into::into)
+}
+
+#[tauri::command]
+pub fn rename_project(db: State<'_, Database>, id: i64, name: String) -> Result<(), String> {
+    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+    let repo = ProjectRepository::new(conn);
+
+    repo.rename(id, &name).map_err(Into::into)
+}
+
+#[tauri::command]
+pub fn archive_project(
+    db: State<'_, Database>,
+    id: i64,
+    archived: Option<bool>,
+) -> Result<(), String> {
+    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+    let repo = ProjectRepository::new(conn);
+
+    repo.set_archived(id, archived.unwrap_or(true))
+        .map_err(Into::into)
+}
+
+#[tauri::command]
+pub fn get_project_totals(
+    db: State<'_, Database>,
+    start_timestamp: i64,
+    end_timestamp: i64,
+) -> Result<Vec<ProjectTotal>, String> {
+    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+    let repo = TimeEntryRepository::new(conn);
+
+    repo.total_minutes_by_project(start_timestamp, end_timestamp)
+        .map_err(Into::into)
+}
+
+#[tauri::command]
+pub fn get_client_totals(
+    db: State<'_, Database>,
+    start_timestamp: i64,
+    end_timestamp: i64,
+) -> Result<Vec<ClientTotal>, String> {
+    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+    let repo = TimeEntryRepository::new(conn);
+
+    repo.total_minutes_by_client(start_timestamp, end_timestamp)
+        .map_err(Into::into)
+}
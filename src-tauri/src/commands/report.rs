@@ -0,0 +1,18 @@
+use crate::db::{Database, ReportBucket, ReportPeriod, ReportRepository};
+use chrono::Local;
+use tauri::State;
+
+/// Minutes per category per day, week or month, bucketed in the local timezone
+#[tauri::command]
+pub fn get_category_report(
+    db: State<'_, Database>,
+    start_timestamp: i64,
+    end_timestamp: i64,
+    period: ReportPeriod,
+) -> Result<Vec<ReportBucket>, String> {
+    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+    let repo = ReportRepository::new(conn);
+
+    repo.minutes_by_category(start_timestamp, end_timestamp, period, &Local)
+        .map_err(Into::into)
+}
@@ -0,0 +1,39 @@
+use crate::db::{Database, ScheduleRepository, WorkBlock, WorkSchedule};
+use tauri::State;
+
+#[tauri::command]
+pub fn get_work_schedule(db: State<'_, Database>) -> Result<WorkSchedule, String> {
+    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+    let repo = ScheduleRepository::new(conn);
+
+    repo.load().map_err(Into::into)
+}
+
+/// Replaces the weekly working hours; an empty list prompts around the clock
+#[tauri::command]
+pub fn set_work_hours(db: State<'_, Database>, blocks: Vec<WorkBlock>) -> Result<(), String> {
+    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+    let repo = ScheduleRepository::new(conn);
+
+    repo.replace_blocks(&blocks).map_err(Into::into)
+}
+
+#[tauri::command]
+pub fn add_day_off(
+    db: State<'_, Database>,
+    date: String,
+    label: Option<String>,
+) -> Result<(), String> {
+    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+    let repo = ScheduleRepository::new(conn);
+
+    repo.add_day_off(&date, label.as_deref()).map_err(Into::into)
+}
+
+#[tauri::command]
+pub fn remove_day_off(db: State<'_, Database>, date: String) -> Result<(), String> {
+    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+    let repo = ScheduleRepository::new(conn);
+
+    repo.remove_day_off(&date).map_err(Into::into)
+}
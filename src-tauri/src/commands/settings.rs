@@ -1,6 +1,7 @@
-use crate::db::{Database, Setting, SettingsRepository};
+use crate::db::{Database, Setting, Settings, SettingsRepository};
+use crate::services::SettingsService;
 use tauri::AppHandle;
-use tauri::State;
+use tauri::{Emitter, State};
 use tauri_plugin_notification::NotificationExt;
 
 #[tauri::command]
@@ -11,12 +12,42 @@ pub fn get_setting(db: State<'_, Database>, key: String) -> Result<Option<String
     repo.get(&key).map_err(Into::into)
 }
 
+/// Validates and stores a setting, then applies it to the running app. The
+/// webview hears about it through a `setting-changed` event.
 #[tauri::command]
-pub fn set_setting(db: State<'_, Database>, key: String, value: String) -> Result<(), String> {
+pub fn set_setting(
+    app: AppHandle,
+    db: State<'_, Database>,
+    settings: State<'_, SettingsService>,
+    key: String,
+    value: String,
+) -> Result<(), String> {
+    let change = settings.set(&db, &key, &value)?;
+    let _ = app.emit("setting-changed", change);
+    Ok(())
+}
+
+#[tauri::command]
+pub fn get_settings(db: State<'_, Database>) -> Result<Settings, String> {
     let conn = db.conn.lock().map_err(|e| e.to_string())?;
     let repo = SettingsRepository::new(conn);
 
-    repo.set(&key, &value).map_err(Into::into)
+    repo.load().map_err(Into::into)
+}
+
+/// Validates and stores all settings at once, so a rejected value leaves
+/// every setting unchanged. Emits `setting-changed` for each changed value.
+#[tauri::command]
+pub fn update_settings(
+    app: AppHandle,
+    db: State<'_, Database>,
+    settings: State<'_, SettingsService>,
+    values: Settings,
+) -> Result<Settings, String> {
+    for change in settings.update(&db, &values)? {
+        let _ = app.emit("setting-changed", change);
+    }
+    get_settings(db)
 }
 
 #[tauri::command]
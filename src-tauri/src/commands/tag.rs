@@ -0,0 +1,91 @@
+use crate::db::{Database, Tag, TagMatch, TagRepository, TagTotal, TimeEntry, TimeEntryRepository};
+use tauri::State;
+
+#[tauri::command]
+pub fn get_tags(db: State<'_, Database>) -> Result<Vec<Tag>, String> {
+    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+    let repo = TagRepository::new(conn);
+
+    repo.find_all().map_err(Into::into)
+}
+
+#[tauri::command]
+pub fn get_entry_tags(db: State<'_, Database>, entry_id: i64) -> Result<Vec<String>, String> {
+    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+    let repo = TagRepository::new(conn);
+
+    repo.find_for_entry(entry_id).map_err(Into::into)
+}
+
+#[tauri::command]
+pub fn add_tag_to_entry(db: State<'_, Database>, entry_id: i64, tag: String) -> Result<(), String> {
+    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+    let repo = TagRepository::new(conn);
+
+    repo.add_to_entry(entry_id, &tag).map_err(Into::into)
+}
+
+#[tauri::command]
+pub fn remove_tag_from_entry(
+    db: State<'_, Database>,
+    entry_id: i64,
+    tag: String,
+) -> Result<(), String> {
+    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+    let repo = TagRepository::new(conn);
+
+    repo.remove_from_entry(entry_id, &tag).map_err(Into::into)
+}
+
+#[tauri::command]
+pub fn get_entries_by_tags(
+    db: State<'_, Database>,
+    tags: Vec<String>,
+    match_mode: Option<TagMatch>,
+    start_timestamp: i64,
+    end_timestamp: i64,
+) -> Result<Vec<TimeEntry>, String> {
+    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+    let repo = TimeEntryRepository::new(conn);
+
+    repo.find_by_tags(
+        &tags,
+        match_mode.unwrap_or(TagMatch::Any),
+        start_timestamp,
+        end_timestamp,
+    )
+    .map_err(Into::into)
+}
+
+#[tauri::command]
+pub fn get_minutes_by_tags(
+    db: State<'_, Database>,
+    tags: Vec<String>,
+    match_mode: Option<TagMatch>,
+    start_timestamp: i64,
+    end_timestamp: i64,
+) -> Result<i64, String> {
+    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+    let repo = TimeEntryRepository::new(conn);
+
+    repo.total_minutes_by_tags(
+        &tags,
+        match_mode.unwrap_or(TagMatch::Any),
+        start_timestamp,
+        end_timestamp,
+    )
+    .map_err(Into::into)
+}
+
+#[tauri::command]
+pub fn get_tag_totals(
+    db: State<'_, Database>,
+    start_timestamp: i64,
+    end_timestamp: i64,
+) -> Result<Vec<TagTotal>, String> {
+    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+    let repo = TagRepository::new(conn);
+
+    repo.total_minutes_per_tag(start_timestamp, end_timestamp)
+        .map_err(Into::into)
+}
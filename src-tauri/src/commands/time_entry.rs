@@ -1,7 +1,14 @@
-use crate::db::{Database, MissedPrompt, MissedPromptRepository, TimeEntry, TimeEntryRepository};
+use crate::db::{
+    Database, EntryChange, EntryFilter, EntryHistoryItem, EntryOverlap, EntrySearchHit,
+    MissedPrompt, MissedPromptRepository, OverlapPolicy, TimeEntry, TimeEntryRepository,
+};
 use tauri::State;
 
+/// Results returned by a search when the caller doesn't ask for a number
+const DEFAULT_SEARCH_LIMIT: u32 = 50;
+
 #[tauri::command]
+#[allow(clippy::too_many_arguments)]
 pub fn create_time_entry(
     db: State<'_, Database>,
     timestamp: i64,
@@ -10,6 +17,8 @@ pub fn create_time_entry(
     is_away: Option<bool>,
     is_retroactive: Option<bool>,
     notes: Option<String>,
+    project_id: Option<i64>,
+    on_overlap: Option<OverlapPolicy>,
 ) -> Result<i64, String> {
     let conn = db.conn.lock().map_err(|e| e.to_string())?;
     let repo = TimeEntryRepository::new(conn);
@@ -21,20 +30,40 @@ pub fn create_time_entry(
         is_away.unwrap_or(false),
         is_retroactive.unwrap_or(false),
         notes.as_deref(),
+        project_id,
+        on_overlap.unwrap_or_default(),
     )
     .map_err(Into::into)
 }
 
+#[tauri::command]
+pub fn find_overlapping_entries(
+    db: State<'_, Database>,
+    start_timestamp: i64,
+    end_timestamp: i64,
+) -> Result<Vec<EntryOverlap>, String> {
+    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+    let repo = TimeEntryRepository::new(conn);
+    repo.find_overlaps(start_timestamp, end_timestamp)
+        .map_err(Into::into)
+}
+
 #[tauri::command]
 pub fn get_entries_for_date(
     db: State<'_, Database>,
     start_timestamp: i64,
     end_timestamp: i64,
+    project_id: Option<i64>,
+    client_id: Option<i64>,
 ) -> Result<Vec<TimeEntry>, String> {
     let conn = db.conn.lock().map_err(|e| e.to_string())?;
     let repo = TimeEntryRepository::new(conn);
 
-    repo.find_by_date_range(start_timestamp, end_timestamp)
+    let filter = EntryFilter {
+        project_id,
+        client_id,
+    };
+    repo.find_by_date_range_filtered(start_timestamp, end_timestamp, &filter)
         .map_err(Into::into)
 }
 
@@ -48,17 +77,24 @@ pub fn update_time_entry(
     let conn = db.conn.lock().map_err(|e| e.to_string())?;
     let repo = TimeEntryRepository::new(conn);
 
-    if let Some(cat) = category {
-        repo.update_category(id, &cat).map_err(|e| e.to_string())?;
-    }
+    repo.update(id, category.as_deref(), notes.as_deref())
+        .map_err(Into::into)
+}
 
-    if let Some(n) = notes {
-        repo.update_notes(id, &n).map_err(|e| e.to_string())?;
-    }
+#[tauri::command]
+pub fn set_time_entry_project(
+    db: State<'_, Database>,
+    id: i64,
+    project_id: Option<i64>,
+) -> Result<(), String> {
+    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+    let repo = TimeEntryRepository::new(conn);
 
-    Ok(())
+    repo.update_project(id, project_id).map_err(Into::into)
 }
 
+/// Moves an entry to the trash, from where `restore_time_entry` or
+/// `undo_last_change` can bring it back until it is purged
 #[tauri::command]
 pub fn delete_time_entry(db: State<'_, Database>, id: i64) -> Result<(), String> {
     let conn = db.conn.lock().map_err(|e| e.to_string())?;
@@ -67,6 +103,68 @@ pub fn delete_time_entry(db: State<'_, Database>, id: i64) -> Result<(), String>
     repo.delete(id).map_err(Into::into)
 }
 
+#[tauri::command]
+pub fn restore_time_entry(db: State<'_, Database>, id: i64) -> Result<(), String> {
+    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+    let repo = TimeEntryRepository::new(conn);
+
+    repo.restore(id).map_err(Into::into)
+}
+
+#[tauri::command]
+pub fn get_deleted_entries(db: State<'_, Database>) -> Result<Vec<TimeEntry>, String> {
+    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+    let repo = TimeEntryRepository::new(conn);
+
+    repo.find_deleted().map_err(Into::into)
+}
+
+/// Entries whose notes match `query`, best match first. Words, "phrases" and
+/// `prefix*` searches are supported; the range and category narrow it down.
+#[tauri::command]
+pub fn search_entries(
+    db: State<'_, Database>,
+    query: String,
+    start_timestamp: Option<i64>,
+    end_timestamp: Option<i64>,
+    category: Option<String>,
+    limit: Option<u32>,
+) -> Result<Vec<EntrySearchHit>, String> {
+    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+    let repo = TimeEntryRepository::new(conn);
+
+    repo.search(
+        &query,
+        start_timestamp,
+        end_timestamp,
+        category.as_deref(),
+        limit.unwrap_or(DEFAULT_SEARCH_LIMIT),
+    )
+    .map_err(Into::into)
+}
+
+/// Every change recorded for the entry, oldest first
+#[tauri::command]
+pub fn get_entry_history(
+    db: State<'_, Database>,
+    entry_id: i64,
+) -> Result<Vec<EntryHistoryItem>, String> {
+    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+    let repo = TimeEntryRepository::new(conn);
+
+    repo.find_history(entry_id).map_err(Into::into)
+}
+
+/// Reverts the latest entry change made from the UI; returns `None` when
+/// there is nothing left to undo
+#[tauri::command]
+pub fn undo_last_change(db: State<'_, Database>) -> Result<Option<EntryChange>, String> {
+    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+    let repo = TimeEntryRepository::new(conn);
+
+    repo.undo_last_change().map_err(Into::into)
+}
+
 #[tauri::command]
 pub fn create_missed_prompt(
     db: State<'_, Database>,
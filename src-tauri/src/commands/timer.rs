@@ -0,0 +1,47 @@
+use crate::services::{TimerCommand, TimerState, TimerStateStore, MAX_SNOOZE_MINUTES};
+use chrono::Utc;
+use tauri::State;
+use tokio::sync::mpsc;
+
+async fn send(timer: &mpsc::Sender<TimerCommand>, cmd: TimerCommand) -> Result<(), String> {
+    timer
+        .send(cmd)
+        .await
+        .map_err(|_| "The timer is not running".to_string())
+}
+
+#[tauri::command]
+pub fn get_timer_state(store: State<'_, TimerStateStore>) -> TimerState {
+    store.get()
+}
+
+/// Stops prompting until `until` (unix seconds), or until resumed when omitted
+#[tauri::command]
+pub async fn pause_timer(
+    timer: State<'_, mpsc::Sender<TimerCommand>>,
+    until: Option<i64>,
+) -> Result<(), String> {
+    if until.is_some_and(|until| until <= Utc::now().timestamp()) {
+        return Err("Pause end must be in the future".to_string());
+    }
+    send(&timer, TimerCommand::Pause { until }).await
+}
+
+#[tauri::command]
+pub async fn resume_timer(timer: State<'_, mpsc::Sender<TimerCommand>>) -> Result<(), String> {
+    send(&timer, TimerCommand::Resume).await
+}
+
+#[tauri::command]
+pub async fn snooze_timer(
+    timer: State<'_, mpsc::Sender<TimerCommand>>,
+    minutes: u64,
+) -> Result<(), String> {
+    if !(1..=MAX_SNOOZE_MINUTES).contains(&minutes) {
+        return Err(format!(
+            "Snooze length must be between 1 and {} minutes",
+            MAX_SNOOZE_MINUTES
+        ));
+    }
+    send(&timer, TimerCommand::Snooze(minutes)).await
+}
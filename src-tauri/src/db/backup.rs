@@ -0,0 +1,455 @@
+use crate::db::migrations::{self, MigrationError};
+use chrono::{NaiveDateTime, TimeZone, Utc};
+use rusqlite::backup::{Backup, Progress};
+use rusqlite::{Connection, DatabaseName, OpenFlags};
+use serde::Serialize;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+const FILE_PREFIX: &str = "time_tracker-";
+const FILE_EXTENSION: &str = ".db";
+const STAMP_FORMAT: &str = "%Y%m%dT%H%M%SZ";
+
+/// Why a backup was taken; part of its file name
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BackupReason {
+    Daily,
+    PreMigration,
+    PreRestore,
+}
+
+impl BackupReason {
+    pub const ALL: [BackupReason; 3] = [
+        BackupReason::Daily,
+        BackupReason::PreMigration,
+        BackupReason::PreRestore,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BackupReason::Daily => "daily",
+            BackupReason::PreMigration => "pre-migration",
+            BackupReason::PreRestore => "pre-restore",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|r| r.as_str() == name)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct BackupInfo {
+    pub file_name: String,
+    pub path: PathBuf,
+    pub created_at: i64,
+    pub reason: BackupReason,
+    pub size_bytes: u64,
+}
+
+/// What restoring a backup would bring back
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RestorePreview {
+    pub backup: BackupInfo,
+    pub schema_version: u32,
+    pub entry_count: i64,
+    pub latest_entry_at: Option<i64>,
+}
+
+#[derive(Debug)]
+pub enum BackupError {
+    Io(io::Error),
+    Database(rusqlite::Error),
+    Migration(MigrationError),
+    /// `PRAGMA integrity_check` found problems in a copy
+    Corrupt { file_name: String, report: String },
+    NotFound(String),
+    TooNew { file_name: String, found: u32, supported: u32 },
+}
+
+impl fmt::Display for BackupError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BackupError::Io(e) => write!(f, "Backup file error: {}", e),
+            BackupError::Database(e) => write!(f, "Backup database error: {}", e),
+            BackupError::Migration(e) => write!(f, "Cannot upgrade restored database: {}", e),
+            BackupError::Corrupt { file_name, report } => {
+                write!(f, "Backup {} failed its integrity check: {}", file_name, report)
+            }
+            BackupError::NotFound(file_name) => write!(f, "Backup not found: {}", file_name),
+            BackupError::TooNew {
+                file_name,
+                found,
+                supported,
+            } => write!(
+                f,
+                "Backup {} has schema version {}, but this version only supports up to {}",
+                file_name, found, supported
+            ),
+        }
+    }
+}
+
+impl std::error::Error for BackupError {}
+
+impl From<io::Error> for BackupError {
+    fn from(err: io::Error) -> Self {
+        BackupError::Io(err)
+    }
+}
+
+impl From<rusqlite::Error> for BackupError {
+    fn from(err: rusqlite::Error) -> Self {
+        BackupError::Database(err)
+    }
+}
+
+impl From<MigrationError> for BackupError {
+    fn from(err: MigrationError) -> Self {
+        BackupError::Migration(err)
+    }
+}
+
+pub type Result<T> = std::result::Result<T, BackupError>;
+
+/// A directory of rotated, integrity-checked database copies
+pub struct BackupStore {
+    dir: PathBuf,
+}
+
+impl BackupStore {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    /// Copies the live database with SQLite's online backup API, which stays
+    /// consistent even with a WAL file in use. The copy only gets its final name
+    /// once it passes an integrity check; then all but the newest `keep`
+    /// backups are deleted.
+    pub fn create(
+        &self,
+        conn: &Connection,
+        reason: BackupReason,
+        now: i64,
+        keep: usize,
+    ) -> Result<BackupInfo> {
+        fs::create_dir_all(&self.dir)?;
+        let file_name = file_name(now, reason);
+        let path = self.dir.join(&file_name);
+        let partial = self.dir.join(format!("{}.partial", file_name));
+
+        conn.backup(DatabaseName::Main, &partial, None::<fn(Progress)>)?;
+        if let Err(e) = check_integrity(&partial, &file_name) {
+            let _ = fs::remove_file(&partial);
+            return Err(e);
+        }
+        fs::rename(&partial, &path)?;
+
+        self.rotate(keep)?;
+        Ok(BackupInfo {
+            size_bytes: fs::metadata(&path)?.len(),
+            file_name,
+            path,
+            created_at: now,
+            reason,
+        })
+    }
+
+    /// Every backup, newest first. Files that don't look like backups are
+    /// left out.
+    pub fn list(&self) -> Result<Vec<BackupInfo>> {
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut backups = Vec::new();
+        for entry in entries {
+            let entry = entry?;
+            let file_name = entry.file_name().to_string_lossy().into_owned();
+            if let Some((created_at, reason)) = parse_file_name(&file_name) {
+                backups.push(BackupInfo {
+                    size_bytes: entry.metadata()?.len(),
+                    path: entry.path(),
+                    file_name,
+                    created_at,
+                    reason,
+                });
+            }
+        }
+        backups.sort_by_key(|b| std::cmp::Reverse(b.created_at));
+        Ok(backups)
+    }
+
+    pub fn latest(&self) -> Result<Option<BackupInfo>> {
+        Ok(self.list()?.into_iter().next())
+    }
+
+    /// Deletes all but the newest `keep` backups
+    pub fn rotate(&self, keep: usize) -> Result<()> {
+        for backup in self.list()?.into_iter().skip(keep.max(1)) {
+            fs::remove_file(&backup.path)?;
+        }
+        Ok(())
+    }
+
+    /// Checks a backup without touching the live database and describes
+    /// what restoring it would bring back
+    pub fn preview(&self, file_name: &str) -> Result<RestorePreview> {
+        let backup = self
+            .list()?
+            .into_iter()
+            .find(|b| b.file_name == file_name)
+            .ok_or_else(|| BackupError::NotFound(file_name.to_string()))?;
+        check_integrity(&backup.path, file_name)?;
+
+        let conn = Connection::open_with_flags(&backup.path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+        let schema_version = migrations::current_version(&conn)?;
+        if schema_version > migrations::latest_version() {
+            return Err(BackupError::TooNew {
+                file_name: file_name.to_string(),
+                found: schema_version,
+                supported: migrations::latest_version(),
+            });
+        }
+        // Entries in the trash don't come back as entries; backups taken
+        // before the trash existed have no such column
+        let has_trash: bool = conn.query_row(
+            "SELECT COUNT(*) > 0 FROM pragma_table_info('time_entries') WHERE name = 'deleted_at'",
+            [],
+            |row| row.get(0),
+        )?;
+        let live = if has_trash { "WHERE deleted_at IS NULL" } else { "" };
+        let (entry_count, latest_entry_at) = conn.query_row(
+            &format!("SELECT COUNT(*), MAX(timestamp) FROM time_entries {}", live),
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )?;
+
+        Ok(RestorePreview {
+            backup,
+            schema_version,
+            entry_count,
+            latest_entry_at,
+        })
+    }
+
+    /// Replaces the live database with a backup. The current data is backed
+    /// up first, so a restore can itself be undone, and the restored copy is
+    /// migrated to the current schema.
+    pub fn restore(
+        &self,
+        conn: &mut Connection,
+        file_name: &str,
+        now: i64,
+        keep: usize,
+    ) -> Result<RestorePreview> {
+        let preview = self.preview(file_name)?;
+        // Rotate afterwards so the backup being restored cannot be dropped
+        self.create(conn, BackupReason::PreRestore, now, usize::MAX)?;
+        copy_read_only(&preview.backup.path, conn)?;
+        migrations::run_migrations(conn)?;
+        self.rotate(keep)?;
+        Ok(preview)
+    }
+}
+
+fn file_name(created_at: i64, reason: BackupReason) -> String {
+    let stamp = Utc
+        .timestamp_opt(created_at, 0)
+        .single()
+        .unwrap_or_default()
+        .format(STAMP_FORMAT);
+    format!("{}{}-{}{}", FILE_PREFIX, stamp, reason.as_str(), FILE_EXTENSION)
+}
+
+fn parse_file_name(file_name: &str) -> Option<(i64, BackupReason)> {
+    let rest = file_name
+        .strip_prefix(FILE_PREFIX)?
+        .strip_suffix(FILE_EXTENSION)?;
+    let (stamp, reason) = rest.split_once('-')?;
+    let created_at = NaiveDateTime::parse_from_str(stamp, STAMP_FORMAT)
+        .ok()?
+        .and_utc()
+        .timestamp();
+    Some((created_at, BackupReason::from_name(reason)?))
+}
+
+/// Replaces the contents of `dst` with the database at `path`, which is
+/// opened read-only so copying never changes it, not even by recovering a
+/// leftover journal
+fn copy_read_only(path: &Path, dst: &mut Connection) -> Result<()> {
+    let src = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+    Backup::new(&src, dst)?.run_to_completion(100, Duration::from_millis(250), None)?;
+    Ok(())
+}
+
+/// Runs on an in-memory copy: checking the notes search index needs a
+/// writable connection, and the backup itself must stay untouched
+fn check_integrity(path: &Path, file_name: &str) -> Result<()> {
+    let mut conn = Connection::open_in_memory()?;
+    copy_read_only(path, &mut conn)?;
+    let mut stmt = conn.prepare("PRAGMA integrity_check")?;
+    let problems = stmt
+        .query_map([], |row| row.get::<_, String>(0))?
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+    if problems != ["ok"] {
+        return Err(BackupError::Corrupt {
+            file_name: file_name.to_string(),
+            report: problems.join("; "),
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scratch_store(name: &str) -> (BackupStore, PathBuf) {
+        let dir = std::env::temp_dir().join(format!("backup-test-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        (BackupStore::new(dir.clone()), dir)
+    }
+
+    fn live_db() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        migrations::run_migrations(&conn).unwrap();
+        conn
+    }
+
+    fn add_entry(conn: &Connection, timestamp: i64) {
+        conn.execute(
+            "INSERT INTO time_entries (timestamp, category, duration_minutes) VALUES (?1, 'meetings', 15)",
+            [timestamp],
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_file_name_roundtrip() {
+        let name = file_name(1_700_000_000, BackupReason::PreMigration);
+        assert_eq!(name, "time_tracker-20231114T221320Z-pre-migration.db");
+        assert_eq!(
+            parse_file_name(&name),
+            Some((1_700_000_000, BackupReason::PreMigration))
+        );
+        assert_eq!(parse_file_name("time_tracker-20231114T221320Z-daily.db.partial"), None);
+        assert_eq!(parse_file_name("notes.txt"), None);
+    }
+
+    #[test]
+    fn test_create_copies_and_rotates() {
+        let (store, dir) = scratch_store("rotate");
+        let conn = live_db();
+        add_entry(&conn, 1_000);
+
+        for day in 0..4 {
+            store
+                .create(&conn, BackupReason::Daily, 1_700_000_000 + day * 86_400, 3)
+                .unwrap();
+        }
+        fs::write(dir.join("unrelated.txt"), "keep me").unwrap();
+
+        let backups = store.list().unwrap();
+        let times: Vec<i64> = backups.iter().map(|b| b.created_at).collect();
+        assert_eq!(
+            times,
+            vec![1_700_259_200, 1_700_172_800, 1_700_086_400],
+            "newest three kept, newest first"
+        );
+        assert!(dir.join("unrelated.txt").exists());
+
+        let copy = Connection::open(&backups[0].path).unwrap();
+        let count: i64 = copy
+            .query_row("SELECT COUNT(*) FROM time_entries", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(count, 1);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_preview_rejects_corrupt_backup() {
+        let (store, dir) = scratch_store("corrupt");
+        fs::create_dir_all(&dir).unwrap();
+        let name = file_name(1_700_000_000, BackupReason::Daily);
+        fs::write(dir.join(&name), "this is not a database").unwrap();
+
+        assert!(store.preview(&name).is_err());
+        assert!(matches!(
+            store.preview("time_tracker-20000101T000000Z-daily.db"),
+            Err(BackupError::NotFound(_))
+        ));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_preview_leaves_backup_untouched() {
+        let (store, dir) = scratch_store("untouched");
+        let conn = live_db();
+        add_entry(&conn, 1_000);
+        let backup = store
+            .create(&conn, BackupReason::Daily, 1_700_000_000, 5)
+            .unwrap();
+        let before = fs::read(&backup.path).unwrap();
+
+        assert_eq!(store.preview(&backup.file_name).unwrap().entry_count, 1);
+        assert_eq!(fs::read(&backup.path).unwrap(), before);
+        let files: Vec<_> = fs::read_dir(&dir).unwrap().collect();
+        assert_eq!(files.len(), 1, "no journal or WAL left next to the backup");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_preview_leaves_out_trashed_entries() {
+        let (store, dir) = scratch_store("trashed");
+        let conn = live_db();
+        add_entry(&conn, 1_000);
+        add_entry(&conn, 2_000);
+        conn.execute("UPDATE time_entries SET deleted_at = 3000 WHERE timestamp = 2000", [])
+            .unwrap();
+        let backup = store
+            .create(&conn, BackupReason::Daily, 1_700_000_000, 5)
+            .unwrap();
+
+        let preview = store.preview(&backup.file_name).unwrap();
+        assert_eq!(preview.entry_count, 1);
+        assert_eq!(preview.latest_entry_at, Some(1_000));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_restore_replaces_live_data_and_keeps_a_copy() {
+        let (store, dir) = scratch_store("restore");
+        let mut conn = live_db();
+        add_entry(&conn, 1_000);
+        let backup = store
+            .create(&conn, BackupReason::Daily, 1_700_000_000, 5)
+            .unwrap();
+        add_entry(&conn, 2_000);
+
+        let preview = store.preview(&backup.file_name).unwrap();
+        assert_eq!(preview.entry_count, 1);
+        assert_eq!(preview.latest_entry_at, Some(1_000));
+        assert_eq!(preview.schema_version, migrations::latest_version());
+
+        store
+            .restore(&mut conn, &backup.file_name, 1_700_000_100, 5)
+            .unwrap();
+        let count: i64 = conn
+            .query_row("SELECT COUNT(*) FROM time_entries", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(count, 1);
+
+        // The state before the restore was saved and can be restored again
+        let safety = store.latest().unwrap().unwrap();
+        assert_eq!(safety.reason, BackupReason::PreRestore);
+        assert_eq!(store.preview(&safety.file_name).unwrap().entry_count, 2);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,34 +1,285 @@
-use rusqlite::Connection;
-use std::path::PathBuf;
-use std::sync::Mutex;
+use crate::db::backup::{BackupError, BackupReason, BackupStore};
+use crate::db::migrations::{self, MigrationError};
+use crate::db::SettingsRepository;
+use rusqlite::{Connection, OpenFlags};
+use serde::Serialize;
+use std::ffi::OsString;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+/// Environment variable that points the app at a specific database file
+pub const DB_PATH_ENV: &str = "TIME_TRACKER_DB";
+
+const DB_FILE_NAME: &str = "time_tracker.db";
+
+/// File in the app data directory holding a user-chosen database path. It
+/// lives outside the database because it decides which database to open.
+const LOCATION_FILE_NAME: &str = "database_location";
+
+/// File in the app data directory naming the database to copy to a newly
+/// chosen location at the next start, before anything is opened
+const PENDING_COPY_FILE_NAME: &str = "database_copy_from";
+
+/// Files SQLite keeps next to a database in WAL mode
+const SIDECAR_SUFFIXES: [&str; 2] = ["-wal", "-shm"];
 
 pub struct Database {
     pub conn: Mutex<Connection>,
 }
 
 impl Database {
-    pub fn new() -> Result<Self, rusqlite::Error> {
-        let db_path = get_db_path();
-
-        // Ensure the directory exists
-        if let Some(parent) = db_path.parent() {
-            std::fs::create_dir_all(parent).ok();
+    /// Opens the database at `path`, creating it and its directory if needed,
+    /// and brings the schema up to date. An existing database is copied to
+    /// `backups` before any migration runs.
+    pub fn open(path: &Path, backups: &BackupStore) -> Result<Self, DatabaseError> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(|source| DatabaseError::Io {
+                path: parent.to_path_buf(),
+                source,
+            })?;
         }
 
-        let conn = Connection::open(&db_path)?;
-        Ok(Database {
+        let has_data = fs::metadata(path).is_ok_and(|m| m.len() > 0);
+        let open_error = |source| DatabaseError::Open {
+            path: path.to_path_buf(),
+            source,
+        };
+        let conn = Connection::open(path).map_err(open_error)?;
+        let database = Database {
             conn: Mutex::new(conn),
-        })
+        };
+
+        if has_data && migrations::has_pending(&database.lock()).map_err(open_error)? {
+            let keep = SettingsRepository::new(database.lock()).get_backup_count();
+            let now = chrono::Utc::now().timestamp();
+            backups
+                .create(&database.lock(), BackupReason::PreMigration, now, keep)
+                .map_err(|source| DatabaseError::Backup {
+                    path: path.to_path_buf(),
+                    source,
+                })?;
+        }
+        migrations::run_migrations(&database.lock()).map_err(|source| {
+            DatabaseError::Migration {
+                path: path.to_path_buf(),
+                source,
+            }
+        })?;
+
+        Ok(database)
+    }
+
+    /// Nothing else holds the connection while the database is being opened
+    fn lock(&self) -> MutexGuard<'_, Connection> {
+        self.conn.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+#[derive(Debug)]
+pub enum DatabaseError {
+    Io { path: PathBuf, source: io::Error },
+    Open { path: PathBuf, source: rusqlite::Error },
+    Migration { path: PathBuf, source: MigrationError },
+    Backup { path: PathBuf, source: BackupError },
+    Copy { path: PathBuf, source: rusqlite::Error },
+}
+
+impl fmt::Display for DatabaseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DatabaseError::Io { path, source } => {
+                write!(f, "Cannot access {}: {}", path.display(), source)
+            }
+            DatabaseError::Open { path, source } => {
+                write!(f, "Cannot open the database at {}: {}", path.display(), source)
+            }
+            DatabaseError::Migration { path, source } => {
+                write!(f, "Cannot upgrade the database at {}: {}", path.display(), source)
+            }
+            DatabaseError::Backup { path, source } => write!(
+                f,
+                "Cannot back up the database at {} before upgrading it: {}",
+                path.display(),
+                source
+            ),
+            DatabaseError::Copy { path, source } => {
+                write!(f, "Cannot copy the database to {}: {}", path.display(), source)
+            }
+        }
+    }
+}
+
+impl std::error::Error for DatabaseError {}
+
+/// Which rule picked the database path
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DbPathSource {
+    Environment,
+    Setting,
+    Default,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DbLocation {
+    pub path: PathBuf,
+    pub source: DbPathSource,
+}
+
+/// Picks the database file: `TIME_TRACKER_DB` wins, then the location saved
+/// in `data_dir`, then `time_tracker.db` inside `data_dir`.
+pub fn resolve_db_location(env_override: Option<OsString>, data_dir: &Path) -> DbLocation {
+    if let Some(path) = env_override.filter(|p| !p.is_empty()) {
+        return DbLocation {
+            path: PathBuf::from(path),
+            source: DbPathSource::Environment,
+        };
+    }
+    if let Some(path) = read_location_setting(data_dir) {
+        return DbLocation {
+            path,
+            source: DbPathSource::Setting,
+        };
+    }
+    DbLocation {
+        path: default_db_path(data_dir),
+        source: DbPathSource::Default,
     }
 }
 
-fn get_db_path() -> PathBuf {
-    let home = std::env::var("HOME").unwrap_or_else(|_| ".".to_string());
-    PathBuf::from(home)
-        .join("Library")
+pub fn default_db_path(data_dir: &Path) -> PathBuf {
+    data_dir.join(DB_FILE_NAME)
+}
+
+/// Where builds before the configurable location kept the database, on
+/// every platform
+pub fn legacy_db_path(home: &Path) -> PathBuf {
+    home.join("Library")
         .join("Application Support")
         .join("com.timetracker.app")
-        .join("time_tracker.db")
+        .join(DB_FILE_NAME)
+}
+
+pub fn read_location_setting(data_dir: &Path) -> Option<PathBuf> {
+    read_path_file(&data_dir.join(LOCATION_FILE_NAME))
+}
+
+/// Saves the database path to use from the next start, or goes back to the
+/// default when `path` is `None`.
+pub fn write_location_setting(data_dir: &Path, path: Option<&Path>) -> io::Result<()> {
+    write_path_file(data_dir, LOCATION_FILE_NAME, path)
+}
+
+/// Marks `from` as the database to copy to the saved location at the next
+/// start, or clears the mark when `from` is `None`
+pub fn write_pending_copy(data_dir: &Path, from: Option<&Path>) -> io::Result<()> {
+    write_path_file(data_dir, PENDING_COPY_FILE_NAME, from)
+}
+
+/// Copies the database marked by `write_pending_copy` to the saved location,
+/// unless a file is already there. Runs at startup, while nothing has the
+/// old database open, so everything written to it comes along. Returns the
+/// paths copied from and to, if anything was copied.
+pub fn copy_pending_database(data_dir: &Path) -> Result<Option<(PathBuf, PathBuf)>, DatabaseError> {
+    let Some(from) = read_path_file(&data_dir.join(PENDING_COPY_FILE_NAME)) else {
+        return Ok(None);
+    };
+    let target = resolve_db_location(None, data_dir).path;
+    let copied = if from == target || target.exists() || !from.exists() {
+        None
+    } else {
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent).map_err(|source| DatabaseError::Io {
+                path: parent.to_path_buf(),
+                source,
+            })?;
+        }
+        let copy_error = |source| DatabaseError::Copy {
+            path: target.clone(),
+            source,
+        };
+        let conn = Connection::open_with_flags(&from, OpenFlags::SQLITE_OPEN_READ_ONLY)
+            .map_err(copy_error)?;
+        conn.execute("VACUUM INTO ?1", [target.to_string_lossy()])
+            .map_err(copy_error)?;
+        Some((from, target))
+    };
+    write_pending_copy(data_dir, None).map_err(|source| DatabaseError::Io {
+        path: data_dir.join(PENDING_COPY_FILE_NAME),
+        source,
+    })?;
+    Ok(copied)
+}
+
+fn read_path_file(file: &Path) -> Option<PathBuf> {
+    let content = fs::read_to_string(file).ok()?;
+    let path = content.trim();
+    (!path.is_empty()).then(|| PathBuf::from(path))
+}
+
+/// Writes `path` to `name` in `data_dir`, or removes the file for `None`
+fn write_path_file(data_dir: &Path, name: &str, path: Option<&Path>) -> io::Result<()> {
+    let file = data_dir.join(name);
+    match path {
+        Some(path) => {
+            fs::create_dir_all(data_dir)?;
+            fs::write(file, path.to_string_lossy().as_bytes())
+        }
+        None => match fs::remove_file(file) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        },
+    }
+}
+
+/// Moves a database left at `legacy` by an older build to `target`, along
+/// with its WAL files. Does nothing when `target` already exists, so a
+/// database at the new location is never overwritten. Returns whether
+/// anything was moved.
+pub fn migrate_legacy_database(legacy: &Path, target: &Path) -> Result<bool, DatabaseError> {
+    if legacy == target || target.exists() || !legacy.exists() {
+        return Ok(false);
+    }
+    if let Some(parent) = target.parent() {
+        fs::create_dir_all(parent).map_err(|source| DatabaseError::Io {
+            path: parent.to_path_buf(),
+            source,
+        })?;
+    }
+
+    // Sidecars first: the main file appearing at `target` is what marks the
+    // move as done
+    for suffix in SIDECAR_SUFFIXES {
+        let from = with_suffix(legacy, suffix);
+        if from.exists() {
+            move_file(&from, &with_suffix(target, suffix))?;
+        }
+    }
+    move_file(legacy, target)?;
+    Ok(true)
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_os_string();
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+/// Renames `from` to `to`, copying instead when they are on different
+/// file systems
+fn move_file(from: &Path, to: &Path) -> Result<(), DatabaseError> {
+    if fs::rename(from, to).is_ok() {
+        return Ok(());
+    }
+    fs::copy(from, to)
+        .and_then(|_| fs::remove_file(from))
+        .map_err(|source| DatabaseError::Io {
+            path: from.to_path_buf(),
+            source,
+        })
 }
 
 #[cfg(test)]
@@ -44,7 +295,13 @@ impl Database {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::db::migrations;
+
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("db-test-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
 
     #[test]
     fn test_new_in_memory_creates_database() {
@@ -63,4 +320,138 @@ mod tests {
             .unwrap();
         assert_eq!(count, 0);
     }
+
+    #[test]
+    fn test_resolve_db_location_precedence() {
+        let dir = scratch_dir("resolve");
+
+        let location = resolve_db_location(None, &dir);
+        assert_eq!(location.source, DbPathSource::Default);
+        assert_eq!(location.path, dir.join("time_tracker.db"));
+
+        write_location_setting(&dir, Some(Path::new("/data/custom.db"))).unwrap();
+        let location = resolve_db_location(Some(OsString::new()), &dir);
+        assert_eq!(location.source, DbPathSource::Setting);
+        assert_eq!(location.path, PathBuf::from("/data/custom.db"));
+
+        let location = resolve_db_location(Some("/tmp/env.db".into()), &dir);
+        assert_eq!(location.source, DbPathSource::Environment);
+        assert_eq!(location.path, PathBuf::from("/tmp/env.db"));
+
+        write_location_setting(&dir, None).unwrap();
+        write_location_setting(&dir, None).unwrap();
+        assert_eq!(resolve_db_location(None, &dir).source, DbPathSource::Default);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_copy_pending_database_to_new_location() {
+        let dir = scratch_dir("pending");
+        let old = dir.join("old.db");
+        let new = dir.join("moved").join("time_tracker.db");
+        Connection::open(&old)
+            .unwrap()
+            .execute_batch("CREATE TABLE scratch (value TEXT); INSERT INTO scratch VALUES ('kept');")
+            .unwrap();
+        write_location_setting(&dir, Some(&new)).unwrap();
+        write_pending_copy(&dir, Some(&old)).unwrap();
+
+        assert_eq!(copy_pending_database(&dir).unwrap(), Some((old.clone(), new.clone())));
+        let value: String = Connection::open(&new)
+            .unwrap()
+            .query_row("SELECT value FROM scratch", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(value, "kept");
+
+        // Done once; a file now at the new location is never overwritten
+        write_pending_copy(&dir, Some(&old)).unwrap();
+        assert_eq!(copy_pending_database(&dir).unwrap(), None);
+        assert_eq!(copy_pending_database(&dir).unwrap(), None);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_migrate_legacy_database_moves_wal_files() {
+        let dir = scratch_dir("legacy");
+        let legacy = legacy_db_path(&dir.join("home"));
+        let target = dir.join("data").join("time_tracker.db");
+        fs::create_dir_all(legacy.parent().unwrap()).unwrap();
+        fs::write(&legacy, "main").unwrap();
+        fs::write(with_suffix(&legacy, "-wal"), "wal").unwrap();
+
+        assert!(migrate_legacy_database(&legacy, &target).unwrap());
+        assert!(!legacy.exists());
+        assert_eq!(fs::read_to_string(&target).unwrap(), "main");
+        assert_eq!(fs::read_to_string(with_suffix(&target, "-wal")).unwrap(), "wal");
+        assert!(!with_suffix(&target, "-shm").exists());
+
+        // Already moved: nothing left to do
+        assert!(!migrate_legacy_database(&legacy, &target).unwrap());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_migrate_legacy_database_keeps_existing_target() {
+        let dir = scratch_dir("keep");
+        let legacy = dir.join("old.db");
+        let target = dir.join("new.db");
+        fs::write(&legacy, "old").unwrap();
+        fs::write(&target, "new").unwrap();
+
+        assert!(!migrate_legacy_database(&legacy, &target).unwrap());
+        assert_eq!(fs::read_to_string(&legacy).unwrap(), "old");
+        assert_eq!(fs::read_to_string(&target).unwrap(), "new");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_open_creates_directory_and_migrates() {
+        let dir = scratch_dir("open");
+        let path = dir.join("nested").join("time_tracker.db");
+        let backups = BackupStore::new(dir.join("backups"));
+
+        let db = Database::open(&path, &backups).unwrap();
+        let conn = db.conn.lock().unwrap();
+        let count: i64 = conn
+            .query_row("SELECT COUNT(*) FROM time_entries", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(count, 0);
+        drop(conn);
+        drop(db);
+        assert!(backups.list().unwrap().is_empty(), "nothing to back up yet");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_open_backs_up_before_migrating() {
+        let dir = scratch_dir("premigration");
+        let path = dir.join("time_tracker.db");
+        let backups = BackupStore::new(dir.join("backups"));
+        Connection::open(&path)
+            .unwrap()
+            .execute_batch("CREATE TABLE scratch (value TEXT)")
+            .unwrap();
+
+        Database::open(&path, &backups).unwrap();
+        let list = backups.list().unwrap();
+        assert_eq!(list.len(), 1);
+        assert_eq!(list[0].reason, BackupReason::PreMigration);
+
+        // Up to date now, so no further backups
+        Database::open(&path, &backups).unwrap();
+        assert_eq!(backups.list().unwrap().len(), 1);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_open_reports_unusable_path() {
+        let dir = scratch_dir("unusable");
+
+        // A directory cannot be opened as a database file
+        let backups = BackupStore::new(dir.join("backups"));
+        let err = Database::open(&dir, &backups).err().unwrap();
+        assert!(matches!(err, DatabaseError::Open { .. }));
+        assert!(err.to_string().contains(&dir.display().to_string()));
+        fs::remove_dir_all(&dir).unwrap();
+    }
 }
@@ -1,6 +1,209 @@
-use rusqlite::Connection;
+use rusqlite::{Connection, OptionalExtension};
+use std::fmt;
 
-pub fn run_migrations(conn: &Connection) -> Result<(), rusqlite::Error> {
+/// A single, numbered schema change. Versions start at 1 and must be
+/// contiguous; once released, a migration must never be edited.
+#[derive(Clone, Copy)]
+struct Migration {
+    version: u32,
+    description: &'static str,
+    up: fn(&Connection) -> Result<(), rusqlite::Error>,
+}
+
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "initial schema",
+        up: initial_schema,
+    },
+    Migration {
+        version: 2,
+        description: "user-defined categories",
+        up: create_categories,
+    },
+    Migration {
+        version: 3,
+        description: "clients and projects",
+        up: create_clients_and_projects,
+    },
+    Migration {
+        version: 4,
+        description: "tags",
+        up: create_tags,
+    },
+    Migration {
+        version: 5,
+        description: "paused category",
+        up: create_paused_category,
+    },
+    Migration {
+        version: 6,
+        description: "work schedule",
+        up: create_work_schedule,
+    },
+    Migration {
+        version: 7,
+        description: "repair settings",
+        up: repair_settings,
+    },
+    Migration {
+        version: 8,
+        description: "timer heartbeat",
+        up: create_timer_heartbeat,
+    },
+    Migration {
+        version: 9,
+        description: "idle backend setting",
+        up: add_idle_backend_setting,
+    },
+    Migration {
+        version: 10,
+        description: "away reasons",
+        up: add_away_reason,
+    },
+    Migration {
+        version: 11,
+        description: "merge away entries",
+        up: merge_away_entries,
+    },
+    Migration {
+        version: 12,
+        description: "backup count setting",
+        up: add_backup_count_setting,
+    },
+    Migration {
+        version: 13,
+        description: "soft delete and change journal",
+        up: add_soft_delete,
+    },
+    Migration {
+        version: 14,
+        description: "entry history",
+        up: create_entry_history,
+    },
+    Migration {
+        version: 15,
+        description: "notes search index",
+        up: create_notes_search,
+    },
+    Migration {
+        version: 16,
+        description: "journal after state",
+        up: add_change_after_state,
+    },
+    Migration {
+        version: 17,
+        description: "purge history source",
+        up: add_purge_history_source,
+    },
+];
+
+#[derive(Debug)]
+pub enum MigrationError {
+    DatabaseError(rusqlite::Error),
+    /// The database was written by a newer build than this one.
+    DatabaseTooNew { found: u32, supported: u32 },
+}
+
+impl fmt::Display for MigrationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MigrationError::DatabaseError(e) => write!(f, "Database error: {}", e),
+            MigrationError::DatabaseTooNew { found, supported } => write!(
+                f,
+                "Database schema version {} is newer than the latest supported version {}",
+                found, supported
+            ),
+        }
+    }
+}
+
+impl std::error::Error for MigrationError {}
+
+impl From<rusqlite::Error> for MigrationError {
+    fn from(err: rusqlite::Error) -> Self {
+        MigrationError::DatabaseError(err)
+    }
+}
+
+/// Latest schema version known to this build
+pub fn latest_version() -> u32 {
+    MIGRATIONS.last().map(|m| m.version).unwrap_or(0)
+}
+
+/// Schema version recorded in the database, 0 if none has been applied
+pub fn current_version(conn: &Connection) -> Result<u32, rusqlite::Error> {
+    let has_table: Option<String> = conn
+        .query_row(
+            "SELECT name FROM sqlite_master WHERE type = 'table' AND name = 'schema_version'",
+            [],
+            |row| row.get(0),
+        )
+        .optional()?;
+    if has_table.is_none() {
+        return Ok(0);
+    }
+
+    let version: Option<u32> =
+        conn.query_row("SELECT MAX(version) FROM schema_version", [], |row| row.get(0))?;
+    Ok(version.unwrap_or(0))
+}
+
+/// Returns true if `run_migrations` would apply at least one step
+pub fn has_pending(conn: &Connection) -> Result<bool, rusqlite::Error> {
+    Ok(current_version(conn)? < latest_version())
+}
+
+pub fn run_migrations(conn: &Connection) -> Result<(), MigrationError> {
+    migrate(conn, MIGRATIONS, latest_version())
+}
+
+/// Applies every pending migration up to and including `target`, each in its
+/// own transaction together with its `schema_version` row.
+fn migrate(conn: &Connection, migrations: &[Migration], target: u32) -> Result<(), MigrationError> {
+    conn.execute_batch(
+        "
+        CREATE TABLE IF NOT EXISTS schema_version (
+            version INTEGER PRIMARY KEY,
+            description TEXT NOT NULL,
+            applied_at INTEGER DEFAULT (strftime('%s', 'now'))
+        );
+        ",
+    )?;
+
+    let current = current_version(conn)?;
+    let latest = migrations.last().map(|m| m.version).unwrap_or(0);
+    if current > latest {
+        return Err(MigrationError::DatabaseTooNew {
+            found: current,
+            supported: latest,
+        });
+    }
+
+    for migration in migrations
+        .iter()
+        .filter(|m| m.version > current && m.version <= target)
+    {
+        let tx = conn.unchecked_transaction()?;
+        (migration.up)(&tx)?;
+        tx.execute(
+            "INSERT INTO schema_version (version, description) VALUES (?1, ?2)",
+            (migration.version, migration.description),
+        )?;
+        tx.commit()?;
+        log::info!(
+            "Applied migration {}: {}",
+            migration.version,
+            migration.description
+        );
+    }
+
+    Ok(())
+}
+
+/// Version 1: the schema that shipped before versioned migrations existed.
+/// Uses `IF NOT EXISTS` so databases created by those builds adopt it as-is.
+fn initial_schema(conn: &Connection) -> Result<(), rusqlite::Error> {
     conn.execute_batch(
         "
         CREATE TABLE IF NOT EXISTS time_entries (
@@ -36,16 +239,470 @@ pub fn run_migrations(conn: &Connection) -> Result<(), rusqlite::Error> {
         INSERT OR IGNORE INTO settings (key, value) VALUES ('notification_enabled', 'true');
         INSERT OR IGNORE INTO settings (key, value) VALUES ('notification_sound', 'default');
         ",
+    )
+}
+
+/// Version 2: moves the built-in categories into a table and registers any
+/// free-text category already used by an entry so existing rows stay valid.
+fn create_categories(conn: &Connection) -> Result<(), rusqlite::Error> {
+    conn.execute_batch(
+        "
+        CREATE TABLE categories (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT NOT NULL,
+            slug TEXT NOT NULL UNIQUE,
+            color TEXT NOT NULL DEFAULT '#6B7280',
+            icon TEXT,
+            is_productive INTEGER DEFAULT 1,
+            archived INTEGER DEFAULT 0,
+            sort_order INTEGER DEFAULT 0,
+            created_at INTEGER DEFAULT (strftime('%s', 'now'))
+        );
+
+        INSERT INTO categories (name, slug, color, is_productive, sort_order) VALUES
+            ('Deep Work', 'deep_work', '#4F46E5', 1, 0),
+            ('Meetings', 'meetings', '#7C3AED', 1, 1),
+            ('Admin', 'admin', '#0891B2', 1, 2),
+            ('Break', 'break', '#059669', 0, 3),
+            ('Away', 'away', '#6B7280', 0, 4);
+
+        INSERT OR IGNORE INTO categories (name, slug, sort_order)
+            SELECT DISTINCT category, category, 5 FROM time_entries;
+        ",
+    )
+}
+
+/// Version 3: clients own projects, and entries may optionally belong to a project
+fn create_clients_and_projects(conn: &Connection) -> Result<(), rusqlite::Error> {
+    conn.execute_batch(
+        "
+        CREATE TABLE clients (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT NOT NULL UNIQUE,
+            archived INTEGER DEFAULT 0,
+            created_at INTEGER DEFAULT (strftime('%s', 'now'))
+        );
+
+        CREATE TABLE projects (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            client_id INTEGER REFERENCES clients(id),
+            name TEXT NOT NULL,
+            archived INTEGER DEFAULT 0,
+            created_at INTEGER DEFAULT (strftime('%s', 'now')),
+            UNIQUE (client_id, name)
+        );
+
+        CREATE INDEX idx_projects_client_id ON projects(client_id);
+
+        ALTER TABLE time_entries ADD COLUMN project_id INTEGER REFERENCES projects(id);
+
+        CREATE INDEX idx_time_entries_project_id ON time_entries(project_id);
+        ",
+    )
+}
+
+/// Version 4: many-to-many tags, backfilled from `#hashtags` in existing notes
+fn create_tags(conn: &Connection) -> Result<(), rusqlite::Error> {
+    conn.execute_batch(
+        "
+        CREATE TABLE tags (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT NOT NULL UNIQUE,
+            created_at INTEGER DEFAULT (strftime('%s', 'now'))
+        );
+
+        CREATE TABLE entry_tags (
+            entry_id INTEGER NOT NULL REFERENCES time_entries(id) ON DELETE CASCADE,
+            tag_id INTEGER NOT NULL REFERENCES tags(id) ON DELETE CASCADE,
+            PRIMARY KEY (entry_id, tag_id)
+        );
+
+        CREATE INDEX idx_entry_tags_tag_id ON entry_tags(tag_id);
+        ",
+    )?;
+
+    let notes: Vec<(i64, String)> = conn
+        .prepare("SELECT id, notes FROM time_entries WHERE notes LIKE '%#%'")?
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<Result<Vec<_>, _>>()?;
+    for (entry_id, text) in notes {
+        for name in v4_hashtags(&text) {
+            conn.execute("INSERT OR IGNORE INTO tags (name) VALUES (?1)", [&name])?;
+            conn.execute(
+                "INSERT OR IGNORE INTO entry_tags (entry_id, tag_id)
+                 SELECT ?1, id FROM tags WHERE name = ?2",
+                (entry_id, &name),
+            )?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Hashtag parsing as version 4 shipped it. A frozen copy, so changes to
+/// `tag::extract_hashtags` never change what this migration does.
+fn v4_hashtags(text: &str) -> Vec<String> {
+    fn is_tag_char(c: char) -> bool {
+        c.is_alphanumeric() || c == '_' || c == '-' || c == '/'
+    }
+
+    let mut tags: Vec<String> = Vec::new();
+    let mut prev: Option<char> = None;
+    let mut chars = text.char_indices().peekable();
+
+    while let Some((i, c)) = chars.next() {
+        let starts_tag = c == '#' && prev.is_none_or(|p| !is_tag_char(p) && p != '#');
+        prev = Some(c);
+        if !starts_tag {
+            continue;
+        }
+
+        let start = i + c.len_utf8();
+        let mut end = start;
+        while let Some(&(j, next)) = chars.peek() {
+            if !is_tag_char(next) {
+                break;
+            }
+            end = j + next.len_utf8();
+            prev = Some(next);
+            chars.next();
+        }
+
+        let name = text[start..end].trim_end_matches(['-', '/']).to_lowercase();
+        if !name.is_empty() && name.chars().all(is_tag_char) && !tags.contains(&name) {
+            tags.push(name);
+        }
+    }
+
+    tags
+}
+
+/// Version 5: built-in category for intervals the timer spent paused or snoozed
+fn create_paused_category(conn: &Connection) -> Result<(), rusqlite::Error> {
+    conn.execute_batch(
+        "
+        INSERT OR IGNORE INTO categories (name, slug, color, is_productive, sort_order)
+            SELECT 'Paused', 'paused', '#9CA3AF', 0, COALESCE(MAX(sort_order), -1) + 1 FROM categories;
+        ",
+    )
+}
+
+/// Version 6: weekly working hours and days off. With no hours configured the
+/// timer keeps prompting around the clock.
+fn create_work_schedule(conn: &Connection) -> Result<(), rusqlite::Error> {
+    conn.execute_batch(
+        "
+        CREATE TABLE work_hours (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            weekday INTEGER NOT NULL CHECK (weekday BETWEEN 0 AND 6),
+            start_minute INTEGER NOT NULL,
+            end_minute INTEGER NOT NULL,
+            CHECK (start_minute >= 0 AND start_minute < end_minute AND end_minute <= 1440)
+        );
+
+        CREATE INDEX idx_work_hours_weekday ON work_hours(weekday);
+
+        CREATE TABLE days_off (
+            date TEXT PRIMARY KEY,
+            label TEXT,
+            created_at INTEGER DEFAULT (strftime('%s', 'now'))
+        );
+        ",
+    )
+}
+
+/// Version 7: settings used to accept any string. Values that fail today's
+/// validation go back to their default, valid ones are normalized (" Glass"
+/// becomes "glass") and missing keys are filled in.
+/// Keys and validation are frozen as they were then; settings added later
+/// insert their own defaults.
+fn repair_settings(conn: &Connection) -> Result<(), rusqlite::Error> {
+    const DEFAULTS: [(&str, &str); 4] = [
+        ("interval_minutes", "15"),
+        ("idle_threshold_minutes", "5"),
+        ("notification_enabled", "true"),
+        ("notification_sound", "default"),
+    ];
+
+    for (key, default) in DEFAULTS {
+        let stored: Option<String> = conn
+            .query_row("SELECT value FROM settings WHERE key = ?1", [key], |row| row.get(0))
+            .optional()?;
+        let repaired = stored
+            .as_deref()
+            .and_then(|value| v7_normalize_setting(key, value))
+            .unwrap_or_else(|| default.to_string());
+        if stored.as_deref() != Some(repaired.as_str()) {
+            conn.execute(
+                "INSERT OR REPLACE INTO settings (key, value) VALUES (?1, ?2)",
+                (key, repaired),
+            )?;
+        }
+    }
+    Ok(())
+}
+
+/// Setting validation as version 7 shipped it: the normalized value, or
+/// `None` when the stored one is invalid
+fn v7_normalize_setting(key: &str, value: &str) -> Option<String> {
+    const SOUNDS: [&str; 10] = [
+        "default", "glass", "hero", "morse", "ping", "pop", "purr", "sosumi", "submarine", "tink",
+    ];
+
+    let value = value.trim();
+    match key {
+        "interval_minutes" => {
+            let minutes: u64 = value.parse().ok()?;
+            (minutes != 0 && 60 % minutes == 0).then(|| minutes.to_string())
+        }
+        "idle_threshold_minutes" => {
+            let minutes: u32 = value.parse().ok()?;
+            (1..=120).contains(&minutes).then(|| minutes.to_string())
+        }
+        "notification_enabled" => matches!(value, "true" | "false").then(|| value.to_string()),
+        "notification_sound" => SOUNDS
+            .into_iter()
+            .find(|sound| sound.eq_ignore_ascii_case(value))
+            .map(str::to_string),
+        _ => None,
+    }
+}
+
+/// Version 8: when the timer last handled a tick, so intervals lost to sleep or
+/// to the app not running can be found afterwards
+fn create_timer_heartbeat(conn: &Connection) -> Result<(), rusqlite::Error> {
+    conn.execute_batch(
+        "
+        CREATE TABLE timer_heartbeat (
+            id INTEGER PRIMARY KEY CHECK (id = 1),
+            last_tick_at INTEGER NOT NULL
+        );
+        ",
+    )
+}
+
+/// Version 9: which idle detection backend to use, auto-detected by default
+fn add_idle_backend_setting(conn: &Connection) -> Result<(), rusqlite::Error> {
+    conn.execute(
+        "INSERT OR IGNORE INTO settings (key, value) VALUES ('idle_backend', 'auto')",
+        [],
     )?;
+    Ok(())
+}
 
+/// Version 10: away entries remember whether the user was idle, had locked the
+/// screen or was suspending. Every earlier away entry came from idle detection.
+fn add_away_reason(conn: &Connection) -> Result<(), rusqlite::Error> {
+    conn.execute_batch(
+        "
+        ALTER TABLE time_entries ADD COLUMN away_reason TEXT
+            CHECK (away_reason IN ('idle', 'locked', 'suspended'));
+        UPDATE time_entries SET away_reason = 'idle' WHERE is_away = 1;
+        ",
+    )
+}
+
+/// Version 11: the timer used to write one away row per interval. Runs of
+/// back-to-back away rows with the same reason that nobody has annotated
+/// become a single entry spanning the whole run.
+fn merge_away_entries(conn: &Connection) -> Result<(), rusqlite::Error> {
+    let rows: Vec<(i64, i64, i64, Option<String>)> = conn
+        .prepare(
+            "SELECT id, timestamp, duration_minutes, away_reason FROM time_entries
+             WHERE category = 'away' AND is_away = 1 AND notes IS NULL AND project_id IS NULL
+             ORDER BY timestamp, id",
+        )?
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)))?
+        .collect::<Result<_, _>>()?;
+
+    // (id, end, reason) of the block being extended
+    let mut block: Option<(i64, i64, Option<String>)> = None;
+    for (id, timestamp, duration_minutes, reason) in rows {
+        let end = timestamp + duration_minutes * 60;
+        match &mut block {
+            Some((block_id, block_end, block_reason))
+                if *block_end == timestamp && *block_reason == reason =>
+            {
+                conn.execute(
+                    "UPDATE time_entries SET duration_minutes = duration_minutes + ?1 WHERE id = ?2",
+                    (duration_minutes, *block_id),
+                )?;
+                conn.execute("DELETE FROM time_entries WHERE id = ?1", [id])?;
+                *block_end = end;
+            }
+            _ => block = Some((id, end, reason)),
+        }
+    }
     Ok(())
 }
 
+/// Version 12: how many rotated database backups to keep
+fn add_backup_count_setting(conn: &Connection) -> Result<(), rusqlite::Error> {
+    conn.execute(
+        "INSERT OR IGNORE INTO settings (key, value) VALUES ('backup_count', '7')",
+        [],
+    )?;
+    Ok(())
+}
+
+/// Version 13: deleted entries go to the trash instead of disappearing, and
+/// user changes to entries are journaled so they can be undone. Each journal
+/// row holds the entry as it was before the change, or NULL if the change
+/// created it.
+fn add_soft_delete(conn: &Connection) -> Result<(), rusqlite::Error> {
+    conn.execute_batch(
+        "
+        ALTER TABLE time_entries ADD COLUMN deleted_at INTEGER;
+        CREATE INDEX idx_time_entries_deleted_at ON time_entries(deleted_at);
+
+        CREATE TABLE entry_changes (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            action TEXT NOT NULL CHECK (action IN ('create', 'update', 'delete', 'restore')),
+            created_at INTEGER NOT NULL DEFAULT (strftime('%s', 'now')),
+            undone_at INTEGER
+        );
+
+        CREATE TABLE entry_change_rows (
+            change_id INTEGER NOT NULL REFERENCES entry_changes(id) ON DELETE CASCADE,
+            entry_id INTEGER NOT NULL,
+            before TEXT,
+            PRIMARY KEY (change_id, entry_id)
+        );
+
+        INSERT OR IGNORE INTO settings (key, value) VALUES ('trash_retention_days', '30');
+        ",
+    )
+}
+
+/// Version 14: every change to an entry is kept as one row per changed field,
+/// with its old and new value as text and where the change came from
+fn create_entry_history(conn: &Connection) -> Result<(), rusqlite::Error> {
+    conn.execute_batch(
+        "
+        CREATE TABLE time_entry_history (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            entry_id INTEGER NOT NULL,
+            field TEXT NOT NULL,
+            old_value TEXT,
+            new_value TEXT,
+            source TEXT NOT NULL CHECK (source IN (
+                'prompt', 'retroactive', 'edit', 'timer', 'import', 'bulk_edit', 'undo'
+            )),
+            changed_at INTEGER NOT NULL DEFAULT (strftime('%s', 'now'))
+        );
+        CREATE INDEX idx_time_entry_history_entry_id ON time_entry_history(entry_id);
+        ",
+    )
+}
+
+/// Version 15: a full-text index over entry notes. It stores no text of its
+/// own; the triggers keep it in step with `time_entries`, and existing notes
+/// are indexed by rebuilding it once.
+fn create_notes_search(conn: &Connection) -> Result<(), rusqlite::Error> {
+    conn.execute_batch(
+        "
+        CREATE VIRTUAL TABLE time_entries_fts USING fts5(
+            notes,
+            content = 'time_entries',
+            content_rowid = 'id',
+            tokenize = 'unicode61 remove_diacritics 2'
+        );
+
+        CREATE TRIGGER time_entries_fts_insert AFTER INSERT ON time_entries BEGIN
+            INSERT INTO time_entries_fts (rowid, notes) VALUES (new.id, new.notes);
+        END;
+        CREATE TRIGGER time_entries_fts_delete AFTER DELETE ON time_entries BEGIN
+            INSERT INTO time_entries_fts (time_entries_fts, rowid, notes)
+            VALUES ('delete', old.id, old.notes);
+        END;
+        CREATE TRIGGER time_entries_fts_update AFTER UPDATE OF notes ON time_entries BEGIN
+            INSERT INTO time_entries_fts (time_entries_fts, rowid, notes)
+            VALUES ('delete', old.id, old.notes);
+            INSERT INTO time_entries_fts (rowid, notes) VALUES (new.id, new.notes);
+        END;
+
+        INSERT INTO time_entries_fts (time_entries_fts) VALUES ('rebuild');
+        ",
+    )
+}
+
+/// Version 16: each journal row also holds the entry as the change left it,
+/// so undo can tell when something has changed the entry since. Rows
+/// journaled before this have none.
+fn add_change_after_state(conn: &Connection) -> Result<(), rusqlite::Error> {
+    conn.execute_batch("ALTER TABLE entry_change_rows ADD COLUMN after TEXT;")
+}
+
+/// Version 17: entries removed for good keep their history, with the removal
+/// as its last rows. SQLite cannot change a CHECK constraint in place, so the
+/// history table is rebuilt to allow the new source.
+fn add_purge_history_source(conn: &Connection) -> Result<(), rusqlite::Error> {
+    conn.execute_batch(
+        "
+        CREATE TABLE time_entry_history_new (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            entry_id INTEGER NOT NULL,
+            field TEXT NOT NULL,
+            old_value TEXT,
+            new_value TEXT,
+            source TEXT NOT NULL CHECK (source IN (
+                'prompt', 'retroactive', 'edit', 'timer', 'import', 'bulk_edit', 'undo', 'purge'
+            )),
+            changed_at INTEGER NOT NULL DEFAULT (strftime('%s', 'now'))
+        );
+        INSERT INTO time_entry_history_new
+            (id, entry_id, field, old_value, new_value, source, changed_at)
+        SELECT id, entry_id, field, old_value, new_value, source, changed_at
+        FROM time_entry_history;
+        DROP TABLE time_entry_history;
+        ALTER TABLE time_entry_history_new RENAME TO time_entry_history;
+        CREATE INDEX idx_time_entry_history_entry_id ON time_entry_history(entry_id);
+        ",
+    )
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use rusqlite::Connection;
 
+    /// Schema created by builds that predate the `schema_version` table
+    const LEGACY_FIXTURE: &str = "
+        CREATE TABLE time_entries (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            timestamp INTEGER NOT NULL,
+            category TEXT NOT NULL,
+            duration_minutes INTEGER DEFAULT 15,
+            is_away INTEGER DEFAULT 0,
+            is_retroactive INTEGER DEFAULT 0,
+            notes TEXT,
+            created_at INTEGER DEFAULT (strftime('%s', 'now'))
+        );
+        CREATE INDEX idx_time_entries_timestamp ON time_entries(timestamp);
+        CREATE TABLE missed_prompts (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            timestamp INTEGER NOT NULL UNIQUE,
+            reason TEXT,
+            created_at INTEGER DEFAULT (strftime('%s', 'now'))
+        );
+        CREATE INDEX idx_missed_prompts_timestamp ON missed_prompts(timestamp);
+        CREATE TABLE settings (
+            key TEXT PRIMARY KEY,
+            value TEXT NOT NULL
+        );
+        INSERT INTO settings (key, value) VALUES ('interval_minutes', '30');
+        INSERT INTO settings (key, value) VALUES ('idle_threshold_minutes', '5');
+        INSERT INTO settings (key, value) VALUES ('notification_enabled', 'false');
+        INSERT INTO settings (key, value) VALUES ('notification_sound', 'default');
+        INSERT INTO time_entries (timestamp, category, duration_minutes, notes)
+            VALUES (900, 'deep_work', 15, 'legacy row');
+        INSERT INTO time_entries (timestamp, category, duration_minutes, notes)
+            VALUES (1800, 'email', 15, 'triage #oncall and #Review');
+        INSERT INTO time_entries (timestamp, category, duration_minutes, is_away)
+            VALUES (2700, 'away', 15, 1);
+        INSERT INTO missed_prompts (timestamp, reason) VALUES (1800, 'idle');
+    ";
+
     fn setup_conn() -> Connection {
         let conn = Connection::open_in_memory().unwrap();
         run_migrations(&conn).unwrap();
@@ -65,6 +722,17 @@ mod tests {
         assert!(tables.contains(&"time_entries".to_string()));
         assert!(tables.contains(&"missed_prompts".to_string()));
         assert!(tables.contains(&"settings".to_string()));
+        assert!(tables.contains(&"schema_version".to_string()));
+        assert!(tables.contains(&"categories".to_string()));
+        assert!(tables.contains(&"clients".to_string()));
+        assert!(tables.contains(&"projects".to_string()));
+        assert!(tables.contains(&"tags".to_string()));
+        assert!(tables.contains(&"entry_tags".to_string()));
+        assert!(tables.contains(&"work_hours".to_string()));
+        assert!(tables.contains(&"days_off".to_string()));
+        assert!(tables.contains(&"timer_heartbeat".to_string()));
+        assert!(tables.contains(&"time_entry_history".to_string()));
+        assert!(tables.contains(&"time_entries_fts".to_string()));
     }
 
     #[test]
@@ -72,6 +740,10 @@ mod tests {
         let conn = Connection::open_in_memory().unwrap();
         run_migrations(&conn).unwrap();
         run_migrations(&conn).unwrap();
+        let rows: i64 = conn
+            .query_row("SELECT COUNT(*) FROM schema_version", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(rows, latest_version() as i64);
     }
 
     #[test]
@@ -89,6 +761,9 @@ mod tests {
         assert_eq!(get("idle_threshold_minutes"), "5");
         assert_eq!(get("notification_enabled"), "true");
         assert_eq!(get("notification_sound"), "default");
+        assert_eq!(get("idle_backend"), "auto");
+        assert_eq!(get("backup_count"), "7");
+        assert_eq!(get("trash_retention_days"), "30");
     }
 
     #[test]
@@ -103,5 +778,227 @@ mod tests {
             .collect();
         assert!(indexes.contains(&"idx_time_entries_timestamp".to_string()));
         assert!(indexes.contains(&"idx_missed_prompts_timestamp".to_string()));
+        assert!(indexes.contains(&"idx_time_entries_project_id".to_string()));
+        assert!(indexes.contains(&"idx_work_hours_weekday".to_string()));
+        assert!(indexes.contains(&"idx_time_entry_history_entry_id".to_string()));
+    }
+
+    #[test]
+    fn test_versions_are_contiguous() {
+        for (i, migration) in MIGRATIONS.iter().enumerate() {
+            assert_eq!(migration.version, i as u32 + 1);
+        }
+    }
+
+    #[test]
+    fn test_fresh_database_reaches_latest_version() {
+        let conn = setup_conn();
+        assert_eq!(current_version(&conn).unwrap(), latest_version());
+        assert!(!has_pending(&conn).unwrap());
+    }
+
+    #[test]
+    fn test_current_version_without_table() {
+        let conn = Connection::open_in_memory().unwrap();
+        assert_eq!(current_version(&conn).unwrap(), 0);
+        assert!(has_pending(&conn).unwrap());
+    }
+
+    #[test]
+    fn test_upgrades_legacy_database() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(LEGACY_FIXTURE).unwrap();
+
+        run_migrations(&conn).unwrap();
+
+        assert_eq!(current_version(&conn).unwrap(), latest_version());
+        let notes: String = conn
+            .query_row("SELECT notes FROM time_entries WHERE timestamp = 900", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(notes, "legacy row");
+        let interval: String = conn
+            .query_row("SELECT value FROM settings WHERE key = 'interval_minutes'", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(interval, "30");
+        let missed: i64 = conn
+            .query_row("SELECT COUNT(*) FROM missed_prompts", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(missed, 1);
+        let legacy_category: (String, bool) = conn
+            .query_row(
+                "SELECT name, archived FROM categories WHERE slug = 'email'",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .unwrap();
+        assert_eq!(legacy_category, ("email".to_string(), false));
+        let tags: Vec<String> = conn
+            .prepare(
+                "SELECT t.name FROM entry_tags et JOIN tags t ON t.id = et.tag_id
+                 JOIN time_entries te ON te.id = et.entry_id
+                 WHERE te.timestamp = 1800 ORDER BY t.name",
+            )
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .filter_map(|r| r.ok())
+            .collect();
+        assert_eq!(tags, vec!["oncall", "review"]);
+        let away_reason: Option<String> = conn
+            .query_row("SELECT away_reason FROM time_entries WHERE timestamp = 2700", [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_eq!(away_reason.as_deref(), Some("idle"));
+    }
+
+    #[test]
+    fn test_merges_adjacent_away_entries() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(LEGACY_FIXTURE).unwrap();
+        conn.execute_batch(
+            "
+            INSERT INTO time_entries (timestamp, category, duration_minutes, is_away)
+                VALUES (3600, 'away', 15, 1);
+            INSERT INTO time_entries (timestamp, category, duration_minutes, is_away)
+                VALUES (4500, 'away', 15, 1);
+            INSERT INTO time_entries (timestamp, category, duration_minutes, is_away, notes)
+                VALUES (5400, 'away', 15, 1, 'school run');
+            INSERT INTO time_entries (timestamp, category, duration_minutes, is_away)
+                VALUES (7200, 'away', 15, 1);
+            ",
+        )
+        .unwrap();
+
+        run_migrations(&conn).unwrap();
+
+        let away: Vec<(i64, i64)> = conn
+            .prepare("SELECT timestamp, duration_minutes FROM time_entries WHERE is_away = 1 ORDER BY timestamp")
+            .unwrap()
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        // 2700 (fixture), 3600 and 4500 merge; the annotated row and the one
+        // after a gap stay on their own
+        assert_eq!(away, vec![(2700, 45), (5400, 15), (7200, 15)]);
+    }
+
+    #[test]
+    fn test_seeds_builtin_categories() {
+        let conn = setup_conn();
+        let slugs: Vec<String> = conn
+            .prepare("SELECT slug FROM categories ORDER BY sort_order")
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .filter_map(|r| r.ok())
+            .collect();
+        assert_eq!(slugs, vec!["deep_work", "meetings", "admin", "break", "away", "paused"]);
+    }
+
+    #[test]
+    fn test_repairs_invalid_settings() {
+        let conn = Connection::open_in_memory().unwrap();
+        migrate(&conn, MIGRATIONS, 6).unwrap();
+        conn.execute_batch(
+            "UPDATE settings SET value = '0' WHERE key = 'interval_minutes';
+             UPDATE settings SET value = ' 30 ' WHERE key = 'idle_threshold_minutes';
+             UPDATE settings SET value = 'Glass' WHERE key = 'notification_sound';
+             DELETE FROM settings WHERE key = 'notification_enabled';",
+        )
+        .unwrap();
+
+        run_migrations(&conn).unwrap();
+
+        let value = |key: &str| -> String {
+            conn.query_row("SELECT value FROM settings WHERE key = ?1", [key], |row| row.get(0))
+                .unwrap()
+        };
+        assert_eq!(value("interval_minutes"), "15");
+        assert_eq!(value("idle_threshold_minutes"), "30");
+        assert_eq!(value("notification_sound"), "glass");
+        assert_eq!(value("notification_enabled"), "true");
+    }
+
+    #[test]
+    fn test_upgrades_from_every_version() {
+        for from in 1..=latest_version() {
+            let conn = Connection::open_in_memory().unwrap();
+            migrate(&conn, MIGRATIONS, from).unwrap();
+            assert_eq!(current_version(&conn).unwrap(), from);
+            conn.execute(
+                "INSERT INTO time_entries (timestamp, category, duration_minutes, notes)
+                 VALUES (900, 'deep_work', 15, 'from version')",
+                [],
+            )
+            .unwrap();
+
+            run_migrations(&conn).unwrap();
+
+            assert_eq!(current_version(&conn).unwrap(), latest_version(), "from v{}", from);
+            let count: i64 = conn
+                .query_row("SELECT COUNT(*) FROM time_entries", [], |row| row.get(0))
+                .unwrap();
+            assert_eq!(count, 1, "from v{}", from);
+            let indexed: i64 = conn
+                .query_row(
+                    "SELECT COUNT(*) FROM time_entries_fts WHERE time_entries_fts MATCH 'version'",
+                    [],
+                    |row| row.get(0),
+                )
+                .unwrap();
+            assert_eq!(indexed, 1, "from v{}", from);
+            let category: i64 = conn
+                .query_row("SELECT COUNT(*) FROM categories WHERE slug = 'deep_work'", [], |row| row.get(0))
+                .unwrap();
+            assert_eq!(category, 1, "from v{}", from);
+        }
+    }
+
+    #[test]
+    fn test_refuses_newer_database() {
+        let conn = setup_conn();
+        conn.execute(
+            "INSERT INTO schema_version (version, description) VALUES (?1, 'future')",
+            [latest_version() + 1],
+        )
+        .unwrap();
+
+        match run_migrations(&conn) {
+            Err(MigrationError::DatabaseTooNew { found, supported }) => {
+                assert_eq!(found, latest_version() + 1);
+                assert_eq!(supported, latest_version());
+            }
+            other => panic!("expected DatabaseTooNew, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_failed_migration_rolls_back() {
+        let conn = setup_conn();
+        let next = latest_version() + 1;
+        let failing = Migration {
+            version: next,
+            description: "broken",
+            up: |conn| {
+                conn.execute_batch("CREATE TABLE half_done (id INTEGER); SELECT * FROM missing_table;")
+            },
+        };
+        let mut migrations = MIGRATIONS.to_vec();
+        migrations.push(failing);
+
+        assert!(migrate(&conn, &migrations, next).is_err());
+
+        let exists: Option<String> = conn
+            .query_row(
+                "SELECT name FROM sqlite_master WHERE name = 'half_done'",
+                [],
+                |row| row.get(0),
+            )
+            .optional()
+            .unwrap();
+        assert!(exists.is_none());
+        assert_eq!(current_version(&conn).unwrap(), latest_version());
     }
 }
@@ -1,3 +1,4 @@
+pub mod backup;
 pub mod connection;
 pub mod migrations;
 pub mod models;
@@ -5,4 +6,8 @@ pub mod repositories;
 
 pub use connection::Database;
 pub use models::*;
-pub use repositories::{MissedPromptRepository, SettingsRepository, TimeEntryRepository};
+pub use repositories::{
+    CategoryRepository, ClientRepository, HeartbeatRepository, ImportRepository,
+    MissedPromptRepository, ProjectRepository, ReportRepository, ScheduleRepository,
+    SettingsRepository, TagRepository, TimeEntryRepository,
+};
@@ -9,7 +9,88 @@ pub struct TimeEntry {
     pub is_away: bool,
     pub is_retroactive: bool,
     pub notes: Option<String>,
+    pub project_id: Option<i64>,
     pub created_at: Option<i64>,
+    /// Why an auto-recorded away entry was recorded; `None` for everything else
+    #[serde(default)]
+    pub away_reason: Option<AwayReason>,
+    /// When the entry was moved to the trash; deleted entries are only
+    /// returned by trash listings
+    #[serde(default)]
+    pub deleted_at: Option<i64>,
+}
+
+/// What made the timer record an interval as away
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AwayReason {
+    /// No input for longer than the idle threshold
+    Idle,
+    /// The screen was locked
+    Locked,
+    /// The system was going to sleep
+    Suspended,
+}
+
+impl AwayReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AwayReason::Idle => "idle",
+            AwayReason::Locked => "locked",
+            AwayReason::Suspended => "suspended",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        [AwayReason::Idle, AwayReason::Locked, AwayReason::Suspended]
+            .into_iter()
+            .find(|reason| reason.as_str() == name)
+    }
+}
+
+/// Optional narrowing applied on top of a timestamp range
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct EntryFilter {
+    pub project_id: Option<i64>,
+    pub client_id: Option<i64>,
+}
+
+/// Flattened entry with project and client names resolved, used by exports
+/// and as the parsed form of imported rows
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportRow {
+    pub timestamp: i64,
+    pub category: String,
+    pub duration_minutes: i32,
+    pub is_away: bool,
+    pub is_retroactive: bool,
+    pub notes: Option<String>,
+    pub project: Option<String>,
+    pub client: Option<String>,
+}
+
+/// An entry together with the names of its project and client, as read a
+/// page at a time by exports
+#[derive(Debug, Clone)]
+pub struct ExportEntry {
+    pub entry: TimeEntry,
+    pub project: Option<String>,
+    pub client: Option<String>,
+}
+
+impl ExportEntry {
+    pub fn to_row(&self) -> ExportRow {
+        ExportRow {
+            timestamp: self.entry.timestamp,
+            category: self.entry.category.clone(),
+            duration_minutes: self.entry.duration_minutes,
+            is_away: self.entry.is_away,
+            is_retroactive: self.entry.is_retroactive,
+            notes: self.entry.notes.clone(),
+            project: self.project.clone(),
+            client: self.client.clone(),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -26,79 +107,500 @@ pub struct Setting {
     pub value: String,
 }
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
-pub enum Category {
-    DeepWork,
-    Meetings,
-    Admin,
-    Break,
-    Away,
+/// Sounds offered for notifications (macOS system sounds)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NotificationSound {
+    #[default]
+    Default,
+    Glass,
+    Hero,
+    Morse,
+    Ping,
+    Pop,
+    Purr,
+    Sosumi,
+    Submarine,
+    Tink,
+}
+
+impl NotificationSound {
+    pub const ALL: [NotificationSound; 10] = [
+        NotificationSound::Default,
+        NotificationSound::Glass,
+        NotificationSound::Hero,
+        NotificationSound::Morse,
+        NotificationSound::Ping,
+        NotificationSound::Pop,
+        NotificationSound::Purr,
+        NotificationSound::Sosumi,
+        NotificationSound::Submarine,
+        NotificationSound::Tink,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            NotificationSound::Default => "default",
+            NotificationSound::Glass => "glass",
+            NotificationSound::Hero => "hero",
+            NotificationSound::Morse => "morse",
+            NotificationSound::Ping => "ping",
+            NotificationSound::Pop => "pop",
+            NotificationSound::Purr => "purr",
+            NotificationSound::Sosumi => "sosumi",
+            NotificationSound::Submarine => "submarine",
+            NotificationSound::Tink => "tink",
+        }
+    }
+
+    /// Case-insensitive lookup by name, e.g. "Glass"
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|sound| sound.as_str().eq_ignore_ascii_case(name))
+    }
 }
 
-impl Category {
+/// Where idle time is read from. `Auto` picks the first backend that works
+/// on the running desktop.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IdleBackend {
+    #[default]
+    Auto,
+    /// CoreGraphics event source (macOS)
+    MacOs,
+    /// X11 screensaver extension (XScreenSaver)
+    X11,
+    /// `org.freedesktop.ScreenSaver` session idle time (KDE, most Wayland desktops)
+    ScreenSaver,
+    /// systemd-logind session idle hint
+    Logind,
+    /// Never report idle
+    None,
+}
+
+impl IdleBackend {
+    pub const ALL: [IdleBackend; 6] = [
+        IdleBackend::Auto,
+        IdleBackend::MacOs,
+        IdleBackend::X11,
+        IdleBackend::ScreenSaver,
+        IdleBackend::Logind,
+        IdleBackend::None,
+    ];
+
     pub fn as_str(&self) -> &'static str {
         match self {
-            Category::DeepWork => "deep_work",
-            Category::Meetings => "meetings",
-            Category::Admin => "admin",
-            Category::Break => "break",
-            Category::Away => "away",
+            IdleBackend::Auto => "auto",
+            IdleBackend::MacOs => "macos",
+            IdleBackend::X11 => "x11",
+            IdleBackend::ScreenSaver => "screensaver",
+            IdleBackend::Logind => "logind",
+            IdleBackend::None => "none",
         }
     }
 
-    pub fn from_str(s: &str) -> Option<Category> {
-        match s {
-            "deep_work" => Some(Category::DeepWork),
-            "meetings" => Some(Category::Meetings),
-            "admin" => Some(Category::Admin),
-            "break" => Some(Category::Break),
-            "away" => Some(Category::Away),
-            _ => None,
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|backend| backend.as_str().eq_ignore_ascii_case(name))
+    }
+}
+
+/// Typed view of the settings table
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Settings {
+    pub interval_minutes: u64,
+    pub idle_threshold_minutes: u32,
+    pub notification_enabled: bool,
+    pub notification_sound: NotificationSound,
+    pub idle_backend: IdleBackend,
+    pub backup_count: u32,
+    pub trash_retention_days: u32,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            interval_minutes: 15,
+            idle_threshold_minutes: 5,
+            notification_enabled: true,
+            notification_sound: NotificationSound::Default,
+            idle_backend: IdleBackend::Auto,
+            backup_count: 7,
+            trash_retention_days: 30,
+        }
+    }
+}
+
+impl Settings {
+    /// Every field as a single-setting change
+    pub fn fields(&self) -> Vec<SettingChange> {
+        vec![
+            SettingChange::IntervalMinutes(self.interval_minutes),
+            SettingChange::IdleThresholdMinutes(self.idle_threshold_minutes),
+            SettingChange::NotificationEnabled(self.notification_enabled),
+            SettingChange::NotificationSound(self.notification_sound),
+            SettingChange::IdleBackend(self.idle_backend),
+            SettingChange::BackupCount(self.backup_count),
+            SettingChange::TrashRetentionDays(self.trash_retention_days),
+        ]
+    }
+
+    pub fn apply(&mut self, change: &SettingChange) {
+        match *change {
+            SettingChange::IntervalMinutes(minutes) => self.interval_minutes = minutes,
+            SettingChange::IdleThresholdMinutes(minutes) => self.idle_threshold_minutes = minutes,
+            SettingChange::NotificationEnabled(enabled) => self.notification_enabled = enabled,
+            SettingChange::NotificationSound(sound) => self.notification_sound = sound,
+            SettingChange::IdleBackend(backend) => self.idle_backend = backend,
+            SettingChange::BackupCount(count) => self.backup_count = count,
+            SettingChange::TrashRetentionDays(days) => self.trash_retention_days = days,
         }
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-
-    #[test]
-    fn test_category_as_str() {
-        assert_eq!(Category::DeepWork.as_str(), "deep_work");
-        assert_eq!(Category::Meetings.as_str(), "meetings");
-        assert_eq!(Category::Admin.as_str(), "admin");
-        assert_eq!(Category::Break.as_str(), "break");
-        assert_eq!(Category::Away.as_str(), "away");
-    }
-
-    #[test]
-    fn test_category_from_str_valid() {
-        assert_eq!(Category::from_str("deep_work"), Some(Category::DeepWork));
-        assert_eq!(Category::from_str("meetings"), Some(Category::Meetings));
-        assert_eq!(Category::from_str("admin"), Some(Category::Admin));
-        assert_eq!(Category::from_str("break"), Some(Category::Break));
-        assert_eq!(Category::from_str("away"), Some(Category::Away));
-    }
-
-    #[test]
-    fn test_category_from_str_invalid() {
-        assert_eq!(Category::from_str("invalid"), None);
-        assert_eq!(Category::from_str(""), None);
-        assert_eq!(Category::from_str("Deep Work"), None);
-        assert_eq!(Category::from_str("AWAY"), None);
-    }
-
-    #[test]
-    fn test_category_roundtrip() {
-        let variants = [
-            Category::DeepWork,
-            Category::Meetings,
-            Category::Admin,
-            Category::Break,
-            Category::Away,
-        ];
-        for variant in variants {
-            assert_eq!(Category::from_str(variant.as_str()), Some(variant));
+/// One validated setting value, as stored and as published to subscribers
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(tag = "key", content = "value", rename_all = "snake_case")]
+pub enum SettingChange {
+    IntervalMinutes(u64),
+    IdleThresholdMinutes(u32),
+    NotificationEnabled(bool),
+    NotificationSound(NotificationSound),
+    IdleBackend(IdleBackend),
+    BackupCount(u32),
+    TrashRetentionDays(u32),
+}
+
+impl SettingChange {
+    pub fn key(&self) -> &'static str {
+        match self {
+            SettingChange::IntervalMinutes(_) => "interval_minutes",
+            SettingChange::IdleThresholdMinutes(_) => "idle_threshold_minutes",
+            SettingChange::NotificationEnabled(_) => "notification_enabled",
+            SettingChange::NotificationSound(_) => "notification_sound",
+            SettingChange::IdleBackend(_) => "idle_backend",
+            SettingChange::BackupCount(_) => "backup_count",
+            SettingChange::TrashRetentionDays(_) => "trash_retention_days",
+        }
+    }
+
+    /// The value as stored in the settings table
+    pub fn value(&self) -> String {
+        match self {
+            SettingChange::IntervalMinutes(minutes) => minutes.to_string(),
+            SettingChange::IdleThresholdMinutes(minutes) => minutes.to_string(),
+            SettingChange::NotificationEnabled(enabled) => enabled.to_string(),
+            SettingChange::NotificationSound(sound) => sound.as_str().to_string(),
+            SettingChange::IdleBackend(backend) => backend.as_str().to_string(),
+            SettingChange::BackupCount(count) => count.to_string(),
+            SettingChange::TrashRetentionDays(days) => days.to_string(),
         }
     }
 }
+
+/// Slug of the built-in category used for auto-recorded away time
+pub const AWAY_CATEGORY: &str = "away";
+
+/// Slug of the built-in category recorded for intervals the timer was paused
+pub const PAUSED_CATEGORY: &str = "paused";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Category {
+    pub id: Option<i64>,
+    pub name: String,
+    pub slug: String,
+    pub color: String,
+    pub icon: Option<String>,
+    pub is_productive: bool,
+    pub archived: bool,
+    pub sort_order: i32,
+    pub created_at: Option<i64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Client {
+    pub id: Option<i64>,
+    pub name: String,
+    pub archived: bool,
+    pub created_at: Option<i64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Project {
+    pub id: Option<i64>,
+    pub client_id: Option<i64>,
+    pub name: String,
+    pub archived: bool,
+    pub created_at: Option<i64>,
+}
+
+/// Minutes logged against a project; `project_id` is `None` for unassigned time
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProjectTotal {
+    pub project_id: Option<i64>,
+    pub project_name: Option<String>,
+    pub client_id: Option<i64>,
+    pub client_name: Option<String>,
+    pub total_minutes: i64,
+}
+
+/// Minutes logged against a client; `client_id` is `None` for time without one
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClientTotal {
+    pub client_id: Option<i64>,
+    pub client_name: Option<String>,
+    pub total_minutes: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Tag {
+    pub id: Option<i64>,
+    pub name: String,
+    pub created_at: Option<i64>,
+}
+
+/// Whether an entry must carry any or all of the requested tags
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum TagMatch {
+    Any,
+    All,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TagTotal {
+    pub tag: String,
+    pub total_minutes: i64,
+}
+
+/// Bucket size for aggregated reports
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ReportPeriod {
+    Day,
+    Week,
+    Month,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CategoryMinutes {
+    pub category: String,
+    pub total_minutes: i64,
+}
+
+/// Aggregated minutes for one day, ISO week or month
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReportBucket {
+    pub start: i64,
+    pub end: i64,
+    pub label: String,
+    pub total_minutes: i64,
+    pub categories: Vec<CategoryMinutes>,
+}
+
+/// What to do with existing entries that share time with a new one
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum OverlapPolicy {
+    /// Refuse to create the new entry
+    #[default]
+    Reject,
+    /// Move the overlapping entries to the trash
+    Replace,
+    /// Trim the overlapping entries around the new one, splitting any that
+    /// straddle it
+    Split,
+}
+
+/// Two entries claiming the same time, and the stretch they share
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EntryOverlap {
+    pub start: i64,
+    pub end: i64,
+    pub first: TimeEntry,
+    pub second: TimeEntry,
+}
+
+/// A run of snippet text, marked when it matched the search
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SnippetPart {
+    pub text: String,
+    pub highlighted: bool,
+}
+
+/// An entry whose notes matched a search, with the matching stretch of its
+/// notes. Lower ranks are better matches.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EntrySearchHit {
+    pub entry: TimeEntry,
+    pub rank: f64,
+    pub snippet: Vec<SnippetPart>,
+}
+
+/// Kind of user action recorded in the change journal
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ChangeAction {
+    Create,
+    Update,
+    Delete,
+    Restore,
+}
+
+impl ChangeAction {
+    pub const ALL: [ChangeAction; 4] = [
+        ChangeAction::Create,
+        ChangeAction::Update,
+        ChangeAction::Delete,
+        ChangeAction::Restore,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ChangeAction::Create => "create",
+            ChangeAction::Update => "update",
+            ChangeAction::Delete => "delete",
+            ChangeAction::Restore => "restore",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|a| a.as_str() == name)
+    }
+}
+
+/// Where a change to an entry came from, as recorded in its history
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeSource {
+    /// Answering a prompt as it came up
+    Prompt,
+    /// Filling in time after the fact
+    Retroactive,
+    /// Editing, deleting or restoring a single entry
+    Edit,
+    /// Away and paused time recorded by the timer
+    Timer,
+    Import,
+    /// Changes to many entries at once, such as merging categories
+    BulkEdit,
+    Undo,
+    /// Emptying the trash, which removes the entry for good
+    Purge,
+}
+
+impl ChangeSource {
+    pub const ALL: [ChangeSource; 8] = [
+        ChangeSource::Prompt,
+        ChangeSource::Retroactive,
+        ChangeSource::Edit,
+        ChangeSource::Timer,
+        ChangeSource::Import,
+        ChangeSource::BulkEdit,
+        ChangeSource::Undo,
+        ChangeSource::Purge,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ChangeSource::Prompt => "prompt",
+            ChangeSource::Retroactive => "retroactive",
+            ChangeSource::Edit => "edit",
+            ChangeSource::Timer => "timer",
+            ChangeSource::Import => "import",
+            ChangeSource::BulkEdit => "bulk_edit",
+            ChangeSource::Undo => "undo",
+            ChangeSource::Purge => "purge",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|s| s.as_str() == name)
+    }
+}
+
+/// One field of an entry changing value. A `None` old value means the
+/// entry was created; a `None` new value that the field was cleared.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EntryHistoryItem {
+    pub id: i64,
+    pub entry_id: i64,
+    pub field: String,
+    pub old_value: Option<String>,
+    pub new_value: Option<String>,
+    pub source: ChangeSource,
+    pub changed_at: i64,
+}
+
+/// A journaled change and every entry it touched
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EntryChange {
+    pub id: i64,
+    pub action: ChangeAction,
+    pub entry_ids: Vec<i64>,
+    pub created_at: i64,
+}
+
+/// What to do when an imported row shares time with an existing entry
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ConflictPolicy {
+    Skip,
+    Overwrite,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ImportAction {
+    Insert,
+    Skip,
+    Overwrite,
+    Invalid,
+}
+
+/// Outcome for one imported row; `row` is its 1-based position in the input
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImportRowResult {
+    pub row: usize,
+    pub timestamp: Option<i64>,
+    pub category: Option<String>,
+    pub action: ImportAction,
+    pub existing_id: Option<i64>,
+    pub message: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImportReport {
+    pub dry_run: bool,
+    pub inserted: usize,
+    pub overwritten: usize,
+    pub skipped: usize,
+    pub invalid: usize,
+    pub rows: Vec<ImportRowResult>,
+}
+
+/// A stretch of working time on one weekday, in minutes since local midnight.
+/// `weekday` counts from Monday = 0; `end_minute` is exclusive and may be 1440.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub struct WorkBlock {
+    pub weekday: u32,
+    pub start_minute: u32,
+    pub end_minute: u32,
+}
+
+/// A date (`YYYY-MM-DD`) with no prompting, such as a holiday
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct DayOff {
+    pub date: String,
+    pub label: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct WorkSchedule {
+    pub blocks: Vec<WorkBlock>,
+    pub days_off: Vec<DayOff>,
+}
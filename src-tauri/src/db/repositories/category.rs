@@ -0,0 +1,402 @@
+use crate::db::models::{Category, ChangeSource, AWAY_CATEGORY, PAUSED_CATEGORY};
+use rusqlite::{Connection, OptionalExtension, Row};
+use std::sync::MutexGuard;
+
+use super::history;
+use super::journal::EntrySnapshot;
+use super::{bool_to_int, int_to_bool, RepositoryError, Result};
+
+const SELECT_COLUMNS: &str =
+    "SELECT id, name, slug, color, icon, is_productive, archived, sort_order, created_at FROM categories";
+
+pub struct CategoryRepository<'a> {
+    conn: MutexGuard<'a, Connection>,
+    now: i64,
+}
+
+impl<'a> CategoryRepository<'a> {
+    pub fn new(conn: MutexGuard<'a, Connection>) -> Self {
+        Self {
+            conn,
+            now: chrono::Utc::now().timestamp(),
+        }
+    }
+
+    /// Stamps the changes made through this repository with `now` rather
+    /// than the time it was created, for callers that keep their own clock
+    pub fn at(mut self, now: i64) -> Self {
+        self.now = now;
+        self
+    }
+
+    pub fn create(
+        &self,
+        name: &str,
+        color: &str,
+        icon: Option<&str>,
+        is_productive: bool,
+    ) -> Result<i64> {
+        let name = validate_name(name)?;
+        let color = validate_color(color)?;
+        let slug = slugify(name);
+        if slug.is_empty() {
+            return Err(RepositoryError::InvalidData(format!(
+                "Category name '{}' must contain at least one letter or digit",
+                name
+            )));
+        }
+        if self.find_by_slug(&slug)?.is_some() {
+            return Err(RepositoryError::InvalidData(format!(
+                "Category '{}' already exists",
+                slug
+            )));
+        }
+
+        self.conn.execute(
+            "INSERT INTO categories (name, slug, color, icon, is_productive, sort_order)
+             VALUES (?1, ?2, ?3, ?4, ?5, (SELECT COALESCE(MAX(sort_order), -1) + 1 FROM categories))",
+            (name, &slug, color, icon, bool_to_int(is_productive)),
+        )?;
+        Ok(self.conn.last_insert_rowid())
+    }
+
+    /// Returns categories in display order, optionally including archived ones
+    pub fn find_all(&self, include_archived: bool) -> Result<Vec<Category>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "{} WHERE archived = 0 OR ?1 ORDER BY sort_order ASC, name ASC",
+            SELECT_COLUMNS
+        ))?;
+
+        let categories = stmt
+            .query_map([bool_to_int(include_archived)], map_category)?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+
+        Ok(categories)
+    }
+
+    pub fn find_by_id(&self, id: i64) -> Result<Category> {
+        self.conn
+            .query_row(&format!("{} WHERE id = ?1", SELECT_COLUMNS), [id], map_category)
+            .optional()?
+            .ok_or_else(|| RepositoryError::NotFound(format!("Category {}", id)))
+    }
+
+    pub fn find_by_slug(&self, slug: &str) -> Result<Option<Category>> {
+        let category = self
+            .conn
+            .query_row(&format!("{} WHERE slug = ?1", SELECT_COLUMNS), [slug], map_category)
+            .optional()?;
+        Ok(category)
+    }
+
+    /// Changes the display name. The slug stays the same so existing entries
+    /// keep pointing at the category.
+    pub fn rename(&self, id: i64, name: &str) -> Result<()> {
+        let name = validate_name(name)?;
+        let updated = self
+            .conn
+            .execute("UPDATE categories SET name = ?1 WHERE id = ?2", (name, id))?;
+        if updated == 0 {
+            return Err(RepositoryError::NotFound(format!("Category {}", id)));
+        }
+        Ok(())
+    }
+
+    /// Archived categories keep their entries but can no longer be assigned
+    pub fn set_archived(&self, id: i64, archived: bool) -> Result<()> {
+        let category = self.find_by_id(id)?;
+        if archived && is_system_category(&category.slug) {
+            return Err(RepositoryError::InvalidData(format!(
+                "The {} category cannot be archived",
+                category.slug
+            )));
+        }
+        self.conn.execute(
+            "UPDATE categories SET archived = ?1 WHERE id = ?2",
+            (bool_to_int(archived), id),
+        )?;
+        Ok(())
+    }
+
+    /// Moves every entry from `source_id` to `target_id` and removes the
+    /// source category. Returns the number of entries moved.
+    pub fn merge(&self, source_id: i64, target_id: i64) -> Result<usize> {
+        if source_id == target_id {
+            return Err(RepositoryError::InvalidData(
+                "Cannot merge a category into itself".to_string(),
+            ));
+        }
+        let source = self.find_by_id(source_id)?;
+        let target = self.find_by_id(target_id)?;
+        if is_system_category(&source.slug) {
+            return Err(RepositoryError::InvalidData(format!(
+                "The {} category cannot be merged into another category",
+                source.slug
+            )));
+        }
+
+        let tx = self.conn.unchecked_transaction()?;
+        let ids: Vec<i64> = tx
+            .prepare("SELECT id FROM time_entries WHERE category = ?1")?
+            .query_map([&source.slug], |row| row.get(0))?
+            .collect::<std::result::Result<_, _>>()?;
+        let before = ids
+            .iter()
+            .map(|&id| EntrySnapshot::load(&tx, id))
+            .collect::<Result<Vec<_>>>()?;
+        let moved = tx.execute(
+            "UPDATE time_entries SET category = ?1 WHERE category = ?2",
+            (&target.slug, &source.slug),
+        )?;
+        for (id, before) in ids.iter().zip(&before) {
+            history::record(&tx, *id, ChangeSource::BulkEdit, before.as_ref(), self.now)?;
+        }
+        tx.execute("DELETE FROM categories WHERE id = ?1", [source_id])?;
+        tx.commit()?;
+
+        Ok(moved)
+    }
+}
+
+fn map_category(row: &Row) -> rusqlite::Result<Category> {
+    Ok(Category {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        slug: row.get(2)?,
+        color: row.get(3)?,
+        icon: row.get(4)?,
+        is_productive: int_to_bool(row.get(5)?),
+        archived: int_to_bool(row.get(6)?),
+        sort_order: row.get(7)?,
+        created_at: row.get(8)?,
+    })
+}
+
+/// Categories the timer writes on its own; they can't be archived or merged away
+fn is_system_category(slug: &str) -> bool {
+    slug == AWAY_CATEGORY || slug == PAUSED_CATEGORY
+}
+
+fn validate_name(name: &str) -> Result<&str> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(RepositoryError::InvalidData(
+            "Category name cannot be empty".to_string(),
+        ));
+    }
+    Ok(name)
+}
+
+/// Colors are stored as `#RRGGBB`, the form the built-in categories use
+fn validate_color(color: &str) -> Result<&str> {
+    let color = color.trim();
+    match color.strip_prefix('#') {
+        Some(hex) if hex.len() == 6 && hex.chars().all(|c| c.is_ascii_hexdigit()) => Ok(color),
+        _ => Err(RepositoryError::InvalidData(format!(
+            "Category color '{}' must be a hex color like #6B7280",
+            color
+        ))),
+    }
+}
+
+/// Derives a stable identifier from a display name, e.g. "Code Review" -> "code_review"
+pub fn slugify(name: &str) -> String {
+    name.to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("_")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{connection::Database, migrations};
+
+    fn setup_db() -> Database {
+        let db = Database::new_in_memory().unwrap();
+        {
+            let conn = db.conn.lock().unwrap();
+            migrations::run_migrations(&conn).unwrap();
+        }
+        db
+    }
+
+    #[test]
+    fn test_slugify() {
+        assert_eq!(slugify("Deep Work"), "deep_work");
+        assert_eq!(slugify("  Code  Review! "), "code_review");
+        assert_eq!(slugify("1:1s"), "1_1s");
+        assert_eq!(slugify("!!!"), "");
+    }
+
+    #[test]
+    fn test_find_all_returns_builtins_in_order() {
+        let db = setup_db();
+        let conn = db.conn.lock().unwrap();
+        let repo = CategoryRepository::new(conn);
+        let slugs: Vec<String> = repo.find_all(false).unwrap().into_iter().map(|c| c.slug).collect();
+        assert_eq!(slugs, vec!["deep_work", "meetings", "admin", "break", "away", "paused"]);
+    }
+
+    #[test]
+    fn test_create_appends_category() {
+        let db = setup_db();
+        let conn = db.conn.lock().unwrap();
+        let repo = CategoryRepository::new(conn);
+        let id = repo.create("Code Review", "#FF0000", Some("eye"), true).unwrap();
+
+        let category = repo.find_by_id(id).unwrap();
+        assert_eq!(category.name, "Code Review");
+        assert_eq!(category.slug, "code_review");
+        assert_eq!(category.color, "#FF0000");
+        assert_eq!(category.icon.as_deref(), Some("eye"));
+        assert!(category.is_productive);
+        assert!(!category.archived);
+        assert_eq!(category.sort_order, 6);
+    }
+
+    #[test]
+    fn test_create_rejects_duplicate_and_empty_names() {
+        let db = setup_db();
+        let conn = db.conn.lock().unwrap();
+        let repo = CategoryRepository::new(conn);
+        assert!(matches!(
+            repo.create("Deep Work", "#000000", None, true),
+            Err(RepositoryError::InvalidData(_))
+        ));
+        assert!(matches!(
+            repo.create("   ", "#000000", None, true),
+            Err(RepositoryError::InvalidData(_))
+        ));
+        assert!(matches!(
+            repo.create("???", "#000000", None, true),
+            Err(RepositoryError::InvalidData(_))
+        ));
+    }
+
+    #[test]
+    fn test_create_rejects_invalid_color() {
+        let db = setup_db();
+        let conn = db.conn.lock().unwrap();
+        let repo = CategoryRepository::new(conn);
+        for bad in ["", "red", "#FFF", "FF0000", "#GG0000", "#FF00001"] {
+            assert!(
+                matches!(repo.create("Review", bad, None, true), Err(RepositoryError::InvalidData(_))),
+                "{}",
+                bad
+            );
+        }
+        let id = repo.create("Review", " #a1b2c3 ", None, true).unwrap();
+        assert_eq!(repo.find_by_id(id).unwrap().color, "#a1b2c3");
+    }
+
+    #[test]
+    fn test_rename_keeps_slug() {
+        let db = setup_db();
+        let conn = db.conn.lock().unwrap();
+        let repo = CategoryRepository::new(conn);
+        let id = repo.find_by_slug("admin").unwrap().unwrap().id.unwrap();
+
+        repo.rename(id, "Paperwork").unwrap();
+
+        let category = repo.find_by_id(id).unwrap();
+        assert_eq!(category.name, "Paperwork");
+        assert_eq!(category.slug, "admin");
+    }
+
+    #[test]
+    fn test_rename_missing_category() {
+        let db = setup_db();
+        let conn = db.conn.lock().unwrap();
+        let repo = CategoryRepository::new(conn);
+        assert!(matches!(repo.rename(999, "x"), Err(RepositoryError::NotFound(_))));
+    }
+
+    #[test]
+    fn test_archive_hides_from_default_listing() {
+        let db = setup_db();
+        let conn = db.conn.lock().unwrap();
+        let repo = CategoryRepository::new(conn);
+        let id = repo.find_by_slug("meetings").unwrap().unwrap().id.unwrap();
+
+        repo.set_archived(id, true).unwrap();
+
+        assert!(repo.find_all(false).unwrap().iter().all(|c| c.slug != "meetings"));
+        assert!(repo.find_all(true).unwrap().iter().any(|c| c.slug == "meetings"));
+
+        repo.set_archived(id, false).unwrap();
+        assert!(repo.find_all(false).unwrap().iter().any(|c| c.slug == "meetings"));
+    }
+
+    #[test]
+    fn test_away_cannot_be_archived() {
+        let db = setup_db();
+        let conn = db.conn.lock().unwrap();
+        let repo = CategoryRepository::new(conn);
+        let id = repo.find_by_slug("away").unwrap().unwrap().id.unwrap();
+        assert!(matches!(repo.set_archived(id, true), Err(RepositoryError::InvalidData(_))));
+    }
+
+    #[test]
+    fn test_paused_cannot_be_archived_or_merged() {
+        let db = setup_db();
+        let conn = db.conn.lock().unwrap();
+        let repo = CategoryRepository::new(conn);
+        let paused = repo.find_by_slug("paused").unwrap().unwrap().id.unwrap();
+        let admin = repo.find_by_slug("admin").unwrap().unwrap().id.unwrap();
+        assert!(matches!(repo.set_archived(paused, true), Err(RepositoryError::InvalidData(_))));
+        assert!(matches!(repo.merge(paused, admin), Err(RepositoryError::InvalidData(_))));
+    }
+
+    #[test]
+    fn test_merge_moves_entries_and_removes_source() {
+        let db = setup_db();
+        let conn = db.conn.lock().unwrap();
+        conn.execute_batch(
+            "INSERT INTO time_entries (timestamp, category) VALUES (900, 'admin');
+             INSERT INTO time_entries (timestamp, category) VALUES (1800, 'admin');
+             INSERT INTO time_entries (timestamp, category) VALUES (2700, 'deep_work');",
+        )
+        .unwrap();
+        let repo = CategoryRepository::new(conn);
+        let admin = repo.find_by_slug("admin").unwrap().unwrap().id.unwrap();
+        let deep_work = repo.find_by_slug("deep_work").unwrap().unwrap().id.unwrap();
+
+        let moved = repo.merge(admin, deep_work).unwrap();
+
+        assert_eq!(moved, 2);
+        assert!(repo.find_by_slug("admin").unwrap().is_none());
+        let remaining: i64 = repo
+            .conn
+            .query_row(
+                "SELECT COUNT(*) FROM time_entries WHERE category = 'deep_work'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(remaining, 3);
+        let bulk_edits: i64 = repo
+            .conn
+            .query_row(
+                "SELECT COUNT(*) FROM time_entry_history
+                 WHERE source = 'bulk_edit' AND field = 'category'
+                   AND old_value = 'admin' AND new_value = 'deep_work'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(bulk_edits, 2);
+    }
+
+    #[test]
+    fn test_merge_rejects_self_and_away() {
+        let db = setup_db();
+        let conn = db.conn.lock().unwrap();
+        let repo = CategoryRepository::new(conn);
+        let away = repo.find_by_slug("away").unwrap().unwrap().id.unwrap();
+        let admin = repo.find_by_slug("admin").unwrap().unwrap().id.unwrap();
+        assert!(matches!(repo.merge(admin, admin), Err(RepositoryError::InvalidData(_))));
+        assert!(matches!(repo.merge(away, admin), Err(RepositoryError::InvalidData(_))));
+    }
+}
@@ -0,0 +1,165 @@
+use crate::db::models::Client;
+use rusqlite::{Connection, OptionalExtension, Row};
+use std::sync::MutexGuard;
+
+use super::{bool_to_int, int_to_bool, RepositoryError, Result};
+
+pub struct ClientRepository<'a> {
+    conn: MutexGuard<'a, Connection>,
+}
+
+impl<'a> ClientRepository<'a> {
+    pub fn new(conn: MutexGuard<'a, Connection>) -> Self {
+        Self { conn }
+    }
+
+    pub fn create(&self, name: &str) -> Result<i64> {
+        let name = validate_name(name)?;
+        self.ensure_name_available(name)?;
+        self.conn
+            .execute("INSERT INTO clients (name) VALUES (?1)", [name])?;
+        Ok(self.conn.last_insert_rowid())
+    }
+
+    pub fn find_all(&self, include_archived: bool) -> Result<Vec<Client>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, name, archived, created_at
+             FROM clients
+             WHERE archived = 0 OR ?1
+             ORDER BY name COLLATE NOCASE ASC",
+        )?;
+
+        let clients = stmt
+            .query_map([bool_to_int(include_archived)], map_client)?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+
+        Ok(clients)
+    }
+
+    pub fn find_by_id(&self, id: i64) -> Result<Client> {
+        self.conn
+            .query_row(
+                "SELECT id, name, archived, created_at FROM clients WHERE id = ?1",
+                [id],
+                map_client,
+            )
+            .optional()?
+            .ok_or_else(|| RepositoryError::NotFound(format!("Client {}", id)))
+    }
+
+    pub fn rename(&self, id: i64, name: &str) -> Result<()> {
+        let name = validate_name(name)?;
+        self.find_by_id(id)?;
+        self.ensure_name_available(name)?;
+        self.conn
+            .execute("UPDATE clients SET name = ?1 WHERE id = ?2", (name, id))?;
+        Ok(())
+    }
+
+    pub fn set_archived(&self, id: i64, archived: bool) -> Result<()> {
+        let updated = self.conn.execute(
+            "UPDATE clients SET archived = ?1 WHERE id = ?2",
+            (bool_to_int(archived), id),
+        )?;
+        if updated == 0 {
+            return Err(RepositoryError::NotFound(format!("Client {}", id)));
+        }
+        Ok(())
+    }
+
+    fn ensure_name_available(&self, name: &str) -> Result<()> {
+        let existing: Option<i64> = self
+            .conn
+            .query_row("SELECT id FROM clients WHERE name = ?1", [name], |row| row.get(0))
+            .optional()?;
+        if existing.is_some() {
+            return Err(RepositoryError::InvalidData(format!(
+                "Client '{}' already exists",
+                name
+            )));
+        }
+        Ok(())
+    }
+}
+
+fn map_client(row: &Row) -> rusqlite::Result<Client> {
+    Ok(Client {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        archived: int_to_bool(row.get(2)?),
+        created_at: row.get(3)?,
+    })
+}
+
+fn validate_name(name: &str) -> Result<&str> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(RepositoryError::InvalidData(
+            "Client name cannot be empty".to_string(),
+        ));
+    }
+    Ok(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{connection::Database, migrations};
+
+    fn setup_db() -> Database {
+        let db = Database::new_in_memory().unwrap();
+        {
+            let conn = db.conn.lock().unwrap();
+            migrations::run_migrations(&conn).unwrap();
+        }
+        db
+    }
+
+    #[test]
+    fn test_create_and_find() {
+        let db = setup_db();
+        let conn = db.conn.lock().unwrap();
+        let repo = ClientRepository::new(conn);
+        let id = repo.create("  Acme ").unwrap();
+
+        let client = repo.find_by_id(id).unwrap();
+        assert_eq!(client.name, "Acme");
+        assert!(!client.archived);
+    }
+
+    #[test]
+    fn test_create_rejects_duplicate_and_empty() {
+        let db = setup_db();
+        let conn = db.conn.lock().unwrap();
+        let repo = ClientRepository::new(conn);
+        repo.create("Acme").unwrap();
+        assert!(matches!(repo.create("Acme"), Err(RepositoryError::InvalidData(_))));
+        assert!(matches!(repo.create(" "), Err(RepositoryError::InvalidData(_))));
+    }
+
+    #[test]
+    fn test_find_all_sorted_and_excludes_archived() {
+        let db = setup_db();
+        let conn = db.conn.lock().unwrap();
+        let repo = ClientRepository::new(conn);
+        repo.create("globex").unwrap();
+        let acme = repo.create("Acme").unwrap();
+        repo.create("Initech").unwrap();
+        repo.set_archived(acme, true).unwrap();
+
+        let names: Vec<String> = repo.find_all(false).unwrap().into_iter().map(|c| c.name).collect();
+        assert_eq!(names, vec!["globex", "Initech"]);
+        assert_eq!(repo.find_all(true).unwrap().len(), 3);
+    }
+
+    #[test]
+    fn test_rename() {
+        let db = setup_db();
+        let conn = db.conn.lock().unwrap();
+        let repo = ClientRepository::new(conn);
+        let id = repo.create("Acme").unwrap();
+        repo.rename(id, "Acme Corp").unwrap();
+        assert_eq!(repo.find_by_id(id).unwrap().name, "Acme Corp");
+        assert!(matches!(repo.rename(999, "x"), Err(RepositoryError::NotFound(_))));
+    }
+}
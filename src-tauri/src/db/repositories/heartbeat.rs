@@ -0,0 +1,52 @@
+use rusqlite::{Connection, OptionalExtension};
+use std::sync::MutexGuard;
+
+use super::Result;
+
+pub struct HeartbeatRepository<'a> {
+    conn: MutexGuard<'a, Connection>,
+}
+
+impl<'a> HeartbeatRepository<'a> {
+    pub fn new(conn: MutexGuard<'a, Connection>) -> Self {
+        Self { conn }
+    }
+
+    /// When the timer last handled a tick, if it ever has
+    pub fn last_tick(&self) -> Result<Option<i64>> {
+        let last = self
+            .conn
+            .query_row("SELECT last_tick_at FROM timer_heartbeat WHERE id = 1", [], |row| {
+                row.get(0)
+            })
+            .optional()?;
+        Ok(last)
+    }
+
+    pub fn record_tick(&self, timestamp: i64) -> Result<()> {
+        self.conn.execute(
+            "INSERT OR REPLACE INTO timer_heartbeat (id, last_tick_at) VALUES (1, ?1)",
+            [timestamp],
+        )?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{connection::Database, migrations};
+
+    #[test]
+    fn test_record_and_read_last_tick() {
+        let db = Database::new_in_memory().unwrap();
+        let conn = db.conn.lock().unwrap();
+        migrations::run_migrations(&conn).unwrap();
+        let repo = HeartbeatRepository::new(conn);
+
+        assert_eq!(repo.last_tick().unwrap(), None);
+        repo.record_tick(900).unwrap();
+        repo.record_tick(1800).unwrap();
+        assert_eq!(repo.last_tick().unwrap(), Some(1800));
+    }
+}
@@ -0,0 +1,66 @@
+use crate::db::models::{ChangeSource, EntryHistoryItem};
+use rusqlite::types::Type;
+use rusqlite::Connection;
+
+use super::journal::{EntrySnapshot, TRACKED_FIELDS};
+use super::Result;
+
+/// Writes a history row for every tracked field of the entry that differs
+/// from `before`, which is `None` when the entry did not exist yet. An entry
+/// that no longer exists shows up with every field cleared. Rows are
+/// stamped with `now`.
+pub(crate) fn record(
+    conn: &Connection,
+    entry_id: i64,
+    source: ChangeSource,
+    before: Option<&EntrySnapshot>,
+    now: i64,
+) -> Result<()> {
+    let after = EntrySnapshot::load(conn, entry_id)?;
+    let old = before.map(EntrySnapshot::tracked_values).unwrap_or_default();
+    let new = after.as_ref().map(EntrySnapshot::tracked_values).unwrap_or_default();
+
+    for ((field, old), new) in TRACKED_FIELDS.iter().zip(old).zip(new) {
+        if old != new {
+            conn.execute(
+                "INSERT INTO time_entry_history
+                     (entry_id, field, old_value, new_value, source, changed_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                (entry_id, field, old, new, source.as_str(), now),
+            )?;
+        }
+    }
+    Ok(())
+}
+
+/// Every recorded change to an entry, oldest first
+pub(crate) fn for_entry(conn: &Connection, entry_id: i64) -> Result<Vec<EntryHistoryItem>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, entry_id, field, old_value, new_value, source, changed_at
+         FROM time_entry_history
+         WHERE entry_id = ?1
+         ORDER BY changed_at ASC, id ASC",
+    )?;
+    let items = stmt
+        .query_map([entry_id], |row| {
+            let source: String = row.get(5)?;
+            let source = ChangeSource::from_name(&source).ok_or_else(|| {
+                rusqlite::Error::FromSqlConversionFailure(
+                    5,
+                    Type::Text,
+                    format!("Unknown change source '{}'", source).into(),
+                )
+            })?;
+            Ok(EntryHistoryItem {
+                id: row.get(0)?,
+                entry_id: row.get(1)?,
+                field: row.get(2)?,
+                old_value: row.get(3)?,
+                new_value: row.get(4)?,
+                source,
+                changed_at: row.get(6)?,
+            })
+        })?
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+    Ok(items)
+}
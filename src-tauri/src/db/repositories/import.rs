@@ -0,0 +1,484 @@
+use crate::db::models::{
+    ChangeSource, ConflictPolicy, ExportRow, ImportAction, ImportReport, ImportRowResult,
+};
+use rusqlite::{Connection, OptionalExtension};
+use std::sync::MutexGuard;
+
+use super::history;
+use super::journal::EntrySnapshot;
+use super::tag::sync_hashtags;
+use super::time_entry::{overlapping_entries, soft_delete};
+use super::{bool_to_int, int_to_bool, Result};
+
+pub struct ImportRepository<'a> {
+    conn: MutexGuard<'a, Connection>,
+    now: i64,
+}
+
+impl<'a> ImportRepository<'a> {
+    pub fn new(conn: MutexGuard<'a, Connection>) -> Self {
+        Self {
+            conn,
+            now: chrono::Utc::now().timestamp(),
+        }
+    }
+
+    /// Stamps the changes made through this repository with `now` rather
+    /// than the time it was created, for callers that keep their own clock
+    pub fn at(mut self, now: i64) -> Self {
+        self.now = now;
+        self
+    }
+
+    /// Imports parsed rows in a single transaction. A row sharing time with
+    /// an entry (including one inserted earlier in the same import) is
+    /// skipped or overwritten according to `policy`. Overwriting updates the
+    /// entry starting at the row's time, or else the first one it overlaps,
+    /// and moves any other overlapping entries to the trash. Rows that
+    /// failed to parse, or name an unknown or archived category or project,
+    /// are reported as invalid and left out.
+    ///
+    /// With `dry_run` the same work is done and then rolled back, so the
+    /// report is exactly what a real import would do.
+    pub fn import(
+        &self,
+        rows: Vec<std::result::Result<ExportRow, String>>,
+        policy: ConflictPolicy,
+        dry_run: bool,
+    ) -> Result<ImportReport> {
+        let tx = self.conn.unchecked_transaction()?;
+        let mut report = ImportReport {
+            dry_run,
+            inserted: 0,
+            overwritten: 0,
+            skipped: 0,
+            invalid: 0,
+            rows: Vec::new(),
+        };
+
+        for (i, row) in rows.into_iter().enumerate() {
+            let result = match row {
+                Ok(row) => import_row(&tx, i + 1, row, policy, self.now)?,
+                Err(message) => ImportRowResult {
+                    row: i + 1,
+                    timestamp: None,
+                    category: None,
+                    action: ImportAction::Invalid,
+                    existing_id: None,
+                    message: Some(message),
+                },
+            };
+            match result.action {
+                ImportAction::Insert => report.inserted += 1,
+                ImportAction::Overwrite => report.overwritten += 1,
+                ImportAction::Skip => report.skipped += 1,
+                ImportAction::Invalid => report.invalid += 1,
+            }
+            report.rows.push(result);
+        }
+
+        if !dry_run {
+            tx.commit()?;
+        }
+        Ok(report)
+    }
+}
+
+fn import_row(
+    conn: &Connection,
+    index: usize,
+    row: ExportRow,
+    policy: ConflictPolicy,
+    now: i64,
+) -> Result<ImportRowResult> {
+    let mut result = ImportRowResult {
+        row: index,
+        timestamp: Some(row.timestamp),
+        category: Some(row.category.clone()),
+        action: ImportAction::Invalid,
+        existing_id: None,
+        message: None,
+    };
+
+    if row.duration_minutes <= 0 {
+        result.message = Some(format!("Invalid duration {}", row.duration_minutes));
+        return Ok(result);
+    }
+    let category = match resolve_category(conn, &row.category)? {
+        Ok(slug) => slug,
+        Err(message) => {
+            result.message = Some(message);
+            return Ok(result);
+        }
+    };
+    result.category = Some(category.clone());
+
+    let project_id = match row.project.as_deref().map(str::trim).filter(|p| !p.is_empty()) {
+        None => None,
+        Some(project) => {
+            let client = row.client.as_deref().map(str::trim).filter(|c| !c.is_empty());
+            match resolve_project(conn, project, client)? {
+                Ok((id, created)) => {
+                    if created {
+                        result.message = Some(format!("Creates project '{}'", project));
+                    }
+                    Some(id)
+                }
+                Err(message) => {
+                    result.message = Some(message);
+                    return Ok(result);
+                }
+            }
+        }
+    };
+
+    let end = row.timestamp + row.duration_minutes as i64 * 60;
+    let overlapping = overlapping_entries(conn, row.timestamp, end)?;
+    let existing = overlapping
+        .iter()
+        .find(|entry| entry.timestamp == row.timestamp)
+        .or(overlapping.first());
+
+    match (existing, policy) {
+        (Some(existing), ConflictPolicy::Skip) => {
+            result.action = ImportAction::Skip;
+            result.existing_id = existing.id;
+        }
+        (Some(existing), ConflictPolicy::Overwrite) => {
+            let id = existing.id.unwrap_or_default();
+            let others: Vec<i64> = overlapping
+                .iter()
+                .filter_map(|entry| entry.id)
+                .filter(|other| *other != id)
+                .collect();
+            for other in &others {
+                let before = EntrySnapshot::load(conn, *other)?;
+                soft_delete(conn, *other, now)?;
+                history::record(conn, *other, ChangeSource::Import, before.as_ref(), now)?;
+            }
+            if !others.is_empty() {
+                let ids: Vec<String> = others.iter().map(i64::to_string).collect();
+                let trashed = format!("Moves overlapping entries {} to the trash", ids.join(", "));
+                result.message = Some(match result.message.take() {
+                    Some(message) => format!("{}; {}", message, trashed),
+                    None => trashed,
+                });
+            }
+
+            let before = EntrySnapshot::load(conn, id)?;
+            conn.execute(
+                "UPDATE time_entries
+                 SET timestamp = ?1, category = ?2, duration_minutes = ?3, is_away = ?4,
+                     is_retroactive = ?5, notes = ?6, project_id = ?7
+                 WHERE id = ?8",
+                (
+                    row.timestamp,
+                    &category,
+                    row.duration_minutes,
+                    bool_to_int(row.is_away),
+                    bool_to_int(row.is_retroactive),
+                    &row.notes,
+                    project_id,
+                    id,
+                ),
+            )?;
+            sync_hashtags(conn, id, existing.notes.as_deref(), row.notes.as_deref())?;
+            history::record(conn, id, ChangeSource::Import, before.as_ref(), now)?;
+            result.action = ImportAction::Overwrite;
+            result.existing_id = Some(id);
+        }
+        (None, _) => {
+            conn.execute(
+                "INSERT INTO time_entries (timestamp, category, duration_minutes, is_away, is_retroactive, notes, project_id, created_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+                (
+                    row.timestamp,
+                    &category,
+                    row.duration_minutes,
+                    bool_to_int(row.is_away),
+                    bool_to_int(row.is_retroactive),
+                    &row.notes,
+                    project_id,
+                    now,
+                ),
+            )?;
+            let id = conn.last_insert_rowid();
+            sync_hashtags(conn, id, None, row.notes.as_deref())?;
+            history::record(conn, id, ChangeSource::Import, None, now)?;
+            result.action = ImportAction::Insert;
+        }
+    }
+
+    Ok(result)
+}
+
+/// Matches a category by slug or, case-insensitively, by display name.
+/// The inner `Err` explains why the row can't be imported.
+fn resolve_category(
+    conn: &Connection,
+    category: &str,
+) -> Result<std::result::Result<String, String>> {
+    let category = category.trim();
+    let found: Option<(String, i32)> = conn
+        .query_row(
+            "SELECT slug, archived FROM categories
+             WHERE slug = ?1 OR name = ?1 COLLATE NOCASE
+             ORDER BY slug = ?1 DESC LIMIT 1",
+            [category],
+            |r| Ok((r.get(0)?, r.get(1)?)),
+        )
+        .optional()?;
+
+    Ok(match found {
+        None => Err(format!("Unknown category '{}'", category)),
+        Some((slug, archived)) if int_to_bool(archived) => {
+            Err(format!("Category '{}' is archived", slug))
+        }
+        Some((slug, _)) => Ok(slug),
+    })
+}
+
+/// Finds a project by name under the named client (or under no client),
+/// creating the client and project if they don't exist yet. Returns the
+/// project id and whether it was created.
+fn resolve_project(
+    conn: &Connection,
+    project: &str,
+    client: Option<&str>,
+) -> Result<std::result::Result<(i64, bool), String>> {
+    let client_id: Option<i64> = match client {
+        None => None,
+        Some(client) => {
+            let id: Option<i64> = conn
+                .query_row("SELECT id FROM clients WHERE name = ?1", [client], |r| r.get(0))
+                .optional()?;
+            match id {
+                Some(id) => Some(id),
+                None => {
+                    conn.execute("INSERT INTO clients (name) VALUES (?1)", [client])?;
+                    Some(conn.last_insert_rowid())
+                }
+            }
+        }
+    };
+
+    let found: Option<(i64, i32)> = conn
+        .query_row(
+            "SELECT id, archived FROM projects WHERE name = ?1 AND client_id IS ?2",
+            (project, client_id),
+            |r| Ok((r.get(0)?, r.get(1)?)),
+        )
+        .optional()?;
+
+    Ok(match found {
+        Some((_, archived)) if int_to_bool(archived) => {
+            Err(format!("Project '{}' is archived", project))
+        }
+        Some((id, _)) => Ok((id, false)),
+        None => {
+            conn.execute(
+                "INSERT INTO projects (client_id, name) VALUES (?1, ?2)",
+                (client_id, project),
+            )?;
+            Ok((conn.last_insert_rowid(), true))
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{connection::Database, migrations};
+
+    fn setup_db() -> Database {
+        let db = Database::new_in_memory().unwrap();
+        {
+            let conn = db.conn.lock().unwrap();
+            migrations::run_migrations(&conn).unwrap();
+            conn.execute_batch(
+                "INSERT INTO time_entries (id, timestamp, category, duration_minutes, notes)
+                     VALUES (1, 900, 'deep_work', 15, 'old #legacy');",
+            )
+            .unwrap();
+        }
+        db
+    }
+
+    fn row(timestamp: i64, category: &str, notes: &str) -> ExportRow {
+        ExportRow {
+            timestamp,
+            category: category.to_string(),
+            duration_minutes: 15,
+            is_away: false,
+            is_retroactive: false,
+            notes: Some(notes.to_string()),
+            project: None,
+            client: None,
+        }
+    }
+
+    fn count(conn: &Connection, sql: &str) -> i64 {
+        conn.query_row(sql, [], |r| r.get(0)).unwrap()
+    }
+
+    #[test]
+    fn test_dry_run_reports_without_writing() {
+        let db = setup_db();
+        let conn = db.conn.lock().unwrap();
+        let repo = ImportRepository::new(conn);
+        let rows = vec![
+            Ok(row(900, "meetings", "clash")),
+            Ok(row(1800, "Deep Work", "new #review")),
+            Err("Row 3: bad date".to_string()),
+            Ok(row(2700, "nonsense", "")),
+        ];
+
+        let report = repo.import(rows, ConflictPolicy::Skip, true).unwrap();
+
+        assert!(report.dry_run);
+        assert_eq!((report.inserted, report.skipped, report.invalid), (1, 1, 2));
+        assert_eq!(report.rows[0].action, ImportAction::Skip);
+        assert_eq!(report.rows[0].existing_id, Some(1));
+        assert_eq!(report.rows[1].category.as_deref(), Some("deep_work"));
+        assert_eq!(report.rows[2].message.as_deref(), Some("Row 3: bad date"));
+        assert_eq!(report.rows[3].action, ImportAction::Invalid);
+        assert_eq!(count(&repo.conn, "SELECT COUNT(*) FROM time_entries"), 1);
+        assert_eq!(count(&repo.conn, "SELECT COUNT(*) FROM tags WHERE name = 'review'"), 0);
+    }
+
+    #[test]
+    fn test_import_inserts_and_skips() {
+        let db = setup_db();
+        let conn = db.conn.lock().unwrap();
+        let repo = ImportRepository::new(conn);
+        let rows = vec![Ok(row(900, "meetings", "clash")), Ok(row(1800, "admin", "#review"))];
+
+        let report = repo.import(rows, ConflictPolicy::Skip, false).unwrap();
+
+        assert_eq!((report.inserted, report.skipped), (1, 1));
+        assert_eq!(count(&repo.conn, "SELECT COUNT(*) FROM time_entries"), 2);
+        assert_eq!(
+            count(&repo.conn, "SELECT COUNT(*) FROM time_entries WHERE id = 1 AND category = 'deep_work'"),
+            1
+        );
+        assert_eq!(count(&repo.conn, "SELECT COUNT(*) FROM entry_tags"), 1);
+    }
+
+    #[test]
+    fn test_import_overwrites_and_resyncs_tags() {
+        let db = setup_db();
+        {
+            let conn = db.conn.lock().unwrap();
+            super::super::tag::attach_tag(&conn, 1, "legacy").unwrap();
+        }
+        let conn = db.conn.lock().unwrap();
+        let repo = ImportRepository::new(conn);
+
+        let report = repo
+            .import(vec![Ok(row(900, "meetings", "now #sync"))], ConflictPolicy::Overwrite, false)
+            .unwrap();
+
+        assert_eq!(report.overwritten, 1);
+        assert_eq!(report.rows[0].existing_id, Some(1));
+        let (category, notes): (String, String) = repo
+            .conn
+            .query_row("SELECT category, notes FROM time_entries WHERE id = 1", [], |r| {
+                Ok((r.get(0)?, r.get(1)?))
+            })
+            .unwrap();
+        assert_eq!((category.as_str(), notes.as_str()), ("meetings", "now #sync"));
+        assert_eq!(
+            count(
+                &repo.conn,
+                "SELECT COUNT(*) FROM entry_tags et JOIN tags t ON t.id = et.tag_id WHERE t.name = 'legacy'"
+            ),
+            0
+        );
+        assert_eq!(count(&repo.conn, "SELECT COUNT(*) FROM time_entries"), 1);
+    }
+
+    #[test]
+    fn test_import_conflicts_with_overlapping_entries() {
+        let db = setup_db();
+        let conn = db.conn.lock().unwrap();
+        let repo = ImportRepository::new(conn);
+        repo.import(vec![Ok(row(1800, "admin", ""))], ConflictPolicy::Skip, false).unwrap();
+        let mut long = row(600, "meetings", "planning");
+        long.duration_minutes = 30;
+
+        // Starts earlier than either entry, but shares time with both
+        let report = repo.import(vec![Ok(long.clone())], ConflictPolicy::Skip, false).unwrap();
+        assert_eq!(report.skipped, 1);
+        assert_eq!(report.rows[0].existing_id, Some(1));
+
+        let report = repo.import(vec![Ok(long)], ConflictPolicy::Overwrite, false).unwrap();
+        assert_eq!(report.overwritten, 1);
+        assert_eq!(report.rows[0].existing_id, Some(1));
+        assert_eq!(
+            report.rows[0].message.as_deref(),
+            Some("Moves overlapping entries 2 to the trash")
+        );
+        assert_eq!(
+            count(
+                &repo.conn,
+                "SELECT COUNT(*) FROM time_entries
+                 WHERE id = 1 AND timestamp = 600 AND duration_minutes = 30 AND category = 'meetings'"
+            ),
+            1
+        );
+        assert_eq!(count(&repo.conn, "SELECT COUNT(*) FROM time_entries WHERE deleted_at IS NULL"), 1);
+    }
+
+    #[test]
+    fn test_duplicate_rows_within_import_conflict() {
+        let db = setup_db();
+        let conn = db.conn.lock().unwrap();
+        let repo = ImportRepository::new(conn);
+        let rows = vec![Ok(row(1800, "admin", "first")), Ok(row(1800, "meetings", "second"))];
+
+        let report = repo.import(rows, ConflictPolicy::Overwrite, false).unwrap();
+
+        assert_eq!((report.inserted, report.overwritten), (1, 1));
+        assert_eq!(
+            count(&repo.conn, "SELECT COUNT(*) FROM time_entries WHERE timestamp = 1800 AND category = 'meetings'"),
+            1
+        );
+    }
+
+    #[test]
+    fn test_import_creates_missing_projects_once() {
+        let db = setup_db();
+        let conn = db.conn.lock().unwrap();
+        let repo = ImportRepository::new(conn);
+        let mut first = row(1800, "deep_work", "");
+        first.project = Some("Website".to_string());
+        first.client = Some("Acme".to_string());
+        let mut second = first.clone();
+        second.timestamp = 2700;
+
+        let report = repo.import(vec![Ok(first), Ok(second)], ConflictPolicy::Skip, false).unwrap();
+
+        assert_eq!(report.inserted, 2);
+        assert_eq!(report.rows[0].message.as_deref(), Some("Creates project 'Website'"));
+        assert!(report.rows[1].message.is_none());
+        assert_eq!(count(&repo.conn, "SELECT COUNT(*) FROM projects"), 1);
+        assert_eq!(count(&repo.conn, "SELECT COUNT(*) FROM clients"), 1);
+        assert_eq!(
+            count(&repo.conn, "SELECT COUNT(*) FROM time_entries WHERE project_id IS NOT NULL"),
+            2
+        );
+    }
+
+    #[test]
+    fn test_archived_category_is_invalid() {
+        let db = setup_db();
+        let conn = db.conn.lock().unwrap();
+        conn.execute("UPDATE categories SET archived = 1 WHERE slug = 'admin'", []).unwrap();
+        let repo = ImportRepository::new(conn);
+
+        let report = repo.import(vec![Ok(row(1800, "admin", ""))], ConflictPolicy::Skip, false).unwrap();
+
+        assert_eq!(report.invalid, 1);
+        assert_eq!(report.rows[0].message.as_deref(), Some("Category 'admin' is archived"));
+    }
+}
@@ -0,0 +1,311 @@
+use crate::db::models::{ChangeAction, ChangeSource, EntryChange};
+use rusqlite::{Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+
+use super::history;
+use super::tag::attach_tag;
+use super::time_entry::overlapping_entries;
+use super::{int_to_bool, RepositoryError, Result};
+
+/// Entry fields whose changes are kept in the history, in the order
+/// `EntrySnapshot::tracked_values` returns them
+pub(crate) const TRACKED_FIELDS: [&str; 10] = [
+    "timestamp",
+    "category",
+    "duration_minutes",
+    "is_away",
+    "is_retroactive",
+    "notes",
+    "project_id",
+    "away_reason",
+    "deleted_at",
+    "tags",
+];
+
+/// An entry row and its tags as they were before a journaled change
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct EntrySnapshot {
+    timestamp: i64,
+    category: String,
+    duration_minutes: i32,
+    is_away: i32,
+    is_retroactive: i32,
+    notes: Option<String>,
+    project_id: Option<i64>,
+    away_reason: Option<String>,
+    created_at: Option<i64>,
+    deleted_at: Option<i64>,
+    tags: Vec<String>,
+}
+
+impl EntrySnapshot {
+    pub(crate) fn load(conn: &Connection, entry_id: i64) -> Result<Option<Self>> {
+        let snapshot = conn
+            .query_row(
+                "SELECT timestamp, category, duration_minutes, is_away, is_retroactive, notes,
+                        project_id, away_reason, created_at, deleted_at
+                 FROM time_entries WHERE id = ?1",
+                [entry_id],
+                |row| {
+                    Ok(EntrySnapshot {
+                        timestamp: row.get(0)?,
+                        category: row.get(1)?,
+                        duration_minutes: row.get(2)?,
+                        is_away: row.get(3)?,
+                        is_retroactive: row.get(4)?,
+                        notes: row.get(5)?,
+                        project_id: row.get(6)?,
+                        away_reason: row.get(7)?,
+                        created_at: row.get(8)?,
+                        deleted_at: row.get(9)?,
+                        tags: Vec::new(),
+                    })
+                },
+            )
+            .optional()?;
+        let Some(mut snapshot) = snapshot else {
+            return Ok(None);
+        };
+
+        let mut stmt = conn.prepare(
+            "SELECT t.name FROM entry_tags et JOIN tags t ON t.id = et.tag_id
+             WHERE et.entry_id = ?1 ORDER BY t.name",
+        )?;
+        snapshot.tags = stmt
+            .query_map([entry_id], |row| row.get(0))?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        Ok(Some(snapshot))
+    }
+
+    /// Values of `TRACKED_FIELDS` as shown in the history
+    pub(crate) fn tracked_values(&self) -> [Option<String>; 10] {
+        [
+            Some(self.timestamp.to_string()),
+            Some(self.category.clone()),
+            Some(self.duration_minutes.to_string()),
+            Some(int_to_bool(self.is_away).to_string()),
+            Some(int_to_bool(self.is_retroactive).to_string()),
+            self.notes.clone(),
+            self.project_id.map(|id| id.to_string()),
+            self.away_reason.clone(),
+            self.deleted_at.map(|at| at.to_string()),
+            (!self.tags.is_empty()).then(|| self.tags.join(" ")),
+        ]
+    }
+
+    /// Puts the entry back exactly as captured
+    fn apply(&self, conn: &Connection, entry_id: i64) -> Result<()> {
+        conn.execute(
+            "UPDATE time_entries
+             SET timestamp = ?2, category = ?3, duration_minutes = ?4, is_away = ?5,
+                 is_retroactive = ?6, notes = ?7, project_id = ?8, away_reason = ?9,
+                 created_at = ?10, deleted_at = ?11
+             WHERE id = ?1",
+            (
+                entry_id,
+                self.timestamp,
+                &self.category,
+                self.duration_minutes,
+                self.is_away,
+                self.is_retroactive,
+                &self.notes,
+                self.project_id,
+                &self.away_reason,
+                self.created_at,
+                self.deleted_at,
+            ),
+        )?;
+
+        conn.execute("DELETE FROM entry_tags WHERE entry_id = ?1", [entry_id])?;
+        for tag in &self.tags {
+            attach_tag(conn, entry_id, tag)?;
+        }
+        Ok(())
+    }
+}
+
+/// Collects the state of every entry a user action touches, before it is
+/// touched, and writes it to the journal as one undoable change and to each
+/// entry's history
+pub(crate) struct ChangeRecorder {
+    action: ChangeAction,
+    source: ChangeSource,
+    now: i64,
+    rows: Vec<(i64, Option<EntrySnapshot>)>,
+}
+
+impl ChangeRecorder {
+    /// Starts recording a change made at `now`
+    pub(crate) fn new(action: ChangeAction, source: ChangeSource, now: i64) -> Self {
+        Self {
+            action,
+            source,
+            now,
+            rows: Vec::new(),
+        }
+    }
+
+    /// Captures an existing entry; call before changing it. Only the first
+    /// capture of an entry counts.
+    pub(crate) fn touch(&mut self, conn: &Connection, entry_id: i64) -> Result<()> {
+        if self.rows.iter().any(|(id, _)| *id == entry_id) {
+            return Ok(());
+        }
+        let before = EntrySnapshot::load(conn, entry_id)?
+            .ok_or_else(|| RepositoryError::NotFound(format!("Time entry {}", entry_id)))?;
+        self.rows.push((entry_id, Some(before)));
+        Ok(())
+    }
+
+    /// Notes an entry the action created, which undo removes again
+    pub(crate) fn created(&mut self, entry_id: i64) {
+        self.rows.push((entry_id, None));
+    }
+
+    /// Writes the change to the journal and history, along with each entry
+    /// as the change left it. Should run in the same transaction as the
+    /// change itself, once it has been made.
+    pub(crate) fn commit(self, conn: &Connection) -> Result<i64> {
+        conn.execute(
+            "INSERT INTO entry_changes (action, created_at) VALUES (?1, ?2)",
+            (self.action.as_str(), self.now),
+        )?;
+        let change_id = conn.last_insert_rowid();
+        for (entry_id, before) in &self.rows {
+            let after = EntrySnapshot::load(conn, *entry_id)?;
+            conn.execute(
+                "INSERT INTO entry_change_rows (change_id, entry_id, before, after)
+                 VALUES (?1, ?2, ?3, ?4)",
+                (change_id, entry_id, to_json(before.as_ref())?, to_json(after.as_ref())?),
+            )?;
+        }
+        for (entry_id, before) in &self.rows {
+            history::record(conn, *entry_id, self.source, before.as_ref(), self.now)?;
+        }
+        Ok(change_id)
+    }
+}
+
+fn to_json(snapshot: Option<&EntrySnapshot>) -> Result<Option<String>> {
+    snapshot
+        .map(serde_json::to_string)
+        .transpose()
+        .map_err(|e| RepositoryError::InvalidData(e.to_string()))
+}
+
+fn from_json(json: Option<&str>) -> Result<Option<EntrySnapshot>> {
+    json.map(serde_json::from_str)
+        .transpose()
+        .map_err(|e| RepositoryError::InvalidData(e.to_string()))
+}
+
+/// Reverts the most recent change that has not been undone yet, returning
+/// it, or `None` when there is nothing left to undo. Undoing repeatedly
+/// walks further back through the journal. Fails, changing nothing, when an
+/// entry has been changed since in a way the journal does not know about, or
+/// when an entry it brings back would overlap one added since. The undo is
+/// stamped with `now`.
+pub(crate) fn undo_last(conn: &Connection, now: i64) -> Result<Option<EntryChange>> {
+    let tx = conn.unchecked_transaction()?;
+    let change: Option<(i64, String, i64)> = tx
+        .query_row(
+            "SELECT id, action, created_at FROM entry_changes
+             WHERE undone_at IS NULL ORDER BY id DESC LIMIT 1",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )
+        .optional()?;
+    let Some((change_id, action, created_at)) = change else {
+        return Ok(None);
+    };
+    let action = ChangeAction::from_name(&action)
+        .ok_or_else(|| RepositoryError::InvalidData(format!("Unknown change '{}'", action)))?;
+
+    let rows: Vec<(i64, Option<String>, Option<String>)> = tx
+        .prepare("SELECT entry_id, before, after FROM entry_change_rows WHERE change_id = ?1")?
+        .query_map([change_id], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?
+        .collect::<std::result::Result<_, _>>()?;
+
+    // Putting back the old state would silently throw away whatever changed
+    // the entry since, such as an import or a tag added on its own. Rows
+    // journaled before the state after a change was kept cannot be checked.
+    let mut currents = Vec::with_capacity(rows.len());
+    for (entry_id, _, after) in &rows {
+        let current = EntrySnapshot::load(&tx, *entry_id)?;
+        if after.is_none() || from_json(after.as_deref())? != current {
+            return Err(RepositoryError::InvalidData(format!(
+                "Cannot undo: entry {} has changed since",
+                entry_id
+            )));
+        }
+        currents.push(current);
+    }
+
+    for ((entry_id, before, _), current) in rows.iter().zip(&currents) {
+        match from_json(before.as_deref())? {
+            Some(snapshot) => {
+                snapshot.apply(&tx, *entry_id)?;
+                history::record(&tx, *entry_id, ChangeSource::Undo, current.as_ref(), now)?;
+            }
+            None => {
+                // The entry goes for good; its history stays, ending in the undo
+                tx.execute("DELETE FROM entry_tags WHERE entry_id = ?1", [entry_id])?;
+                tx.execute("DELETE FROM time_entries WHERE id = ?1", [entry_id])?;
+                history::record(&tx, *entry_id, ChangeSource::Undo, current.as_ref(), now)?;
+            }
+        }
+    }
+
+    // Entries of this change only ever overlap each other the way they did
+    // before it; anything else sharing their time was added since
+    let in_change = |id: Option<i64>| rows.iter().any(|(entry_id, _, _)| Some(*entry_id) == id);
+    for (entry_id, _, _) in &rows {
+        let Some((start, end)) = live_span(&tx, *entry_id)? else {
+            continue;
+        };
+        let overlaps = overlapping_entries(&tx, start, end)?;
+        if let Some(existing) = overlaps.iter().find(|existing| !in_change(existing.id)) {
+            return Err(RepositoryError::InvalidData(format!(
+                "Cannot undo: entry {} would overlap entry {} at {}",
+                entry_id,
+                existing.id.unwrap_or_default(),
+                existing.timestamp
+            )));
+        }
+    }
+
+    tx.execute(
+        "UPDATE entry_changes SET undone_at = ?1 WHERE id = ?2",
+        (now, change_id),
+    )?;
+    tx.commit()?;
+    Ok(Some(EntryChange {
+        id: change_id,
+        action,
+        entry_ids: rows.into_iter().map(|(id, _, _)| id).collect(),
+        created_at,
+    }))
+}
+
+/// Start and end of an entry that is not in the trash
+fn live_span(conn: &Connection, entry_id: i64) -> Result<Option<(i64, i64)>> {
+    Ok(conn
+        .query_row(
+            "SELECT timestamp, timestamp + duration_minutes * 60 FROM time_entries
+             WHERE id = ?1 AND deleted_at IS NULL",
+            [entry_id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()?)
+}
+
+/// Drops journal entries recorded before `before`; they can no longer be
+/// undone. Returns how many were dropped.
+pub(crate) fn forget_before(conn: &Connection, before: i64) -> Result<usize> {
+    conn.execute(
+        "DELETE FROM entry_change_rows
+         WHERE change_id IN (SELECT id FROM entry_changes WHERE created_at < ?1)",
+        [before],
+    )?;
+    Ok(conn.execute("DELETE FROM entry_changes WHERE created_at < ?1", [before])?)
+}
@@ -21,6 +21,27 @@ impl<'a> MissedPromptRepository<'a> {
         Ok(self.conn.last_insert_rowid())
     }
 
+    /// Records a missed prompt for each interval start that is neither covered
+    /// by an entry nor has a missed prompt yet. Returns how many were added.
+    pub fn create_for_gaps(&self, timestamps: &[i64], reason: &str) -> Result<usize> {
+        let tx = self.conn.unchecked_transaction()?;
+        let mut added = 0;
+        for &timestamp in timestamps {
+            added += tx.execute(
+                "INSERT OR IGNORE INTO missed_prompts (timestamp, reason)
+                 SELECT ?1, ?2
+                 WHERE NOT EXISTS (
+                     SELECT 1 FROM time_entries
+                     WHERE timestamp <= ?1 AND timestamp + duration_minutes * 60 > ?1
+                       AND deleted_at IS NULL
+                 )",
+                (timestamp, reason),
+            )?;
+        }
+        tx.commit()?;
+        Ok(added)
+    }
+
     pub fn find_by_date_range(&self, start: i64, end: i64) -> Result<Vec<MissedPrompt>> {
         let mut stmt = self.conn.prepare(
             "SELECT id, timestamp, reason, created_at
@@ -127,4 +148,28 @@ mod tests {
         assert_eq!(prompts[1].timestamp, 2000);
         assert_eq!(prompts[2].timestamp, 3000);
     }
+
+    #[test]
+    fn test_create_for_gaps_skips_covered_intervals() {
+        let db = setup_db();
+        let conn = db.conn.lock().unwrap();
+        conn.execute(
+            "INSERT INTO time_entries (timestamp, category) VALUES (1800, 'deep_work')",
+            [],
+        )
+        .unwrap();
+        let repo = MissedPromptRepository::new(conn);
+        repo.create(2700, Some("idle")).unwrap();
+
+        let added = repo.create_for_gaps(&[900, 1800, 2700, 3600], "sleep").unwrap();
+
+        assert_eq!(added, 2);
+        let prompts = repo.find_by_date_range(0, 5000).unwrap();
+        let reasons: Vec<(i64, Option<&str>)> =
+            prompts.iter().map(|p| (p.timestamp, p.reason.as_deref())).collect();
+        assert_eq!(
+            reasons,
+            vec![(900, Some("sleep")), (2700, Some("idle")), (3600, Some("sleep"))]
+        );
+    }
 }
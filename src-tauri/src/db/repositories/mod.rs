@@ -1,11 +1,29 @@
+pub mod category;
+pub mod client;
 pub mod error;
+pub mod heartbeat;
+pub mod history;
+pub mod import;
+pub mod journal;
 pub mod missed_prompt;
+pub mod project;
+pub mod report;
+pub mod schedule;
 pub mod settings;
+pub mod tag;
 pub mod time_entry;
 
+pub use category::CategoryRepository;
+pub use client::ClientRepository;
 pub use error::{RepositoryError, Result};
+pub use heartbeat::HeartbeatRepository;
+pub use import::ImportRepository;
 pub use missed_prompt::MissedPromptRepository;
+pub use project::ProjectRepository;
+pub use report::ReportRepository;
+pub use schedule::ScheduleRepository;
 pub use settings::SettingsRepository;
+pub use tag::TagRepository;
 pub use time_entry::TimeEntryRepository;
 
 /// Convert SQLite integer (0/1) to bool
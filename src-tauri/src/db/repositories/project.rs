@@ -0,0 +1,211 @@
+use crate::db::models::Project;
+use rusqlite::{Connection, OptionalExtension, Row};
+use std::sync::MutexGuard;
+
+use super::{bool_to_int, int_to_bool, RepositoryError, Result};
+
+pub struct ProjectRepository<'a> {
+    conn: MutexGuard<'a, Connection>,
+}
+
+impl<'a> ProjectRepository<'a> {
+    pub fn new(conn: MutexGuard<'a, Connection>) -> Self {
+        Self { conn }
+    }
+
+    pub fn create(&self, client_id: Option<i64>, name: &str) -> Result<i64> {
+        let name = validate_name(name)?;
+        if let Some(client_id) = client_id {
+            self.ensure_client_exists(client_id)?;
+        }
+        self.ensure_name_available(client_id, name)?;
+        self.conn.execute(
+            "INSERT INTO projects (client_id, name) VALUES (?1, ?2)",
+            (client_id, name),
+        )?;
+        Ok(self.conn.last_insert_rowid())
+    }
+
+    /// Lists projects, optionally restricted to one client
+    pub fn find_all(&self, client_id: Option<i64>, include_archived: bool) -> Result<Vec<Project>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, client_id, name, archived, created_at
+             FROM projects
+             WHERE (?1 IS NULL OR client_id = ?1) AND (archived = 0 OR ?2)
+             ORDER BY name COLLATE NOCASE ASC",
+        )?;
+
+        let projects = stmt
+            .query_map((client_id, bool_to_int(include_archived)), map_project)?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+
+        Ok(projects)
+    }
+
+    pub fn find_by_id(&self, id: i64) -> Result<Project> {
+        self.conn
+            .query_row(
+                "SELECT id, client_id, name, archived, created_at FROM projects WHERE id = ?1",
+                [id],
+                map_project,
+            )
+            .optional()?
+            .ok_or_else(|| RepositoryError::NotFound(format!("Project {}", id)))
+    }
+
+    pub fn rename(&self, id: i64, name: &str) -> Result<()> {
+        let name = validate_name(name)?;
+        let project = self.find_by_id(id)?;
+        self.ensure_name_available(project.client_id, name)?;
+        self.conn
+            .execute("UPDATE projects SET name = ?1 WHERE id = ?2", (name, id))?;
+        Ok(())
+    }
+
+    pub fn set_archived(&self, id: i64, archived: bool) -> Result<()> {
+        let updated = self.conn.execute(
+            "UPDATE projects SET archived = ?1 WHERE id = ?2",
+            (bool_to_int(archived), id),
+        )?;
+        if updated == 0 {
+            return Err(RepositoryError::NotFound(format!("Project {}", id)));
+        }
+        Ok(())
+    }
+
+    fn ensure_client_exists(&self, client_id: i64) -> Result<()> {
+        let exists: Option<i64> = self
+            .conn
+            .query_row("SELECT id FROM clients WHERE id = ?1", [client_id], |row| row.get(0))
+            .optional()?;
+        if exists.is_none() {
+            return Err(RepositoryError::InvalidData(format!(
+                "Unknown client {}",
+                client_id
+            )));
+        }
+        Ok(())
+    }
+
+    /// Project names are unique per client (SQLite's UNIQUE treats NULL
+    /// client ids as distinct, so this is checked here as well)
+    fn ensure_name_available(&self, client_id: Option<i64>, name: &str) -> Result<()> {
+        let existing: Option<i64> = self
+            .conn
+            .query_row(
+                "SELECT id FROM projects WHERE client_id IS ?1 AND name = ?2",
+                (client_id, name),
+                |row| row.get(0),
+            )
+            .optional()?;
+        if existing.is_some() {
+            return Err(RepositoryError::InvalidData(format!(
+                "Project '{}' already exists",
+                name
+            )));
+        }
+        Ok(())
+    }
+}
+
+fn map_project(row: &Row) -> rusqlite::Result<Project> {
+    Ok(Project {
+        id: row.get(0)?,
+        client_id: row.get(1)?,
+        name: row.get(2)?,
+        archived: int_to_bool(row.get(3)?),
+        created_at: row.get(4)?,
+    })
+}
+
+fn validate_name(name: &str) -> Result<&str> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(RepositoryError::InvalidData(
+            "Project name cannot be empty".to_string(),
+        ));
+    }
+    Ok(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{connection::Database, migrations};
+
+    fn setup_db() -> Database {
+        let db = Database::new_in_memory().unwrap();
+        {
+            let conn = db.conn.lock().unwrap();
+            migrations::run_migrations(&conn).unwrap();
+            conn.execute_batch(
+                "INSERT INTO clients (id, name) VALUES (1, 'Acme');
+                 INSERT INTO clients (id, name) VALUES (2, 'Globex');",
+            )
+            .unwrap();
+        }
+        db
+    }
+
+    #[test]
+    fn test_create_and_find() {
+        let db = setup_db();
+        let conn = db.conn.lock().unwrap();
+        let repo = ProjectRepository::new(conn);
+        let id = repo.create(Some(1), "Website").unwrap();
+
+        let project = repo.find_by_id(id).unwrap();
+        assert_eq!(project.client_id, Some(1));
+        assert_eq!(project.name, "Website");
+        assert!(!project.archived);
+    }
+
+    #[test]
+    fn test_create_rejects_unknown_client() {
+        let db = setup_db();
+        let conn = db.conn.lock().unwrap();
+        let repo = ProjectRepository::new(conn);
+        assert!(matches!(repo.create(Some(99), "Website"), Err(RepositoryError::InvalidData(_))));
+    }
+
+    #[test]
+    fn test_name_unique_per_client() {
+        let db = setup_db();
+        let conn = db.conn.lock().unwrap();
+        let repo = ProjectRepository::new(conn);
+        repo.create(Some(1), "Website").unwrap();
+        repo.create(Some(2), "Website").unwrap();
+        repo.create(None, "Website").unwrap();
+        assert!(matches!(repo.create(Some(1), "Website"), Err(RepositoryError::InvalidData(_))));
+        assert!(matches!(repo.create(None, "Website"), Err(RepositoryError::InvalidData(_))));
+    }
+
+    #[test]
+    fn test_find_all_by_client() {
+        let db = setup_db();
+        let conn = db.conn.lock().unwrap();
+        let repo = ProjectRepository::new(conn);
+        repo.create(Some(1), "Website").unwrap();
+        let app = repo.create(Some(1), "App").unwrap();
+        repo.create(Some(2), "Portal").unwrap();
+        repo.set_archived(app, true).unwrap();
+
+        let names: Vec<String> = repo.find_all(Some(1), false).unwrap().into_iter().map(|p| p.name).collect();
+        assert_eq!(names, vec!["Website"]);
+        assert_eq!(repo.find_all(Some(1), true).unwrap().len(), 2);
+        assert_eq!(repo.find_all(None, true).unwrap().len(), 3);
+    }
+
+    #[test]
+    fn test_rename() {
+        let db = setup_db();
+        let conn = db.conn.lock().unwrap();
+        let repo = ProjectRepository::new(conn);
+        let id = repo.create(Some(1), "Website").unwrap();
+        repo.create(Some(1), "App").unwrap();
+
+        repo.rename(id, "Marketing Site").unwrap();
+        assert_eq!(repo.find_by_id(id).unwrap().name, "Marketing Site");
+        assert!(matches!(repo.rename(id, "App"), Err(RepositoryError::InvalidData(_))));
+    }
+}
@@ -0,0 +1,412 @@
+use crate::db::models::{CategoryMinutes, ReportBucket, ReportPeriod};
+use chrono::{Datelike, Days, Duration, NaiveDate, TimeZone};
+use rusqlite::Connection;
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+use std::sync::MutexGuard;
+
+use super::{RepositoryError, Result};
+
+/// Most buckets one report may have: ten years of days, or longer in weeks
+/// and months. Keeps a stray range from the UI from building millions.
+const MAX_REPORT_BUCKETS: usize = 3660;
+
+pub struct ReportRepository<'a> {
+    conn: MutexGuard<'a, Connection>,
+}
+
+impl<'a> ReportRepository<'a> {
+    pub fn new(conn: MutexGuard<'a, Connection>) -> Self {
+        Self { conn }
+    }
+
+    /// Minutes per category for every day, week or month overlapping
+    /// `[start, end)`, with boundaries at local midnight in `tz`. Buckets are
+    /// clipped to the requested range and returned even when empty so charts
+    /// get a continuous axis. Ranges needing more than `MAX_REPORT_BUCKETS`
+    /// buckets are rejected.
+    pub fn minutes_by_category<Tz: TimeZone>(
+        &self,
+        start: i64,
+        end: i64,
+        period: ReportPeriod,
+        tz: &Tz,
+    ) -> Result<Vec<ReportBucket>> {
+        if end <= start {
+            return Err(RepositoryError::InvalidData(format!(
+                "Report range end {} must be after start {}",
+                end, start
+            )));
+        }
+
+        let ranges = period_buckets(start, end, period, tz, MAX_REPORT_BUCKETS).ok_or_else(|| {
+            RepositoryError::InvalidData(format!(
+                "Report range {}-{} spans more than {} buckets",
+                start, end, MAX_REPORT_BUCKETS
+            ))
+        })?;
+
+        // Entries come in timestamp order and the buckets are contiguous, so
+        // each entry's bucket is found by searching the bucket ends
+        let mut totals: Vec<BTreeMap<String, i64>> = vec![BTreeMap::new(); ranges.len()];
+        let mut stmt = self.conn.prepare(
+            "SELECT timestamp, category, duration_minutes
+             FROM time_entries
+             WHERE timestamp >= ?1 AND timestamp < ?2 AND deleted_at IS NULL
+             ORDER BY timestamp ASC",
+        )?;
+        let mut rows = stmt.query([start, end])?;
+        while let Some(row) = rows.next()? {
+            let timestamp: i64 = row.get(0)?;
+            let index = ranges.partition_point(|(_, bucket_end, _)| *bucket_end <= timestamp);
+            if let Some(bucket) = totals.get_mut(index) {
+                *bucket.entry(row.get(1)?).or_default() += row.get::<_, i64>(2)?;
+            }
+        }
+
+        Ok(ranges
+            .into_iter()
+            .zip(totals)
+            .map(|((start, end, label), totals)| {
+                let mut categories: Vec<CategoryMinutes> = totals
+                    .into_iter()
+                    .map(|(category, total_minutes)| CategoryMinutes {
+                        category,
+                        total_minutes,
+                    })
+                    .collect();
+                // Largest first; the map already ordered ties by name
+                categories.sort_by_key(|c| Reverse(c.total_minutes));
+                ReportBucket {
+                    start,
+                    end,
+                    label,
+                    total_minutes: categories.iter().map(|c| c.total_minutes).sum(),
+                    categories,
+                }
+            })
+            .collect())
+    }
+}
+
+/// Splits `[start, end)` at local period boundaries in `tz`, returning
+/// `(bucket_start, bucket_end, label)` triples, or `None` when that takes
+/// more than `limit` buckets. Stops at the last date chrono can represent.
+fn period_buckets<Tz: TimeZone>(
+    start: i64,
+    end: i64,
+    period: ReportPeriod,
+    tz: &Tz,
+    limit: usize,
+) -> Option<Vec<(i64, i64, String)>> {
+    let Some(first) = tz.timestamp_opt(start, 0).earliest() else {
+        return Some(Vec::new());
+    };
+
+    let mut date = period_start(first.date_naive(), period);
+    let mut buckets = Vec::new();
+    loop {
+        let next = next_period_start(date, period);
+        let bucket_start = local_midnight(tz, date).max(start);
+        if bucket_start >= end {
+            break;
+        }
+        if buckets.len() == limit {
+            return None;
+        }
+        let bucket_end = next.map_or(end, |next| local_midnight(tz, next).min(end));
+        buckets.push((bucket_start, bucket_end, period_label(date, period)));
+        match next {
+            Some(next) => date = next,
+            None => break,
+        }
+    }
+    Some(buckets)
+}
+
+fn period_start(date: NaiveDate, period: ReportPeriod) -> NaiveDate {
+    match period {
+        ReportPeriod::Day => date,
+        ReportPeriod::Week => date
+            .checked_sub_days(Days::new(date.weekday().num_days_from_monday() as u64))
+            .unwrap_or(date),
+        ReportPeriod::Month => date.with_day(1).unwrap_or(date),
+    }
+}
+
+fn next_period_start(date: NaiveDate, period: ReportPeriod) -> Option<NaiveDate> {
+    match period {
+        ReportPeriod::Day => date.succ_opt(),
+        ReportPeriod::Week => date.checked_add_days(Days::new(7)),
+        ReportPeriod::Month => {
+            let (year, month) = if date.month() == 12 {
+                (date.year() + 1, 1)
+            } else {
+                (date.year(), date.month() + 1)
+            };
+            NaiveDate::from_ymd_opt(year, month, 1)
+        }
+    }
+}
+
+fn period_label(date: NaiveDate, period: ReportPeriod) -> String {
+    match period {
+        ReportPeriod::Day => date.format("%Y-%m-%d").to_string(),
+        ReportPeriod::Week => date.format("%G-W%V").to_string(),
+        ReportPeriod::Month => date.format("%Y-%m").to_string(),
+    }
+}
+
+/// Timestamp of the first instant of `date` in `tz`. Zones that move their
+/// clocks at midnight skip it on transition days, so fall forward to the
+/// first local time that exists.
+pub(crate) fn local_midnight<Tz: TimeZone>(tz: &Tz, date: NaiveDate) -> i64 {
+    let midnight = date.and_hms_opt(0, 0, 0).expect("midnight is a valid time");
+    (0..=24)
+        .find_map(|hour| {
+            let time = midnight.checked_add_signed(Duration::hours(hour))?;
+            tz.from_local_datetime(&time).earliest()
+        })
+        .map(|dt| dt.timestamp())
+        .unwrap_or_else(|| midnight.and_utc().timestamp())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{connection::Database, migrations};
+    use chrono::Utc;
+    use chrono_tz::America::{New_York, Sao_Paulo};
+    use chrono_tz::Europe::Berlin;
+
+    fn setup_db() -> Database {
+        let db = Database::new_in_memory().unwrap();
+        {
+            let conn = db.conn.lock().unwrap();
+            migrations::run_migrations(&conn).unwrap();
+        }
+        db
+    }
+
+    fn ts<Tz: TimeZone>(tz: &Tz, y: i32, m: u32, d: u32, h: u32, min: u32) -> i64 {
+        tz.with_ymd_and_hms(y, m, d, h, min, 0).earliest().unwrap().timestamp()
+    }
+
+    fn insert(conn: &Connection, timestamp: i64, category: &str, minutes: i32) {
+        conn.execute(
+            "INSERT INTO time_entries (timestamp, category, duration_minutes) VALUES (?1, ?2, ?3)",
+            (timestamp, category, minutes),
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_daily_buckets_group_by_category() {
+        let db = setup_db();
+        let conn = db.conn.lock().unwrap();
+        insert(&conn, ts(&Utc, 2024, 5, 1, 9, 0), "deep_work", 15);
+        insert(&conn, ts(&Utc, 2024, 5, 1, 9, 15), "deep_work", 15);
+        insert(&conn, ts(&Utc, 2024, 5, 1, 10, 0), "meetings", 30);
+        insert(&conn, ts(&Utc, 2024, 5, 3, 23, 45), "admin", 15);
+        let repo = ReportRepository::new(conn);
+
+        let buckets = repo
+            .minutes_by_category(
+                ts(&Utc, 2024, 5, 1, 0, 0),
+                ts(&Utc, 2024, 5, 4, 0, 0),
+                ReportPeriod::Day,
+                &Utc,
+            )
+            .unwrap();
+
+        let labels: Vec<&str> = buckets.iter().map(|b| b.label.as_str()).collect();
+        assert_eq!(labels, vec!["2024-05-01", "2024-05-02", "2024-05-03"]);
+        assert_eq!(buckets[0].total_minutes, 60);
+        assert_eq!(buckets[0].categories.len(), 2);
+        assert_eq!(buckets[0].categories[0].category, "deep_work");
+        assert_eq!(buckets[0].categories[0].total_minutes, 30);
+        assert!(buckets[1].categories.is_empty());
+        assert_eq!(buckets[2].categories[0].category, "admin");
+    }
+
+    #[test]
+    fn test_local_midnight_decides_the_day() {
+        let db = setup_db();
+        let conn = db.conn.lock().unwrap();
+        // 23:30 in New York on May 1st is already May 2nd in UTC
+        insert(&conn, ts(&New_York, 2024, 5, 1, 23, 30), "deep_work", 15);
+        let repo = ReportRepository::new(conn);
+
+        let buckets = repo
+            .minutes_by_category(
+                ts(&New_York, 2024, 5, 1, 0, 0),
+                ts(&New_York, 2024, 5, 3, 0, 0),
+                ReportPeriod::Day,
+                &New_York,
+            )
+            .unwrap();
+
+        assert_eq!(buckets[0].label, "2024-05-01");
+        assert_eq!(buckets[0].total_minutes, 15);
+        assert_eq!(buckets[1].total_minutes, 0);
+    }
+
+    #[test]
+    fn test_spring_forward_day_is_23_hours() {
+        let db = setup_db();
+        let conn = db.conn.lock().unwrap();
+        // Berlin skips 02:00-03:00 on 2024-03-31
+        insert(&conn, ts(&Berlin, 2024, 3, 31, 23, 45), "deep_work", 15);
+        insert(&conn, ts(&Berlin, 2024, 4, 1, 0, 0), "meetings", 15);
+        let repo = ReportRepository::new(conn);
+
+        let buckets = repo
+            .minutes_by_category(
+                ts(&Berlin, 2024, 3, 31, 0, 0),
+                ts(&Berlin, 2024, 4, 2, 0, 0),
+                ReportPeriod::Day,
+                &Berlin,
+            )
+            .unwrap();
+
+        assert_eq!(buckets.len(), 2);
+        assert_eq!(buckets[0].end - buckets[0].start, 23 * 3600);
+        assert_eq!(buckets[0].categories[0].category, "deep_work");
+        assert_eq!(buckets[1].categories[0].category, "meetings");
+    }
+
+    #[test]
+    fn test_fall_back_day_is_25_hours() {
+        let db = setup_db();
+        let conn = db.conn.lock().unwrap();
+        // New York repeats 01:00-02:00 on 2024-11-03; both 01:30s land on the same day
+        let first = ts(&New_York, 2024, 11, 3, 1, 30);
+        insert(&conn, first, "deep_work", 15);
+        insert(&conn, first + 3600, "deep_work", 15);
+        insert(&conn, ts(&New_York, 2024, 11, 3, 23, 45), "admin", 15);
+        let repo = ReportRepository::new(conn);
+
+        let buckets = repo
+            .minutes_by_category(
+                ts(&New_York, 2024, 11, 3, 0, 0),
+                ts(&New_York, 2024, 11, 5, 0, 0),
+                ReportPeriod::Day,
+                &New_York,
+            )
+            .unwrap();
+
+        assert_eq!(buckets[0].label, "2024-11-03");
+        assert_eq!(buckets[0].end - buckets[0].start, 25 * 3600);
+        assert_eq!(buckets[0].total_minutes, 45);
+        assert_eq!(buckets[1].total_minutes, 0);
+    }
+
+    #[test]
+    fn test_midnight_gap_starts_day_at_first_valid_time() {
+        // Sao Paulo moved clocks forward at midnight on 2018-11-04
+        let start = local_midnight(&Sao_Paulo, NaiveDate::from_ymd_opt(2018, 11, 4).unwrap());
+        assert_eq!(start, ts(&Sao_Paulo, 2018, 11, 4, 1, 0));
+    }
+
+    #[test]
+    fn test_weekly_buckets_are_iso_weeks() {
+        let db = setup_db();
+        let conn = db.conn.lock().unwrap();
+        insert(&conn, ts(&Berlin, 2024, 3, 24, 12, 0), "deep_work", 15);
+        insert(&conn, ts(&Berlin, 2024, 3, 25, 12, 0), "deep_work", 30);
+        insert(&conn, ts(&Berlin, 2024, 3, 31, 23, 0), "admin", 15);
+        let repo = ReportRepository::new(conn);
+
+        let buckets = repo
+            .minutes_by_category(
+                ts(&Berlin, 2024, 3, 20, 0, 0),
+                ts(&Berlin, 2024, 4, 1, 0, 0),
+                ReportPeriod::Week,
+                &Berlin,
+            )
+            .unwrap();
+
+        let labels: Vec<&str> = buckets.iter().map(|b| b.label.as_str()).collect();
+        assert_eq!(labels, vec!["2024-W12", "2024-W13"]);
+        // First bucket is clipped to the requested start (a Wednesday)
+        assert_eq!(buckets[0].start, ts(&Berlin, 2024, 3, 20, 0, 0));
+        assert_eq!(buckets[0].total_minutes, 15);
+        // The week containing the DST switch is an hour short
+        assert_eq!(buckets[1].end - buckets[1].start, 7 * 24 * 3600 - 3600);
+        assert_eq!(buckets[1].total_minutes, 45);
+    }
+
+    #[test]
+    fn test_monthly_buckets() {
+        let db = setup_db();
+        let conn = db.conn.lock().unwrap();
+        insert(&conn, ts(&New_York, 2024, 1, 31, 23, 45), "deep_work", 15);
+        insert(&conn, ts(&New_York, 2024, 2, 1, 0, 0), "meetings", 15);
+        insert(&conn, ts(&New_York, 2024, 12, 15, 9, 0), "admin", 15);
+        let repo = ReportRepository::new(conn);
+
+        let buckets = repo
+            .minutes_by_category(
+                ts(&New_York, 2024, 1, 1, 0, 0),
+                ts(&New_York, 2025, 1, 1, 0, 0),
+                ReportPeriod::Month,
+                &New_York,
+            )
+            .unwrap();
+
+        assert_eq!(buckets.len(), 12);
+        assert_eq!(buckets[0].label, "2024-01");
+        assert_eq!(buckets[0].categories[0].category, "deep_work");
+        assert_eq!(buckets[1].categories[0].category, "meetings");
+        assert_eq!(buckets[11].label, "2024-12");
+        assert_eq!(buckets[11].total_minutes, 15);
+    }
+
+    #[test]
+    fn test_rejects_ranges_with_too_many_buckets() {
+        let db = setup_db();
+        let conn = db.conn.lock().unwrap();
+        let repo = ReportRepository::new(conn);
+        assert!(matches!(
+            repo.minutes_by_category(0, i64::MAX, ReportPeriod::Day, &Utc),
+            Err(RepositoryError::InvalidData(_))
+        ));
+        let buckets = repo
+            .minutes_by_category(
+                ts(&Utc, 2020, 1, 1, 0, 0),
+                ts(&Utc, 2030, 1, 1, 0, 0),
+                ReportPeriod::Month,
+                &Utc,
+            )
+            .unwrap();
+        assert_eq!(buckets.len(), 120);
+    }
+
+    #[test]
+    fn test_range_at_the_end_of_the_calendar() {
+        let db = setup_db();
+        let conn = db.conn.lock().unwrap();
+        let repo = ReportRepository::new(conn);
+        let last_day = local_midnight(&Utc, NaiveDate::MAX);
+
+        let buckets = repo
+            .minutes_by_category(last_day, last_day + 40 * 86400, ReportPeriod::Week, &Utc)
+            .unwrap();
+
+        assert_eq!(buckets.len(), 1);
+        assert_eq!(buckets[0].end, last_day + 40 * 86400);
+        let beyond = repo.minutes_by_category(i64::MAX - 10, i64::MAX, ReportPeriod::Day, &Utc);
+        assert!(beyond.unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_rejects_empty_range() {
+        let db = setup_db();
+        let conn = db.conn.lock().unwrap();
+        let repo = ReportRepository::new(conn);
+        assert!(matches!(
+            repo.minutes_by_category(100, 100, ReportPeriod::Day, &Utc),
+            Err(RepositoryError::InvalidData(_))
+        ));
+    }
+}
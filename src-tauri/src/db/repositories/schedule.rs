@@ -0,0 +1,215 @@
+use crate::db::models::{DayOff, WorkBlock, WorkSchedule};
+use chrono::NaiveDate;
+use rusqlite::Connection;
+use std::sync::MutexGuard;
+
+use super::{RepositoryError, Result};
+
+const MINUTES_PER_DAY: u32 = 24 * 60;
+
+pub struct ScheduleRepository<'a> {
+    conn: MutexGuard<'a, Connection>,
+}
+
+impl<'a> ScheduleRepository<'a> {
+    pub fn new(conn: MutexGuard<'a, Connection>) -> Self {
+        Self { conn }
+    }
+
+    pub fn load(&self) -> Result<WorkSchedule> {
+        Ok(WorkSchedule {
+            blocks: self.find_blocks()?,
+            days_off: self.find_days_off()?,
+        })
+    }
+
+    /// Working blocks ordered by weekday, then start time
+    pub fn find_blocks(&self) -> Result<Vec<WorkBlock>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT weekday, start_minute, end_minute FROM work_hours
+             ORDER BY weekday ASC, start_minute ASC",
+        )?;
+
+        let blocks = stmt
+            .query_map([], |row| {
+                Ok(WorkBlock {
+                    weekday: row.get(0)?,
+                    start_minute: row.get(1)?,
+                    end_minute: row.get(2)?,
+                })
+            })?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+
+        Ok(blocks)
+    }
+
+    /// Replaces the whole weekly schedule. Blocks on the same weekday may not
+    /// overlap; an empty list turns the schedule off.
+    pub fn replace_blocks(&self, blocks: &[WorkBlock]) -> Result<()> {
+        validate_blocks(blocks)?;
+
+        let tx = self.conn.unchecked_transaction()?;
+        tx.execute("DELETE FROM work_hours", [])?;
+        for block in blocks {
+            tx.execute(
+                "INSERT INTO work_hours (weekday, start_minute, end_minute) VALUES (?1, ?2, ?3)",
+                (block.weekday, block.start_minute, block.end_minute),
+            )?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    pub fn find_days_off(&self) -> Result<Vec<DayOff>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT date, label FROM days_off ORDER BY date ASC")?;
+
+        let days = stmt
+            .query_map([], |row| {
+                Ok(DayOff {
+                    date: row.get(0)?,
+                    label: row.get(1)?,
+                })
+            })?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+
+        Ok(days)
+    }
+
+    /// Adds a day off, or relabels it if the date is already listed
+    pub fn add_day_off(&self, date: &str, label: Option<&str>) -> Result<()> {
+        let date = parse_date(date)?;
+        self.conn.execute(
+            "INSERT INTO days_off (date, label) VALUES (?1, ?2)
+             ON CONFLICT(date) DO UPDATE SET label = excluded.label",
+            (date.format("%Y-%m-%d").to_string(), label),
+        )?;
+        Ok(())
+    }
+
+    pub fn remove_day_off(&self, date: &str) -> Result<()> {
+        let date = parse_date(date)?;
+        let removed = self.conn.execute(
+            "DELETE FROM days_off WHERE date = ?1",
+            [date.format("%Y-%m-%d").to_string()],
+        )?;
+        if removed == 0 {
+            return Err(RepositoryError::NotFound(format!("Day off {}", date)));
+        }
+        Ok(())
+    }
+}
+
+fn parse_date(date: &str) -> Result<NaiveDate> {
+    NaiveDate::parse_from_str(date.trim(), "%Y-%m-%d")
+        .map_err(|_| RepositoryError::InvalidData(format!("'{}' is not a YYYY-MM-DD date", date)))
+}
+
+fn validate_blocks(blocks: &[WorkBlock]) -> Result<()> {
+    for block in blocks {
+        if block.weekday > 6 {
+            return Err(RepositoryError::InvalidData(format!(
+                "Weekday {} must be between 0 (Monday) and 6 (Sunday)",
+                block.weekday
+            )));
+        }
+        if block.start_minute >= block.end_minute || block.end_minute > MINUTES_PER_DAY {
+            return Err(RepositoryError::InvalidData(format!(
+                "Working hours {}-{} must start before they end, within one day",
+                block.start_minute, block.end_minute
+            )));
+        }
+    }
+
+    let mut sorted = blocks.to_vec();
+    sorted.sort_by_key(|b| (b.weekday, b.start_minute));
+    for pair in sorted.windows(2) {
+        if pair[0].weekday == pair[1].weekday && pair[1].start_minute < pair[0].end_minute {
+            return Err(RepositoryError::InvalidData(format!(
+                "Working hours overlap on weekday {}",
+                pair[0].weekday
+            )));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{connection::Database, migrations};
+
+    fn setup_db() -> Database {
+        let db = Database::new_in_memory().unwrap();
+        {
+            let conn = db.conn.lock().unwrap();
+            migrations::run_migrations(&conn).unwrap();
+        }
+        db
+    }
+
+    fn block(weekday: u32, start_minute: u32, end_minute: u32) -> WorkBlock {
+        WorkBlock {
+            weekday,
+            start_minute,
+            end_minute,
+        }
+    }
+
+    #[test]
+    fn test_replace_and_find_blocks() {
+        let db = setup_db();
+        let conn = db.conn.lock().unwrap();
+        let repo = ScheduleRepository::new(conn);
+        repo.replace_blocks(&[block(0, 780, 1020), block(0, 540, 720), block(4, 540, 960)])
+            .unwrap();
+
+        assert_eq!(
+            repo.find_blocks().unwrap(),
+            vec![block(0, 540, 720), block(0, 780, 1020), block(4, 540, 960)]
+        );
+
+        repo.replace_blocks(&[]).unwrap();
+        assert!(repo.find_blocks().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_replace_blocks_rejects_invalid_blocks() {
+        let db = setup_db();
+        let conn = db.conn.lock().unwrap();
+        let repo = ScheduleRepository::new(conn);
+        repo.replace_blocks(&[block(1, 540, 1020)]).unwrap();
+
+        for invalid in [
+            vec![block(7, 540, 600)],
+            vec![block(0, 600, 600)],
+            vec![block(0, 600, 1441)],
+            vec![block(2, 540, 720), block(2, 700, 800)],
+        ] {
+            assert!(matches!(repo.replace_blocks(&invalid), Err(RepositoryError::InvalidData(_))));
+        }
+        // A rejected schedule leaves the previous one in place
+        assert_eq!(repo.find_blocks().unwrap(), vec![block(1, 540, 1020)]);
+    }
+
+    #[test]
+    fn test_days_off() {
+        let db = setup_db();
+        let conn = db.conn.lock().unwrap();
+        let repo = ScheduleRepository::new(conn);
+        repo.add_day_off("2024-12-25", Some("Christmas")).unwrap();
+        repo.add_day_off("2024-01-01", None).unwrap();
+        repo.add_day_off("2024-01-01", Some("New Year")).unwrap();
+
+        let days = repo.load().unwrap().days_off;
+        assert_eq!(days.len(), 2);
+        assert_eq!(days[0].date, "2024-01-01");
+        assert_eq!(days[0].label.as_deref(), Some("New Year"));
+
+        repo.remove_day_off("2024-12-25").unwrap();
+        assert_eq!(repo.find_days_off().unwrap().len(), 1);
+        assert!(matches!(repo.remove_day_off("2024-12-25"), Err(RepositoryError::NotFound(_))));
+        assert!(matches!(repo.add_day_off("25/12/2024", None), Err(RepositoryError::InvalidData(_))));
+    }
+}
@@ -1,8 +1,12 @@
-use crate::db::models::Setting;
+use crate::db::models::{IdleBackend, NotificationSound, Setting, SettingChange, Settings};
 use rusqlite::Connection;
 use std::sync::MutexGuard;
 
-use super::Result;
+use super::{RepositoryError, Result};
+
+const MAX_IDLE_THRESHOLD_MINUTES: u32 = 120;
+const MAX_BACKUP_COUNT: u32 = 100;
+const MAX_TRASH_RETENTION_DAYS: u32 = 365;
 
 pub struct SettingsRepository<'a> {
     conn: MutexGuard<'a, Connection>,
@@ -19,12 +23,12 @@ impl<'a> SettingsRepository<'a> {
         Ok(result)
     }
 
-    pub fn set(&self, key: &str, value: &str) -> Result<()> {
-        self.conn.execute(
-            "INSERT OR REPLACE INTO settings (key, value) VALUES (?1, ?2)",
-            (key, value),
-        )?;
-        Ok(())
+    /// Validates and stores one setting, returning the normalized value.
+    /// Unknown keys and out-of-range values are rejected.
+    pub fn set(&self, key: &str, value: &str) -> Result<SettingChange> {
+        let change = parse_setting(key, value)?;
+        store(&self.conn, &change)?;
+        Ok(change)
     }
 
     pub fn get_all(&self) -> Result<Vec<Setting>> {
@@ -42,42 +46,206 @@ impl<'a> SettingsRepository<'a> {
         Ok(settings)
     }
 
+    /// Reads every known setting. Missing keys take their default; a stored
+    /// value that fails validation is an error rather than silently replaced.
+    pub fn load(&self) -> Result<Settings> {
+        let mut settings = Settings::default();
+        for field in Settings::default().fields() {
+            if let Some(value) = self.get(field.key())? {
+                settings.apply(&parse_setting(field.key(), &value)?);
+            }
+        }
+        Ok(settings)
+    }
+
+    /// Reads every known setting like `load`, except that a stored value
+    /// failing validation only falls back to its own default, with a warning
+    /// naming the key, so one bad value leaves the others in effect
+    pub fn load_or_default(&self) -> Settings {
+        let mut settings = Settings::default();
+        for field in Settings::default().fields() {
+            if let Some(change) = self.stored(field.key()) {
+                settings.apply(&change);
+            }
+        }
+        settings
+    }
+
+    /// Validates and writes all settings in one transaction. Returns the
+    /// settings whose stored value changed; a stored value that fails
+    /// validation counts as changed, so saving repairs it.
+    pub fn save(&self, settings: &Settings) -> Result<Vec<SettingChange>> {
+        let fields = settings.fields();
+        for field in &fields {
+            parse_setting(field.key(), &field.value())?;
+        }
+
+        let mut changed = Vec::new();
+        for (new, default) in fields.into_iter().zip(Settings::default().fields()) {
+            let old = match self.get(new.key())? {
+                Some(value) => parse_setting(new.key(), &value).ok(),
+                None => Some(default),
+            };
+            if old.as_ref() != Some(&new) {
+                changed.push(new);
+            }
+        }
+
+        let tx = self.conn.unchecked_transaction()?;
+        for change in &changed {
+            store(&tx, change)?;
+        }
+        tx.commit()?;
+        Ok(changed)
+    }
+
     /// Get interval_minutes setting, defaults to 15
     pub fn get_interval_minutes(&self) -> u64 {
-        self.get("interval_minutes")
-            .ok()
-            .flatten()
-            .and_then(|v| v.parse().ok())
-            .unwrap_or(15)
+        self.with_stored("interval_minutes").interval_minutes
     }
 
     /// Get idle_threshold_minutes setting, defaults to 5
     pub fn get_idle_threshold_minutes(&self) -> u32 {
-        self.get("idle_threshold_minutes")
-            .ok()
-            .flatten()
-            .and_then(|v| v.parse().ok())
-            .unwrap_or(5)
+        self.with_stored("idle_threshold_minutes").idle_threshold_minutes
     }
 
     /// Get notification_enabled setting, defaults to true
     pub fn is_notification_enabled(&self) -> bool {
-        self.get("notification_enabled")
-            .ok()
-            .flatten()
-            .map(|v| v == "true")
-            .unwrap_or(true)
+        self.with_stored("notification_enabled").notification_enabled
     }
 
     /// Get notification_sound setting, defaults to "default"
     pub fn get_notification_sound(&self) -> String {
-        self.get("notification_sound")
+        self.with_stored("notification_sound")
+            .notification_sound
+            .as_str()
+            .to_string()
+    }
+
+    /// Get idle_backend setting, defaults to auto-detection
+    pub fn get_idle_backend(&self) -> IdleBackend {
+        self.with_stored("idle_backend").idle_backend
+    }
+
+    /// Get backup_count setting, defaults to 7
+    pub fn get_backup_count(&self) -> usize {
+        self.with_stored("backup_count").backup_count as usize
+    }
+
+    /// Get trash_retention_days setting, defaults to 30
+    pub fn get_trash_retention_days(&self) -> u32 {
+        self.with_stored("trash_retention_days").trash_retention_days
+    }
+
+    /// The default settings with only `key` read from the database
+    fn with_stored(&self, key: &str) -> Settings {
+        let mut settings = Settings::default();
+        if let Some(change) = self.stored(key) {
+            settings.apply(&change);
+        }
+        settings
+    }
+
+    /// The stored value of one setting, or `None` when it is missing or
+    /// cannot be used; the latter is logged
+    fn stored(&self, key: &str) -> Option<SettingChange> {
+        let value = match self.get(key) {
+            Ok(value) => value?,
+            Err(e) => {
+                log::warn!("Could not read setting {}, using its default: {}", key, e);
+                return None;
+            }
+        };
+        parse_setting(key, &value)
+            .inspect_err(|e| log::warn!("Invalid setting {}, using its default: {}", key, e))
             .ok()
-            .flatten()
-            .unwrap_or_else(|| "default".to_string())
     }
 }
 
+fn store(conn: &Connection, change: &SettingChange) -> Result<()> {
+    conn.execute(
+        "INSERT OR REPLACE INTO settings (key, value) VALUES (?1, ?2)",
+        (change.key(), change.value()),
+    )?;
+    Ok(())
+}
+
+/// Parses and validates a raw key/value pair as sent by the settings UI
+pub fn parse_setting(key: &str, value: &str) -> Result<SettingChange> {
+    let value = value.trim();
+    match key {
+        "interval_minutes" => {
+            let minutes: u64 = parse_number(key, value)?;
+            // Prompts line up with the hour, so the interval has to divide it
+            if minutes == 0 || 60 % minutes != 0 {
+                return Err(invalid(key, value, "must be a whole divisor of 60 minutes"));
+            }
+            Ok(SettingChange::IntervalMinutes(minutes))
+        }
+        "idle_threshold_minutes" => {
+            let minutes: u32 = parse_number(key, value)?;
+            if minutes == 0 || minutes > MAX_IDLE_THRESHOLD_MINUTES {
+                return Err(invalid(
+                    key,
+                    value,
+                    &format!("must be between 1 and {} minutes", MAX_IDLE_THRESHOLD_MINUTES),
+                ));
+            }
+            Ok(SettingChange::IdleThresholdMinutes(minutes))
+        }
+        "notification_enabled" => match value {
+            "true" => Ok(SettingChange::NotificationEnabled(true)),
+            "false" => Ok(SettingChange::NotificationEnabled(false)),
+            _ => Err(invalid(key, value, "must be true or false")),
+        },
+        "notification_sound" => NotificationSound::from_name(value)
+            .map(SettingChange::NotificationSound)
+            .ok_or_else(|| {
+                let names: Vec<&str> = NotificationSound::ALL.iter().map(|s| s.as_str()).collect();
+                invalid(key, value, &format!("must be one of {}", names.join(", ")))
+            }),
+        "idle_backend" => IdleBackend::from_name(value)
+            .map(SettingChange::IdleBackend)
+            .ok_or_else(|| {
+                let names: Vec<&str> = IdleBackend::ALL.iter().map(|b| b.as_str()).collect();
+                invalid(key, value, &format!("must be one of {}", names.join(", ")))
+            }),
+        "backup_count" => {
+            let count: u32 = parse_number(key, value)?;
+            if count == 0 || count > MAX_BACKUP_COUNT {
+                return Err(invalid(
+                    key,
+                    value,
+                    &format!("must be between 1 and {}", MAX_BACKUP_COUNT),
+                ));
+            }
+            Ok(SettingChange::BackupCount(count))
+        }
+        "trash_retention_days" => {
+            let days: u32 = parse_number(key, value)?;
+            if days == 0 || days > MAX_TRASH_RETENTION_DAYS {
+                return Err(invalid(
+                    key,
+                    value,
+                    &format!("must be between 1 and {} days", MAX_TRASH_RETENTION_DAYS),
+                ));
+            }
+            Ok(SettingChange::TrashRetentionDays(days))
+        }
+        _ => Err(RepositoryError::InvalidData(format!("Unknown setting '{}'", key))),
+    }
+}
+
+fn parse_number<T: std::str::FromStr>(key: &str, value: &str) -> Result<T> {
+    value
+        .parse()
+        .map_err(|_| invalid(key, value, "must be a whole number"))
+}
+
+fn invalid(key: &str, value: &str, reason: &str) -> RepositoryError {
+    RepositoryError::InvalidData(format!("{} = '{}' {}", key, value, reason))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -133,6 +301,175 @@ mod tests {
         assert_eq!(repo.get("interval_minutes").unwrap(), Some("30".to_string()));
     }
 
+    #[test]
+    fn test_set_normalizes_value() {
+        let db = setup_db();
+        let conn = db.conn.lock().unwrap();
+        let repo = SettingsRepository::new(conn);
+        assert_eq!(
+            repo.set("notification_sound", " Glass ").unwrap(),
+            SettingChange::NotificationSound(NotificationSound::Glass)
+        );
+        assert_eq!(repo.get("notification_sound").unwrap(), Some("glass".to_string()));
+    }
+
+    #[test]
+    fn test_set_rejects_unknown_key_and_invalid_value() {
+        let db = setup_db();
+        let conn = db.conn.lock().unwrap();
+        let repo = SettingsRepository::new(conn);
+        match repo.set("theme", "dark") {
+            Err(RepositoryError::InvalidData(msg)) => assert_eq!(msg, "Unknown setting 'theme'"),
+            other => panic!("expected InvalidData, got {:?}", other),
+        }
+        match repo.set("interval_minutes", "abc") {
+            Err(RepositoryError::InvalidData(msg)) => {
+                assert_eq!(msg, "interval_minutes = 'abc' must be a whole number")
+            }
+            other => panic!("expected InvalidData, got {:?}", other),
+        }
+        assert_eq!(repo.get("theme").unwrap(), None);
+        assert_eq!(repo.get_interval_minutes(), 15);
+    }
+
+    #[test]
+    fn test_parse_interval_minutes() {
+        assert_eq!(
+            parse_setting("interval_minutes", " 1 ").unwrap(),
+            SettingChange::IntervalMinutes(1)
+        );
+        for bad in ["0", "7", "90", "abc", "-15", ""] {
+            assert!(parse_setting("interval_minutes", bad).is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn test_parse_idle_threshold_minutes() {
+        assert_eq!(
+            parse_setting("idle_threshold_minutes", "10").unwrap(),
+            SettingChange::IdleThresholdMinutes(10)
+        );
+        for bad in ["0", "121", "soon"] {
+            assert!(parse_setting("idle_threshold_minutes", bad).is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn test_parse_notification_enabled() {
+        assert_eq!(
+            parse_setting("notification_enabled", "false").unwrap(),
+            SettingChange::NotificationEnabled(false)
+        );
+        assert!(parse_setting("notification_enabled", "yes").is_err());
+    }
+
+    #[test]
+    fn test_parse_notification_sound() {
+        assert_eq!(
+            parse_setting("notification_sound", "TINK").unwrap(),
+            SettingChange::NotificationSound(NotificationSound::Tink)
+        );
+        assert!(parse_setting("notification_sound", "airhorn").is_err());
+    }
+
+    #[test]
+    fn test_load_defaults_and_stored_values() {
+        let db = setup_db();
+        let conn = db.conn.lock().unwrap();
+        let repo = SettingsRepository::new(conn);
+        assert_eq!(repo.load().unwrap(), Settings::default());
+
+        repo.set("idle_threshold_minutes", "30").unwrap();
+        repo.conn.execute("DELETE FROM settings WHERE key = 'interval_minutes'", []).unwrap();
+        let settings = repo.load().unwrap();
+        assert_eq!(settings.idle_threshold_minutes, 30);
+        assert_eq!(settings.interval_minutes, 15);
+    }
+
+    #[test]
+    fn test_load_rejects_corrupt_value() {
+        let db = setup_db();
+        let conn = db.conn.lock().unwrap();
+        let repo = SettingsRepository::new(conn);
+        repo.conn
+            .execute("UPDATE settings SET value = '0' WHERE key = 'interval_minutes'", [])
+            .unwrap();
+        assert!(matches!(repo.load(), Err(RepositoryError::InvalidData(_))));
+    }
+
+    #[test]
+    fn test_corrupt_value_falls_back_on_its_own() {
+        let db = setup_db();
+        let conn = db.conn.lock().unwrap();
+        let repo = SettingsRepository::new(conn);
+        repo.set("idle_threshold_minutes", "30").unwrap();
+        repo.set("trash_retention_days", "90").unwrap();
+        repo.conn
+            .execute("UPDATE settings SET value = '0' WHERE key = 'interval_minutes'", [])
+            .unwrap();
+
+        assert_eq!(repo.get_interval_minutes(), 15);
+        assert_eq!(repo.get_idle_threshold_minutes(), 30);
+        assert_eq!(repo.get_trash_retention_days(), 90);
+        let settings = repo.load_or_default();
+        assert_eq!(settings.interval_minutes, 15);
+        assert_eq!(settings.idle_threshold_minutes, 30);
+    }
+
+    #[test]
+    fn test_save_repairs_corrupt_value() {
+        let db = setup_db();
+        let conn = db.conn.lock().unwrap();
+        let repo = SettingsRepository::new(conn);
+        repo.conn
+            .execute("UPDATE settings SET value = '0' WHERE key = 'interval_minutes'", [])
+            .unwrap();
+
+        let changed = repo.save(&Settings::default()).unwrap();
+
+        assert_eq!(changed, vec![SettingChange::IntervalMinutes(15)]);
+        assert_eq!(repo.load().unwrap(), Settings::default());
+    }
+
+    #[test]
+    fn test_save_writes_only_changes() {
+        let db = setup_db();
+        let conn = db.conn.lock().unwrap();
+        let repo = SettingsRepository::new(conn);
+        let settings = Settings {
+            interval_minutes: 30,
+            notification_sound: NotificationSound::Hero,
+            ..Settings::default()
+        };
+
+        let changed = repo.save(&settings).unwrap();
+
+        assert_eq!(
+            changed,
+            vec![
+                SettingChange::IntervalMinutes(30),
+                SettingChange::NotificationSound(NotificationSound::Hero),
+            ]
+        );
+        assert_eq!(repo.load().unwrap(), settings);
+        assert!(repo.save(&settings).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_save_is_all_or_nothing() {
+        let db = setup_db();
+        let conn = db.conn.lock().unwrap();
+        let repo = SettingsRepository::new(conn);
+        let settings = Settings {
+            idle_threshold_minutes: 10,
+            interval_minutes: 7,
+            ..Settings::default()
+        };
+
+        assert!(matches!(repo.save(&settings), Err(RepositoryError::InvalidData(_))));
+        assert_eq!(repo.load().unwrap(), Settings::default());
+    }
+
     #[test]
     fn test_get_nonexistent_key() {
         let db = setup_db();
@@ -147,11 +484,39 @@ mod tests {
         let conn = db.conn.lock().unwrap();
         let repo = SettingsRepository::new(conn);
         let all = repo.get_all().unwrap();
-        assert_eq!(all.len(), 4);
+        assert_eq!(all.len(), 7);
         let keys: Vec<&str> = all.iter().map(|s| s.key.as_str()).collect();
         assert!(keys.contains(&"interval_minutes"));
         assert!(keys.contains(&"idle_threshold_minutes"));
         assert!(keys.contains(&"notification_enabled"));
         assert!(keys.contains(&"notification_sound"));
+        assert!(keys.contains(&"idle_backend"));
+        assert!(keys.contains(&"backup_count"));
+        assert!(keys.contains(&"trash_retention_days"));
+    }
+
+    #[test]
+    fn test_parse_idle_backend() {
+        assert_eq!(
+            parse_setting("idle_backend", "Logind").unwrap(),
+            SettingChange::IdleBackend(IdleBackend::Logind)
+        );
+        match parse_setting("idle_backend", "wayland") {
+            Err(RepositoryError::InvalidData(msg)) => assert_eq!(
+                msg,
+                "idle_backend = 'wayland' must be one of auto, macos, x11, screensaver, logind, none"
+            ),
+            other => panic!("expected InvalidData, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_parse_backup_count() {
+        assert_eq!(
+            parse_setting("backup_count", "14").unwrap(),
+            SettingChange::BackupCount(14)
+        );
+        assert!(parse_setting("backup_count", "0").is_err());
+        assert!(parse_setting("backup_count", "101").is_err());
     }
 }
@@ -0,0 +1,326 @@
+use crate::db::models::{ChangeSource, Tag, TagTotal};
+use rusqlite::{Connection, OptionalExtension};
+use std::sync::MutexGuard;
+
+use super::history;
+use super::journal::EntrySnapshot;
+use super::{RepositoryError, Result};
+
+pub struct TagRepository<'a> {
+    conn: MutexGuard<'a, Connection>,
+    now: i64,
+}
+
+impl<'a> TagRepository<'a> {
+    pub fn new(conn: MutexGuard<'a, Connection>) -> Self {
+        Self {
+            conn,
+            now: chrono::Utc::now().timestamp(),
+        }
+    }
+
+    /// Stamps the changes made through this repository with `now` rather
+    /// than the time it was created, for callers that keep their own clock
+    pub fn at(mut self, now: i64) -> Self {
+        self.now = now;
+        self
+    }
+
+    pub fn find_all(&self) -> Result<Vec<Tag>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT id, name, created_at FROM tags ORDER BY name ASC")?;
+
+        let tags = stmt
+            .query_map([], |row| {
+                Ok(Tag {
+                    id: row.get(0)?,
+                    name: row.get(1)?,
+                    created_at: row.get(2)?,
+                })
+            })?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+
+        Ok(tags)
+    }
+
+    pub fn find_for_entry(&self, entry_id: i64) -> Result<Vec<String>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT t.name
+             FROM entry_tags et
+             JOIN tags t ON t.id = et.tag_id
+             WHERE et.entry_id = ?1
+             ORDER BY t.name ASC",
+        )?;
+
+        let tags = stmt
+            .query_map([entry_id], |row| row.get(0))?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+
+        Ok(tags)
+    }
+
+    pub fn add_to_entry(&self, entry_id: i64, tag: &str) -> Result<()> {
+        let name = normalize_tag(tag).ok_or_else(|| {
+            RepositoryError::InvalidData(format!("'{}' is not a valid tag", tag))
+        })?;
+        let exists: Option<i64> = self
+            .conn
+            .query_row(
+                "SELECT id FROM time_entries WHERE id = ?1 AND deleted_at IS NULL",
+                [entry_id],
+                |row| row.get(0),
+            )
+            .optional()?;
+        if exists.is_none() {
+            return Err(RepositoryError::NotFound(format!("Time entry {}", entry_id)));
+        }
+        let tx = self.conn.unchecked_transaction()?;
+        let before = EntrySnapshot::load(&tx, entry_id)?;
+        attach_tag(&tx, entry_id, &name)?;
+        history::record(&tx, entry_id, ChangeSource::Edit, before.as_ref(), self.now)?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    pub fn remove_from_entry(&self, entry_id: i64, tag: &str) -> Result<()> {
+        if let Some(name) = normalize_tag(tag) {
+            let tx = self.conn.unchecked_transaction()?;
+            let before = EntrySnapshot::load(&tx, entry_id)?;
+            detach_tag(&tx, entry_id, &name)?;
+            history::record(&tx, entry_id, ChangeSource::Edit, before.as_ref(), self.now)?;
+            tx.commit()?;
+        }
+        Ok(())
+    }
+
+    /// Minutes logged per tag in the range, largest first
+    pub fn total_minutes_per_tag(&self, start: i64, end: i64) -> Result<Vec<TagTotal>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT t.name, SUM(te.duration_minutes) AS total
+             FROM entry_tags et
+             JOIN tags t ON t.id = et.tag_id
+             JOIN time_entries te ON te.id = et.entry_id
+             WHERE te.timestamp >= ?1 AND te.timestamp < ?2 AND te.deleted_at IS NULL
+             GROUP BY t.id
+             ORDER BY total DESC, t.name ASC",
+        )?;
+
+        let totals = stmt
+            .query_map([start, end], |row| {
+                Ok(TagTotal {
+                    tag: row.get(0)?,
+                    total_minutes: row.get(1)?,
+                })
+            })?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+
+        Ok(totals)
+    }
+}
+
+/// Lowercases a tag and strips a leading `#`. Returns `None` if nothing
+/// usable is left.
+pub fn normalize_tag(tag: &str) -> Option<String> {
+    let name = tag.trim().trim_start_matches('#').to_lowercase();
+    if name.is_empty() || !name.chars().all(is_tag_char) {
+        return None;
+    }
+    Some(name)
+}
+
+/// Finds `#hashtags` in free text, normalized and de-duplicated in order of
+/// first appearance. A `#` only starts a tag at the beginning of the text or
+/// after a non-word character, so "C#" or "issue#12" are left alone.
+pub fn extract_hashtags(text: &str) -> Vec<String> {
+    let mut tags: Vec<String> = Vec::new();
+    let mut prev: Option<char> = None;
+    let mut chars = text.char_indices().peekable();
+
+    while let Some((i, c)) = chars.next() {
+        let starts_tag = c == '#' && prev.is_none_or(|p| !is_tag_char(p) && p != '#');
+        prev = Some(c);
+        if !starts_tag {
+            continue;
+        }
+
+        let start = i + c.len_utf8();
+        let mut end = start;
+        while let Some(&(j, next)) = chars.peek() {
+            if !is_tag_char(next) {
+                break;
+            }
+            end = j + next.len_utf8();
+            prev = Some(next);
+            chars.next();
+        }
+
+        // Trailing separators belong to the sentence, not the tag ("#oncall-")
+        let raw = text[start..end].trim_end_matches(['-', '/']);
+        if let Some(name) = normalize_tag(raw) {
+            if !tags.contains(&name) {
+                tags.push(name);
+            }
+        }
+    }
+
+    tags
+}
+
+fn is_tag_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '-' || c == '/'
+}
+
+/// Links an already-normalized tag to an entry, creating the tag if needed
+pub(crate) fn attach_tag(conn: &Connection, entry_id: i64, name: &str) -> Result<()> {
+    conn.execute("INSERT OR IGNORE INTO tags (name) VALUES (?1)", [name])?;
+    conn.execute(
+        "INSERT OR IGNORE INTO entry_tags (entry_id, tag_id)
+         SELECT ?1, id FROM tags WHERE name = ?2",
+        (entry_id, name),
+    )?;
+    Ok(())
+}
+
+pub(crate) fn detach_tag(conn: &Connection, entry_id: i64, name: &str) -> Result<()> {
+    conn.execute(
+        "DELETE FROM entry_tags
+         WHERE entry_id = ?1 AND tag_id = (SELECT id FROM tags WHERE name = ?2)",
+        (entry_id, name),
+    )?;
+    Ok(())
+}
+
+/// Brings an entry's note-derived tags in line with an edit of its notes:
+/// hashtags that disappeared from the notes are detached, new ones attached.
+/// Tags added by hand and never mentioned in the notes are left untouched.
+pub(crate) fn sync_hashtags(
+    conn: &Connection,
+    entry_id: i64,
+    old_notes: Option<&str>,
+    new_notes: Option<&str>,
+) -> Result<()> {
+    let old_tags = old_notes.map(extract_hashtags).unwrap_or_default();
+    let new_tags = new_notes.map(extract_hashtags).unwrap_or_default();
+
+    for name in old_tags.iter().filter(|t| !new_tags.contains(t)) {
+        detach_tag(conn, entry_id, name)?;
+    }
+    for name in &new_tags {
+        attach_tag(conn, entry_id, name)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{connection::Database, migrations};
+
+    fn setup_db() -> Database {
+        let db = Database::new_in_memory().unwrap();
+        {
+            let conn = db.conn.lock().unwrap();
+            migrations::run_migrations(&conn).unwrap();
+            conn.execute_batch(
+                "INSERT INTO time_entries (id, timestamp, category, duration_minutes) VALUES (1, 900, 'deep_work', 15);
+                 INSERT INTO time_entries (id, timestamp, category, duration_minutes) VALUES (2, 1800, 'meetings', 30);",
+            )
+            .unwrap();
+        }
+        db
+    }
+
+    #[test]
+    fn test_normalize_tag() {
+        assert_eq!(normalize_tag("#OnCall"), Some("oncall".to_string()));
+        assert_eq!(normalize_tag("  review "), Some("review".to_string()));
+        assert_eq!(normalize_tag("team/infra"), Some("team/infra".to_string()));
+        assert_eq!(normalize_tag("#"), None);
+        assert_eq!(normalize_tag("two words"), None);
+    }
+
+    #[test]
+    fn test_extract_hashtags() {
+        assert_eq!(
+            extract_hashtags("Paged again #oncall, then #Review of #oncall fixes"),
+            vec!["oncall", "review"]
+        );
+        assert_eq!(extract_hashtags("(#billing-migration)."), vec!["billing-migration"]);
+        assert_eq!(extract_hashtags("#a-"), vec!["a"]);
+    }
+
+    #[test]
+    fn test_extract_hashtags_ignores_embedded_hashes() {
+        assert!(extract_hashtags("Wrote some C# and fixed issue#12").is_empty());
+        assert!(extract_hashtags("## heading").is_empty());
+        assert!(extract_hashtags("no tags here").is_empty());
+    }
+
+    #[test]
+    fn test_add_and_find_for_entry() {
+        let db = setup_db();
+        let conn = db.conn.lock().unwrap();
+        let repo = TagRepository::new(conn);
+        repo.add_to_entry(1, "#Review").unwrap();
+        repo.add_to_entry(1, "oncall").unwrap();
+        repo.add_to_entry(1, "review").unwrap();
+
+        assert_eq!(repo.find_for_entry(1).unwrap(), vec!["oncall", "review"]);
+        assert_eq!(repo.find_all().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_add_rejects_invalid_tag_and_missing_entry() {
+        let db = setup_db();
+        let conn = db.conn.lock().unwrap();
+        let repo = TagRepository::new(conn);
+        assert!(matches!(repo.add_to_entry(1, "not valid"), Err(RepositoryError::InvalidData(_))));
+        assert!(matches!(repo.add_to_entry(99, "review"), Err(RepositoryError::NotFound(_))));
+    }
+
+    #[test]
+    fn test_remove_from_entry() {
+        let db = setup_db();
+        let conn = db.conn.lock().unwrap();
+        let repo = TagRepository::new(conn);
+        repo.add_to_entry(1, "review").unwrap();
+        repo.add_to_entry(2, "review").unwrap();
+
+        repo.remove_from_entry(1, "#review").unwrap();
+
+        assert!(repo.find_for_entry(1).unwrap().is_empty());
+        assert_eq!(repo.find_for_entry(2).unwrap(), vec!["review"]);
+    }
+
+    #[test]
+    fn test_sync_hashtags_keeps_manual_tags() {
+        let db = setup_db();
+        let conn = db.conn.lock().unwrap();
+        attach_tag(&conn, 1, "manual").unwrap();
+        sync_hashtags(&conn, 1, None, Some("#oncall #review")).unwrap();
+        sync_hashtags(&conn, 1, Some("#oncall #review"), Some("#review #infra")).unwrap();
+
+        let repo = TagRepository::new(conn);
+        assert_eq!(repo.find_for_entry(1).unwrap(), vec!["infra", "manual", "review"]);
+    }
+
+    #[test]
+    fn test_total_minutes_per_tag() {
+        let db = setup_db();
+        let conn = db.conn.lock().unwrap();
+        let repo = TagRepository::new(conn);
+        repo.add_to_entry(1, "review").unwrap();
+        repo.add_to_entry(2, "review").unwrap();
+        repo.add_to_entry(2, "oncall").unwrap();
+
+        let totals = repo.total_minutes_per_tag(0, 5000).unwrap();
+        assert_eq!(totals.len(), 2);
+        assert_eq!(totals[0].tag, "review");
+        assert_eq!(totals[0].total_minutes, 45);
+        assert_eq!(totals[1].tag, "oncall");
+        assert_eq!(totals[1].total_minutes, 30);
+
+        assert!(repo.total_minutes_per_tag(1000, 1500).unwrap().is_empty());
+    }
+}
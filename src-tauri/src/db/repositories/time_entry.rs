@@ -1,18 +1,59 @@
-use crate::db::models::TimeEntry;
-use rusqlite::Connection;
+use crate::db::models::{
+    AwayReason, ChangeAction, ChangeSource, ClientTotal, EntryChange, EntryFilter,
+    EntryHistoryItem, EntryOverlap, EntrySearchHit, ExportEntry, ExportRow, OverlapPolicy, ProjectTotal,
+    SnippetPart, TagMatch, TimeEntry, PAUSED_CATEGORY,
+};
+use rusqlite::types::Value;
+use rusqlite::{params_from_iter, Connection, OptionalExtension, Row};
 use std::sync::MutexGuard;
 
-use super::{int_to_bool, bool_to_int, Result};
+use super::history;
+use super::journal::{self, ChangeRecorder, EntrySnapshot};
+use super::tag::{normalize_tag, sync_hashtags};
+use super::{int_to_bool, bool_to_int, RepositoryError, Result};
+
+/// Characters marking where a search match starts and ends in a snippet,
+/// `char(1)` and `char(2)` in SQL; control characters don't turn up in notes
+const MATCH_START: char = '\u{1}';
+const MATCH_END: char = '\u{2}';
+
+const ENTRY_COLUMNS: &str = "te.id, te.timestamp, te.category, te.duration_minutes, te.is_away,
+     te.is_retroactive, te.notes, te.project_id, te.created_at, te.away_reason, te.deleted_at";
+
+/// Away entries the timer recorded and nobody has annotated or deleted since;
+/// only these are merged into neighbouring away time
+pub(crate) const MERGEABLE_AWAY: &str = "category = 'away' AND is_away = 1 AND notes IS NULL
+     AND project_id IS NULL AND deleted_at IS NULL";
+
+/// Paused entries the timer recorded and nobody has touched since; the
+/// paused counterpart of `MERGEABLE_AWAY`
+const MERGEABLE_PAUSED: &str = "category = 'paused' AND is_away = 0 AND notes IS NULL
+     AND project_id IS NULL AND deleted_at IS NULL";
 
 pub struct TimeEntryRepository<'a> {
     conn: MutexGuard<'a, Connection>,
+    now: i64,
 }
 
 impl<'a> TimeEntryRepository<'a> {
     pub fn new(conn: MutexGuard<'a, Connection>) -> Self {
-        Self { conn }
+        Self {
+            conn,
+            now: chrono::Utc::now().timestamp(),
+        }
+    }
+
+    /// Stamps the changes made through this repository with `now` rather
+    /// than the time it was created, for callers that keep their own clock
+    pub fn at(mut self, now: i64) -> Self {
+        self.now = now;
+        self
     }
 
+    /// Creates an entry. Existing entries sharing any of its time are handled
+    /// according to `on_overlap`. Undoing the change removes the entry and
+    /// puts back whatever it replaced or trimmed.
+    #[allow(clippy::too_many_arguments)]
     pub fn create(
         &self,
         timestamp: i64,
@@ -21,10 +62,50 @@ impl<'a> TimeEntryRepository<'a> {
         is_away: bool,
         is_retroactive: bool,
         notes: Option<&str>,
+        project_id: Option<i64>,
+        on_overlap: OverlapPolicy,
     ) -> Result<i64> {
-        self.conn.execute(
-            "INSERT INTO time_entries (timestamp, category, duration_minutes, is_away, is_retroactive, notes)
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        self.ensure_assignable_category(category)?;
+        if let Some(project_id) = project_id {
+            self.ensure_assignable_project(project_id)?;
+        }
+        let end = timestamp + duration_minutes as i64 * 60;
+        let tx = self.conn.unchecked_transaction()?;
+        let source = if is_retroactive {
+            ChangeSource::Retroactive
+        } else {
+            ChangeSource::Prompt
+        };
+        let mut change = ChangeRecorder::new(ChangeAction::Create, source, self.now);
+        let overlapping = overlapping_entries(&tx, timestamp, end)?;
+        match (on_overlap, overlapping.first()) {
+            (_, None) => {}
+            (OverlapPolicy::Reject, Some(existing)) => {
+                return Err(RepositoryError::InvalidData(format!(
+                    "Overlaps entry {} at {}",
+                    existing.id.unwrap_or_default(),
+                    existing.timestamp
+                )));
+            }
+            (OverlapPolicy::Replace, Some(_)) => {
+                for existing in &overlapping {
+                    let id = existing.id.unwrap_or_default();
+                    change.touch(&tx, id)?;
+                    soft_delete(&tx, id, self.now)?;
+                }
+            }
+            (OverlapPolicy::Split, Some(_)) => {
+                for existing in &overlapping {
+                    change.touch(&tx, existing.id.unwrap_or_default())?;
+                    if let Some(tail) = cut_out(&tx, existing, timestamp, end, self.now)? {
+                        change.created(tail);
+                    }
+                }
+            }
+        }
+        tx.execute(
+            "INSERT INTO time_entries (timestamp, category, duration_minutes, is_away, is_retroactive, notes, project_id, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
             (
                 timestamp,
                 category,
@@ -32,94 +113,873 @@ impl<'a> TimeEntryRepository<'a> {
                 bool_to_int(is_away),
                 bool_to_int(is_retroactive),
                 notes,
+                project_id,
+                self.now,
             ),
         )?;
-        Ok(self.conn.last_insert_rowid())
+        let id = tx.last_insert_rowid();
+        sync_hashtags(&tx, id, None, notes)?;
+        change.created(id);
+        change.commit(&tx)?;
+        tx.commit()?;
+        Ok(id)
     }
 
-    pub fn create_away_entry(&self, timestamp: i64, duration_minutes: i32) -> Result<i64> {
-        self.conn.execute(
-            "INSERT INTO time_entries (timestamp, category, duration_minutes, is_away) VALUES (?1, 'away', ?2, 1)",
-            (timestamp, duration_minutes),
-        )?;
-        Ok(self.conn.last_insert_rowid())
+    /// Records an away interval. When an untouched away entry with the same
+    /// reason ends exactly where this interval starts, that entry grows to
+    /// cover it instead, so a long absence stays a single block. Time that
+    /// another entry already covers is rejected either way.
+    pub fn create_away_entry(
+        &self,
+        timestamp: i64,
+        duration_minutes: i32,
+        reason: AwayReason,
+    ) -> Result<i64> {
+        ensure_free(&self.conn, timestamp, timestamp + duration_minutes as i64 * 60)?;
+        let previous: Option<i64> = self
+            .conn
+            .query_row(
+                &format!(
+                    "SELECT id FROM time_entries
+                     WHERE timestamp < ?1 AND timestamp + duration_minutes * 60 = ?1
+                       AND away_reason = ?2 AND {}",
+                    MERGEABLE_AWAY
+                ),
+                (timestamp, reason.as_str()),
+                |row| row.get(0),
+            )
+            .optional()?;
+        let tx = self.conn.unchecked_transaction()?;
+        let id = if let Some(id) = previous {
+            let before = EntrySnapshot::load(&tx, id)?;
+            tx.execute(
+                "UPDATE time_entries SET duration_minutes = duration_minutes + ?1 WHERE id = ?2",
+                (duration_minutes, id),
+            )?;
+            history::record(&tx, id, ChangeSource::Timer, before.as_ref(), self.now)?;
+            id
+        } else {
+            tx.execute(
+                "INSERT INTO time_entries (timestamp, category, duration_minutes, is_away, away_reason, created_at)
+                 VALUES (?1, 'away', ?2, 1, ?3, ?4)",
+                (timestamp, duration_minutes, reason.as_str(), self.now),
+            )?;
+            let id = tx.last_insert_rowid();
+            history::record(&tx, id, ChangeSource::Timer, None, self.now)?;
+            id
+        };
+        tx.commit()?;
+        Ok(id)
+    }
+
+    /// Records an interval the timer spent paused or snoozed. Like away time,
+    /// it extends an untouched paused entry ending exactly where it starts,
+    /// so a long pause stays a single block, and is rejected where another
+    /// entry already covers the time.
+    pub fn create_paused_entry(&self, timestamp: i64, duration_minutes: i32) -> Result<i64> {
+        ensure_free(&self.conn, timestamp, timestamp + duration_minutes as i64 * 60)?;
+        let previous: Option<i64> = self
+            .conn
+            .query_row(
+                &format!(
+                    "SELECT id FROM time_entries
+                     WHERE timestamp < ?1 AND timestamp + duration_minutes * 60 = ?1 AND {}",
+                    MERGEABLE_PAUSED
+                ),
+                [timestamp],
+                |row| row.get(0),
+            )
+            .optional()?;
+        let tx = self.conn.unchecked_transaction()?;
+        let id = if let Some(id) = previous {
+            let before = EntrySnapshot::load(&tx, id)?;
+            tx.execute(
+                "UPDATE time_entries SET duration_minutes = duration_minutes + ?1 WHERE id = ?2",
+                (duration_minutes, id),
+            )?;
+            history::record(&tx, id, ChangeSource::Timer, before.as_ref(), self.now)?;
+            id
+        } else {
+            tx.execute(
+                "INSERT INTO time_entries (timestamp, category, duration_minutes, created_at)
+                 VALUES (?1, ?2, ?3, ?4)",
+                (timestamp, PAUSED_CATEGORY, duration_minutes, self.now),
+            )?;
+            let id = tx.last_insert_rowid();
+            history::record(&tx, id, ChangeSource::Timer, None, self.now)?;
+            id
+        };
+        tx.commit()?;
+        Ok(id)
     }
 
     pub fn find_by_date_range(&self, start: i64, end: i64) -> Result<Vec<TimeEntry>> {
-        let mut stmt = self.conn.prepare(
-            "SELECT id, timestamp, category, duration_minutes, is_away, is_retroactive, notes, created_at
-             FROM time_entries
-             WHERE timestamp >= ?1 AND timestamp < ?2
-             ORDER BY timestamp ASC",
-        )?;
+        self.find_by_date_range_filtered(start, end, &EntryFilter::default())
+    }
+
+    pub fn find_by_date_range_filtered(
+        &self,
+        start: i64,
+        end: i64,
+        filter: &EntryFilter,
+    ) -> Result<Vec<TimeEntry>> {
+        let mut entries = Vec::new();
+        self.each_by_date_range(start, end, filter, |entry| -> Result<()> {
+            entries.push(entry);
+            Ok(())
+        })?;
+        Ok(entries)
+    }
+
+    /// Pairs of entries touching `[start, end)` that share time with each
+    /// other, for cleaning up double-booked intervals
+    pub fn find_overlaps(&self, start: i64, end: i64) -> Result<Vec<EntryOverlap>> {
+        let entries = overlapping_entries(&self.conn, start, end)?;
+        let mut overlaps = Vec::new();
+        for (i, first) in entries.iter().enumerate() {
+            let first_end = entry_end(first);
+            for second in &entries[i + 1..] {
+                if second.timestamp >= first_end {
+                    break;
+                }
+                overlaps.push(EntryOverlap {
+                    start: second.timestamp,
+                    end: first_end.min(entry_end(second)),
+                    first: first.clone(),
+                    second: second.clone(),
+                });
+            }
+        }
+        Ok(overlaps)
+    }
+
+    /// Streams entries in the range to `f` in timestamp order without
+    /// collecting them, stopping at the first error `f` returns
+    pub fn each_by_date_range<E, F>(
+        &self,
+        start: i64,
+        end: i64,
+        filter: &EntryFilter,
+        mut f: F,
+    ) -> std::result::Result<(), E>
+    where
+        E: From<RepositoryError>,
+        F: FnMut(TimeEntry) -> std::result::Result<(), E>,
+    {
+        let mut stmt = self
+            .conn
+            .prepare(&format!(
+                "SELECT {}
+                 FROM time_entries te
+                 LEFT JOIN projects p ON p.id = te.project_id
+                 WHERE te.timestamp >= ?1 AND te.timestamp < ?2 AND te.deleted_at IS NULL
+                   AND (?3 IS NULL OR te.project_id = ?3)
+                   AND (?4 IS NULL OR p.client_id = ?4)
+                 ORDER BY te.timestamp ASC",
+                ENTRY_COLUMNS
+            ))
+            .map_err(RepositoryError::from)?;
 
+        let rows = stmt
+            .query_map((start, end, filter.project_id, filter.client_id), map_entry)
+            .map_err(RepositoryError::from)?;
+        for entry in rows {
+            f(entry.map_err(RepositoryError::from)?)?;
+        }
+        Ok(())
+    }
+
+    /// Up to `limit` of the entries `each_by_date_range` yields, with project
+    /// and client names, continuing after the entry at `after` (its timestamp
+    /// and id). Lets a long export read one page at a time and leave the
+    /// connection free in between.
+    pub fn export_page(
+        &self,
+        start: i64,
+        end: i64,
+        filter: &EntryFilter,
+        after: Option<(i64, i64)>,
+        limit: usize,
+    ) -> Result<Vec<ExportEntry>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {}, p.name, c.name
+             FROM time_entries te
+             LEFT JOIN projects p ON p.id = te.project_id
+             LEFT JOIN clients c ON c.id = p.client_id
+             WHERE te.timestamp >= ?1 AND te.timestamp < ?2 AND te.deleted_at IS NULL
+               AND (?3 IS NULL OR te.project_id = ?3)
+               AND (?4 IS NULL OR p.client_id = ?4)
+               AND (?5 IS NULL OR (te.timestamp, te.id) > (?5, ?6))
+             ORDER BY te.timestamp ASC, te.id ASC
+             LIMIT ?7",
+            ENTRY_COLUMNS
+        ))?;
+
+        let (after_timestamp, after_id) = after.unzip();
         let entries = stmt
-            .query_map([start, end], |row| {
-                Ok(TimeEntry {
-                    id: row.get(0)?,
-                    timestamp: row.get(1)?,
-                    category: row.get(2)?,
-                    duration_minutes: row.get(3)?,
-                    is_away: int_to_bool(row.get(4)?),
-                    is_retroactive: int_to_bool(row.get(5)?),
-                    notes: row.get(6)?,
-                    created_at: row.get(7)?,
+            .query_map(
+                (
+                    start,
+                    end,
+                    filter.project_id,
+                    filter.client_id,
+                    after_timestamp,
+                    after_id,
+                    limit as i64,
+                ),
+                |row| {
+                    Ok(ExportEntry {
+                        entry: map_entry(row)?,
+                        project: row.get(11)?,
+                        client: row.get(12)?,
+                    })
+                },
+            )?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+
+        Ok(entries)
+    }
+
+    /// Entries in the range carrying any or all of `tags`
+    pub fn find_by_tags(
+        &self,
+        tags: &[String],
+        mode: TagMatch,
+        start: i64,
+        end: i64,
+    ) -> Result<Vec<TimeEntry>> {
+        let (subquery, params) = tagged_entries_subquery(tags, mode, start, end)?;
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {}
+             FROM time_entries te
+             WHERE te.id IN ({})
+             ORDER BY te.timestamp ASC",
+            ENTRY_COLUMNS, subquery
+        ))?;
+
+        let entries = stmt
+            .query_map(params_from_iter(params), map_entry)?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+
+        Ok(entries)
+    }
+
+    /// Entries whose notes match `query`, best match first, optionally limited
+    /// to `[start, end)` and one category. See `match_query` for what the
+    /// query may contain; a query without any words finds nothing.
+    pub fn search(
+        &self,
+        query: &str,
+        start: Option<i64>,
+        end: Option<i64>,
+        category: Option<&str>,
+        limit: u32,
+    ) -> Result<Vec<EntrySearchHit>> {
+        let Some(query) = match_query(query) else {
+            return Ok(Vec::new());
+        };
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {}, time_entries_fts.rank,
+                    snippet(time_entries_fts, 0, char(1), char(2), '…', 16)
+             FROM time_entries_fts
+             JOIN time_entries te ON te.id = time_entries_fts.rowid
+             WHERE time_entries_fts MATCH ?1
+               AND te.deleted_at IS NULL
+               AND (?2 IS NULL OR te.timestamp >= ?2)
+               AND (?3 IS NULL OR te.timestamp < ?3)
+               AND (?4 IS NULL OR te.category = ?4)
+             ORDER BY time_entries_fts.rank ASC, te.timestamp DESC
+             LIMIT ?5",
+            ENTRY_COLUMNS
+        ))?;
+
+        let hits = stmt
+            .query_map((query, start, end, category, limit), |row| {
+                Ok(EntrySearchHit {
+                    entry: map_entry(row)?,
+                    rank: row.get(11)?,
+                    snippet: snippet_parts(&row.get::<_, String>(12)?),
                 })
             })?
             .collect::<std::result::Result<Vec<_>, _>>()?;
 
-        Ok(entries)
+        Ok(hits)
+    }
+
+    /// Total minutes of entries in the range carrying any or all of `tags`.
+    /// Entries matching several tags are only counted once.
+    pub fn total_minutes_by_tags(
+        &self,
+        tags: &[String],
+        mode: TagMatch,
+        start: i64,
+        end: i64,
+    ) -> Result<i64> {
+        let (subquery, params) = tagged_entries_subquery(tags, mode, start, end)?;
+        let total = self.conn.query_row(
+            &format!(
+                "SELECT COALESCE(SUM(te.duration_minutes), 0)
+                 FROM time_entries te
+                 WHERE te.id IN ({})",
+                subquery
+            ),
+            params_from_iter(params),
+            |row| row.get(0),
+        )?;
+        Ok(total)
+    }
+
+    /// Number of entries `each_by_date_range` would yield for the same arguments
+    pub fn count_by_date_range(&self, start: i64, end: i64, filter: &EntryFilter) -> Result<usize> {
+        let count: i64 = self.conn.query_row(
+            "SELECT COUNT(*)
+             FROM time_entries te
+             LEFT JOIN projects p ON p.id = te.project_id
+             WHERE te.timestamp >= ?1 AND te.timestamp < ?2 AND te.deleted_at IS NULL
+               AND (?3 IS NULL OR te.project_id = ?3)
+               AND (?4 IS NULL OR p.client_id = ?4)",
+            (start, end, filter.project_id, filter.client_id),
+            |row| row.get(0),
+        )?;
+        Ok(count as usize)
     }
 
     pub fn find_raw_by_date_range(
         &self,
         start: i64,
         end: i64,
-    ) -> Result<Vec<(i64, String, i32, bool, bool, Option<String>)>> {
+        filter: &EntryFilter,
+    ) -> Result<Vec<ExportRow>> {
+        let mut rows = Vec::new();
+        self.each_raw_by_date_range(start, end, filter, |row| -> Result<()> {
+            rows.push(row);
+            Ok(())
+        })?;
+        Ok(rows)
+    }
+
+    /// Streaming counterpart of `find_raw_by_date_range`
+    pub fn each_raw_by_date_range<E, F>(
+        &self,
+        start: i64,
+        end: i64,
+        filter: &EntryFilter,
+        mut f: F,
+    ) -> std::result::Result<(), E>
+    where
+        E: From<RepositoryError>,
+        F: FnMut(ExportRow) -> std::result::Result<(), E>,
+    {
+        let mut stmt = self
+            .conn
+            .prepare(
+                "SELECT te.timestamp, te.category, te.duration_minutes, te.is_away, te.is_retroactive,
+                        te.notes, p.name, c.name
+                 FROM time_entries te
+                 LEFT JOIN projects p ON p.id = te.project_id
+                 LEFT JOIN clients c ON c.id = p.client_id
+                 WHERE te.timestamp >= ?1 AND te.timestamp < ?2 AND te.deleted_at IS NULL
+                   AND (?3 IS NULL OR te.project_id = ?3)
+                   AND (?4 IS NULL OR p.client_id = ?4)
+                 ORDER BY te.timestamp ASC",
+            )
+            .map_err(RepositoryError::from)?;
+
+        let rows = stmt
+            .query_map((start, end, filter.project_id, filter.client_id), |row| {
+                Ok(ExportRow {
+                    timestamp: row.get(0)?,
+                    category: row.get(1)?,
+                    duration_minutes: row.get(2)?,
+                    is_away: int_to_bool(row.get(3)?),
+                    is_retroactive: int_to_bool(row.get(4)?),
+                    notes: row.get(5)?,
+                    project: row.get(6)?,
+                    client: row.get(7)?,
+                })
+            })
+            .map_err(RepositoryError::from)?;
+        for row in rows {
+            f(row.map_err(RepositoryError::from)?)?;
+        }
+        Ok(())
+    }
+
+    /// Minutes per project in the range, largest first. Entries without a
+    /// project are reported under a `None` project.
+    pub fn total_minutes_by_project(&self, start: i64, end: i64) -> Result<Vec<ProjectTotal>> {
         let mut stmt = self.conn.prepare(
-            "SELECT timestamp, category, duration_minutes, is_away, is_retroactive, notes
-             FROM time_entries
-             WHERE timestamp >= ?1 AND timestamp < ?2
-             ORDER BY timestamp ASC",
+            "SELECT p.id, p.name, c.id, c.name, SUM(te.duration_minutes) AS total
+             FROM time_entries te
+             LEFT JOIN projects p ON p.id = te.project_id
+             LEFT JOIN clients c ON c.id = p.client_id
+             WHERE te.timestamp >= ?1 AND te.timestamp < ?2 AND te.deleted_at IS NULL
+             GROUP BY p.id
+             ORDER BY total DESC",
         )?;
 
-        let entries = stmt
+        let totals = stmt
             .query_map([start, end], |row| {
-                Ok((
-                    row.get(0)?,
-                    row.get(1)?,
-                    row.get(2)?,
-                    int_to_bool(row.get(3)?),
-                    int_to_bool(row.get(4)?),
-                    row.get(5)?,
-                ))
+                Ok(ProjectTotal {
+                    project_id: row.get(0)?,
+                    project_name: row.get(1)?,
+                    client_id: row.get(2)?,
+                    client_name: row.get(3)?,
+                    total_minutes: row.get(4)?,
+                })
             })?
             .collect::<std::result::Result<Vec<_>, _>>()?;
 
-        Ok(entries)
+        Ok(totals)
     }
 
-    pub fn update_category(&self, id: i64, category: &str) -> Result<()> {
-        self.conn.execute(
-            "UPDATE time_entries SET category = ?1 WHERE id = ?2",
-            (category, id),
+    /// Minutes per client in the range, largest first. Entries without a
+    /// project, or whose project has no client, are reported under `None`.
+    pub fn total_minutes_by_client(&self, start: i64, end: i64) -> Result<Vec<ClientTotal>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT c.id, c.name, SUM(te.duration_minutes) AS total
+             FROM time_entries te
+             LEFT JOIN projects p ON p.id = te.project_id
+             LEFT JOIN clients c ON c.id = p.client_id
+             WHERE te.timestamp >= ?1 AND te.timestamp < ?2 AND te.deleted_at IS NULL
+             GROUP BY c.id
+             ORDER BY total DESC",
         )?;
-        Ok(())
+
+        let totals = stmt
+            .query_map([start, end], |row| {
+                Ok(ClientTotal {
+                    client_id: row.get(0)?,
+                    client_name: row.get(1)?,
+                    total_minutes: row.get(2)?,
+                })
+            })?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+
+        Ok(totals)
+    }
+
+    pub fn update_category(&self, id: i64, category: &str) -> Result<()> {
+        self.update(id, Some(category), None)
     }
 
     pub fn update_notes(&self, id: i64, notes: &str) -> Result<()> {
-        self.conn.execute(
-            "UPDATE time_entries SET notes = ?1 WHERE id = ?2",
-            (notes, id),
+        self.update(id, None, Some(notes))
+    }
+
+    /// Changes the category and/or notes of an entry as a single undoable change
+    pub fn update(&self, id: i64, category: Option<&str>, notes: Option<&str>) -> Result<()> {
+        if let Some(category) = category {
+            self.ensure_assignable_category(category)?;
+        }
+        let tx = self.conn.unchecked_transaction()?;
+        let mut change = ChangeRecorder::new(ChangeAction::Update, ChangeSource::Edit, self.now);
+        change.touch(&tx, id)?;
+        ensure_live(&tx, id)?;
+        if let Some(category) = category {
+            tx.execute(
+                "UPDATE time_entries SET category = ?1 WHERE id = ?2",
+                (category, id),
+            )?;
+        }
+        if let Some(notes) = notes {
+            let old_notes: Option<String> =
+                tx.query_row("SELECT notes FROM time_entries WHERE id = ?1", [id], |row| row.get(0))?;
+            tx.execute(
+                "UPDATE time_entries SET notes = ?1 WHERE id = ?2",
+                (notes, id),
+            )?;
+            sync_hashtags(&tx, id, old_notes.as_deref(), Some(notes))?;
+        }
+        change.commit(&tx)?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Assigns the entry to a project, or clears the assignment with `None`
+    pub fn update_project(&self, id: i64, project_id: Option<i64>) -> Result<()> {
+        if let Some(project_id) = project_id {
+            self.ensure_assignable_project(project_id)?;
+        }
+        let tx = self.conn.unchecked_transaction()?;
+        let mut change = ChangeRecorder::new(ChangeAction::Update, ChangeSource::Edit, self.now);
+        change.touch(&tx, id)?;
+        ensure_live(&tx, id)?;
+        tx.execute(
+            "UPDATE time_entries SET project_id = ?1 WHERE id = ?2",
+            (project_id, id),
         )?;
+        change.commit(&tx)?;
+        tx.commit()?;
         Ok(())
     }
 
+    /// Moves an entry to the trash. It keeps its tags and can be restored
+    /// until it is purged.
     pub fn delete(&self, id: i64) -> Result<()> {
-        self.conn
-            .execute("DELETE FROM time_entries WHERE id = ?1", [id])?;
+        let tx = self.conn.unchecked_transaction()?;
+        let mut change = ChangeRecorder::new(ChangeAction::Delete, ChangeSource::Edit, self.now);
+        change.touch(&tx, id)?;
+        ensure_live(&tx, id)?;
+        soft_delete(&tx, id, self.now)?;
+        change.commit(&tx)?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Takes an entry back out of the trash, unless its time has been filled
+    /// by another entry in the meantime
+    pub fn restore(&self, id: i64) -> Result<()> {
+        let tx = self.conn.unchecked_transaction()?;
+        let mut change = ChangeRecorder::new(ChangeAction::Restore, ChangeSource::Edit, self.now);
+        change.touch(&tx, id)?;
+        let entry = tx
+            .query_row(
+                &format!(
+                    "SELECT {} FROM time_entries te WHERE te.id = ?1 AND te.deleted_at IS NOT NULL",
+                    ENTRY_COLUMNS
+                ),
+                [id],
+                map_entry,
+            )
+            .optional()?
+            .ok_or_else(|| RepositoryError::NotFound(format!("Deleted time entry {}", id)))?;
+        ensure_free(&tx, entry.timestamp, entry_end(&entry))?;
+        tx.execute("UPDATE time_entries SET deleted_at = NULL WHERE id = ?1", [id])?;
+        change.commit(&tx)?;
+        tx.commit()?;
         Ok(())
     }
+
+    /// Entries in the trash, most recently deleted first
+    pub fn find_deleted(&self) -> Result<Vec<TimeEntry>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {}
+             FROM time_entries te
+             WHERE te.deleted_at IS NOT NULL
+             ORDER BY te.deleted_at DESC, te.id DESC",
+            ENTRY_COLUMNS
+        ))?;
+        let entries = stmt
+            .query_map([], map_entry)?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        Ok(entries)
+    }
+
+    /// Every recorded change to the entry, oldest first, including while it
+    /// is in the trash
+    pub fn find_history(&self, id: i64) -> Result<Vec<EntryHistoryItem>> {
+        history::for_entry(&self.conn, id)
+    }
+
+    /// Reverts the most recent create, update, delete or restore that has not
+    /// been undone yet. Returns `None` when there is nothing to undo.
+    pub fn undo_last_change(&self) -> Result<Option<EntryChange>> {
+        journal::undo_last(&self.conn, self.now)
+    }
+
+    /// Permanently removes entries deleted before `before` and forgets
+    /// journaled changes from before then. Their history is kept, with the
+    /// purge as its last rows. Returns how many entries went.
+    pub fn purge_deleted(&self, before: i64) -> Result<usize> {
+        let tx = self.conn.unchecked_transaction()?;
+        let ids: Vec<i64> = tx
+            .prepare("SELECT id FROM time_entries WHERE deleted_at < ?1")?
+            .query_map([before], |row| row.get(0))?
+            .collect::<std::result::Result<_, _>>()?;
+        for id in &ids {
+            let snapshot = EntrySnapshot::load(&tx, *id)?;
+            tx.execute("DELETE FROM entry_tags WHERE entry_id = ?1", [id])?;
+            tx.execute("DELETE FROM time_entries WHERE id = ?1", [id])?;
+            history::record(&tx, *id, ChangeSource::Purge, snapshot.as_ref(), self.now)?;
+        }
+        journal::forget_before(&tx, before)?;
+        tx.commit()?;
+        Ok(ids.len())
+    }
+
+    /// Entries may only reference categories that exist and are not archived
+    fn ensure_assignable_category(&self, slug: &str) -> Result<()> {
+        let archived: Option<i32> = self
+            .conn
+            .query_row(
+                "SELECT archived FROM categories WHERE slug = ?1",
+                [slug],
+                |row| row.get(0),
+            )
+            .optional()?;
+        match archived {
+            None => Err(RepositoryError::InvalidData(format!(
+                "Unknown category '{}'",
+                slug
+            ))),
+            Some(archived) if int_to_bool(archived) => Err(RepositoryError::InvalidData(
+                format!("Category '{}' is archived", slug),
+            )),
+            Some(_) => Ok(()),
+        }
+    }
+
+    /// Entries may only reference projects that exist and are not archived
+    fn ensure_assignable_project(&self, project_id: i64) -> Result<()> {
+        let archived: Option<i32> = self
+            .conn
+            .query_row(
+                "SELECT archived FROM projects WHERE id = ?1",
+                [project_id],
+                |row| row.get(0),
+            )
+            .optional()?;
+        match archived {
+            None => Err(RepositoryError::InvalidData(format!(
+                "Unknown project {}",
+                project_id
+            ))),
+            Some(archived) if int_to_bool(archived) => Err(RepositoryError::InvalidData(
+                format!("Project {} is archived", project_id),
+            )),
+            Some(_) => Ok(()),
+        }
+    }
+}
+
+/// Changes may only be made to entries that are not in the trash
+fn ensure_live(conn: &Connection, id: i64) -> Result<()> {
+    let deleted_at: Option<i64> =
+        conn.query_row("SELECT deleted_at FROM time_entries WHERE id = ?1", [id], |row| row.get(0))?;
+    match deleted_at {
+        Some(_) => Err(RepositoryError::InvalidData(format!("Time entry {} is deleted", id))),
+        None => Ok(()),
+    }
+}
+
+/// No live entry may share any time with `[start, end)`
+fn ensure_free(conn: &Connection, start: i64, end: i64) -> Result<()> {
+    match overlapping_entries(conn, start, end)?.first() {
+        Some(existing) => Err(RepositoryError::InvalidData(format!(
+            "Overlaps entry {} at {}",
+            existing.id.unwrap_or_default(),
+            existing.timestamp
+        ))),
+        None => Ok(()),
+    }
+}
+
+pub(crate) fn soft_delete(conn: &Connection, id: i64, now: i64) -> Result<()> {
+    conn.execute(
+        "UPDATE time_entries SET deleted_at = ?1 WHERE id = ?2",
+        (now, id),
+    )?;
+    Ok(())
+}
+
+fn entry_end(entry: &TimeEntry) -> i64 {
+    entry.timestamp + entry.duration_minutes as i64 * 60
+}
+
+/// Entries sharing any time with `[start, end)`, in timestamp order
+pub(crate) fn overlapping_entries(
+    conn: &Connection,
+    start: i64,
+    end: i64,
+) -> Result<Vec<TimeEntry>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {}
+         FROM time_entries te
+         WHERE te.timestamp < ?2 AND te.timestamp + te.duration_minutes * 60 > ?1
+           AND te.deleted_at IS NULL
+         ORDER BY te.timestamp ASC, te.id ASC",
+        ENTRY_COLUMNS
+    ))?;
+    let entries = stmt
+        .query_map((start, end), map_entry)?
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+    Ok(entries)
+}
+
+/// Shrinks `entry` so it no longer covers `[start, end)`. A part left over
+/// on each side is kept; an entry straddling the range becomes two entries,
+/// and the id of the new tail entry is returned. Both parts are rounded down
+/// to whole minutes, so neither reaches back into the range; a part left
+/// with no whole minute is dropped, and an entry left with no time at all
+/// goes to the trash. Changes are stamped with `now`.
+fn cut_out(
+    conn: &Connection,
+    entry: &TimeEntry,
+    start: i64,
+    end: i64,
+    now: i64,
+) -> Result<Option<i64>> {
+    let id = entry.id.unwrap_or_default();
+    let before_minutes = whole_minutes(start - entry.timestamp);
+    let after_minutes = whole_minutes(entry_end(entry) - end);
+
+    if before_minutes > 0 {
+        conn.execute(
+            "UPDATE time_entries SET duration_minutes = ?1 WHERE id = ?2",
+            (before_minutes, id),
+        )?;
+    } else if after_minutes > 0 {
+        // Only the tail survives: move the entry rather than copy it
+        conn.execute(
+            "UPDATE time_entries SET timestamp = ?1, duration_minutes = ?2 WHERE id = ?3",
+            (end, after_minutes, id),
+        )?;
+        return Ok(None);
+    } else {
+        soft_delete(conn, id, now)?;
+        return Ok(None);
+    }
+
+    if after_minutes > 0 {
+        conn.execute(
+            "INSERT INTO time_entries
+                 (timestamp, category, duration_minutes, is_away, is_retroactive, notes, project_id, away_reason, created_at)
+             SELECT ?1, category, ?2, is_away, is_retroactive, notes, project_id, away_reason, ?4
+             FROM time_entries WHERE id = ?3",
+            (end, after_minutes, id, now),
+        )?;
+        let tail = conn.last_insert_rowid();
+        sync_hashtags(conn, tail, None, entry.notes.as_deref())?;
+        return Ok(Some(tail));
+    }
+    Ok(None)
+}
+
+/// Whole minutes in `seconds`, rounded down; a negative span stays negative
+fn whole_minutes(seconds: i64) -> i32 {
+    seconds.div_euclid(60) as i32
+}
+
+fn map_entry(row: &Row) -> rusqlite::Result<TimeEntry> {
+    Ok(TimeEntry {
+        id: row.get(0)?,
+        timestamp: row.get(1)?,
+        category: row.get(2)?,
+        duration_minutes: row.get(3)?,
+        is_away: int_to_bool(row.get(4)?),
+        is_retroactive: int_to_bool(row.get(5)?),
+        notes: row.get(6)?,
+        project_id: row.get(7)?,
+        created_at: row.get(8)?,
+        away_reason: row
+            .get::<_, Option<String>>(9)?
+            .and_then(|reason| AwayReason::from_name(&reason)),
+        deleted_at: row.get(10)?,
+    })
+}
+
+/// Turns a search as typed into an FTS5 query. Words match anywhere in the
+/// notes, "quoted words" only next to each other, and a trailing `*` makes
+/// the word or phrase before it match as a prefix. Everything else is taken
+/// literally, so no input is a syntax error. Returns `None` when there is no
+/// word to search for.
+fn match_query(input: &str) -> Option<String> {
+    let mut terms = Vec::new();
+    let mut chars = input.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c.is_whitespace() {
+            continue;
+        }
+        let mut text = String::new();
+        if c == '"' {
+            text.extend(chars.by_ref().take_while(|&c| c != '"'));
+        } else {
+            text.push(c);
+            while let Some(&c) = chars.peek() {
+                if c.is_whitespace() || c == '"' {
+                    break;
+                }
+                text.push(c);
+                chars.next();
+            }
+        }
+        let mut prefix = false;
+        while chars.peek() == Some(&'*') {
+            prefix = true;
+            chars.next();
+        }
+        let trimmed = text.trim_end_matches('*');
+        prefix |= trimmed.len() < text.len();
+
+        if trimmed.chars().any(char::is_alphanumeric) {
+            terms.push(format!("\"{}\"{}", trimmed, if prefix { "*" } else { "" }));
+        }
+    }
+    (!terms.is_empty()).then(|| terms.join(" "))
+}
+
+/// Splits a snippet produced with `MATCH_START`/`MATCH_END` around matches
+/// into plain and highlighted parts
+fn snippet_parts(snippet: &str) -> Vec<SnippetPart> {
+    let mut parts = Vec::new();
+    let mut push = |text: &str, highlighted: bool| {
+        if !text.is_empty() {
+            parts.push(SnippetPart {
+                text: text.to_string(),
+                highlighted,
+            });
+        }
+    };
+    let mut pieces = snippet.split(MATCH_START);
+    push(pieces.next().unwrap_or_default(), false);
+    for piece in pieces {
+        match piece.split_once(MATCH_END) {
+            Some((matched, rest)) => {
+                push(matched, true);
+                push(rest, false);
+            }
+            None => push(piece, true),
+        }
+    }
+    parts
+}
+
+/// Builds a subquery selecting ids of entries in `[start, end)` tagged with
+/// any or all of `tags`, along with its positional parameters
+fn tagged_entries_subquery(
+    tags: &[String],
+    mode: TagMatch,
+    start: i64,
+    end: i64,
+) -> Result<(String, Vec<Value>)> {
+    let mut names: Vec<String> = Vec::new();
+    for tag in tags {
+        let name = normalize_tag(tag).ok_or_else(|| {
+            RepositoryError::InvalidData(format!("'{}' is not a valid tag", tag))
+        })?;
+        if !names.contains(&name) {
+            names.push(name);
+        }
+    }
+    if names.is_empty() {
+        return Err(RepositoryError::InvalidData(
+            "At least one tag is required".to_string(),
+        ));
+    }
+
+    let placeholders = (0..names.len())
+        .map(|i| format!("?{}", i + 3))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let having = match mode {
+        TagMatch::Any => String::new(),
+        TagMatch::All => format!("HAVING COUNT(DISTINCT t.id) = {}", names.len()),
+    };
+    let subquery = format!(
+        "SELECT et.entry_id
+         FROM entry_tags et
+         JOIN tags t ON t.id = et.tag_id
+         JOIN time_entries tagged ON tagged.id = et.entry_id
+         WHERE tagged.timestamp >= ?1 AND tagged.timestamp < ?2 AND tagged.deleted_at IS NULL
+           AND t.name IN ({})
+         GROUP BY et.entry_id {}",
+        placeholders, having
+    );
+
+    let mut params = vec![Value::Integer(start), Value::Integer(end)];
+    params.extend(names.into_iter().map(Value::Text));
+    Ok((subquery, params))
 }
 
 #[cfg(test)]
@@ -141,7 +1001,7 @@ mod tests {
         let db = setup_db();
         let conn = db.conn.lock().unwrap();
         let repo = TimeEntryRepository::new(conn);
-        let id = repo.create(1000, "deep_work", 15, false, false, Some("test")).unwrap();
+        let id = repo.create(1000, "deep_work", 15, false, false, Some("test"), None, OverlapPolicy::Reject).unwrap();
         assert!(id > 0);
     }
 
@@ -150,7 +1010,7 @@ mod tests {
         let db = setup_db();
         let conn = db.conn.lock().unwrap();
         let repo = TimeEntryRepository::new(conn);
-        repo.create(1000, "deep_work", 15, false, true, Some("coding")).unwrap();
+        repo.create(1000, "deep_work", 15, false, true, Some("coding"), None, OverlapPolicy::Reject).unwrap();
 
         let entries = repo.find_by_date_range(0, 2000).unwrap();
         assert_eq!(entries.len(), 1);
@@ -168,9 +1028,9 @@ mod tests {
         let db = setup_db();
         let conn = db.conn.lock().unwrap();
         let repo = TimeEntryRepository::new(conn);
-        repo.create(500, "admin", 15, false, false, None).unwrap();
-        repo.create(1500, "meetings", 15, false, false, None).unwrap();
-        repo.create(2500, "break", 15, false, false, None).unwrap();
+        repo.create(500, "admin", 15, false, false, None, None, OverlapPolicy::Reject).unwrap();
+        repo.create(1500, "meetings", 15, false, false, None, None, OverlapPolicy::Reject).unwrap();
+        repo.create(2500, "break", 15, false, false, None, None, OverlapPolicy::Reject).unwrap();
 
         let entries = repo.find_by_date_range(1000, 2000).unwrap();
         assert_eq!(entries.len(), 1);
@@ -191,13 +1051,255 @@ mod tests {
         let db = setup_db();
         let conn = db.conn.lock().unwrap();
         let repo = TimeEntryRepository::new(conn);
-        repo.create_away_entry(1000, 15).unwrap();
+        repo.create_away_entry(1000, 15, AwayReason::Locked).unwrap();
 
         let entries = repo.find_by_date_range(0, 2000).unwrap();
         assert_eq!(entries.len(), 1);
         assert_eq!(entries[0].category, "away");
         assert!(entries[0].is_away);
         assert_eq!(entries[0].duration_minutes, 15);
+        assert_eq!(entries[0].away_reason, Some(AwayReason::Locked));
+    }
+
+    #[test]
+    fn test_create_away_entry_extends_adjacent_block() {
+        let db = setup_db();
+        let conn = db.conn.lock().unwrap();
+        let repo = TimeEntryRepository::new(conn);
+        let first = repo.create_away_entry(900, 15, AwayReason::Idle).unwrap();
+        assert_eq!(repo.create_away_entry(1800, 15, AwayReason::Idle).unwrap(), first);
+        assert_eq!(repo.create_away_entry(2700, 15, AwayReason::Idle).unwrap(), first);
+
+        let entries = repo.find_by_date_range(0, 10000).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].timestamp, 900);
+        assert_eq!(entries[0].duration_minutes, 45);
+    }
+
+    #[test]
+    fn test_create_away_entry_starts_new_block() {
+        let db = setup_db();
+        let conn = db.conn.lock().unwrap();
+        let repo = TimeEntryRepository::new(conn);
+        repo.create_away_entry(900, 15, AwayReason::Idle).unwrap();
+        // A different reason, a gap, and an annotated block each start afresh
+        repo.create_away_entry(1800, 15, AwayReason::Locked).unwrap();
+        let annotated = repo.create_away_entry(3600, 15, AwayReason::Locked).unwrap();
+        repo.update_notes(annotated, "dentist").unwrap();
+        repo.create_away_entry(4500, 15, AwayReason::Locked).unwrap();
+
+        let durations: Vec<i32> = repo
+            .find_by_date_range(0, 10000)
+            .unwrap()
+            .iter()
+            .map(|entry| entry.duration_minutes)
+            .collect();
+        assert_eq!(durations, vec![15, 15, 15, 15]);
+    }
+
+    fn away_block(repo: &TimeEntryRepository) -> i64 {
+        // 900-3600, recorded one interval at a time
+        let id = repo.create_away_entry(900, 15, AwayReason::Idle).unwrap();
+        repo.create_away_entry(1800, 15, AwayReason::Idle).unwrap();
+        repo.create_away_entry(2700, 15, AwayReason::Idle).unwrap();
+        id
+    }
+
+    fn spans(repo: &TimeEntryRepository) -> Vec<(i64, i32, String)> {
+        repo.find_by_date_range(0, 10000)
+            .unwrap()
+            .into_iter()
+            .map(|e| (e.timestamp, e.duration_minutes, e.category))
+            .collect()
+    }
+
+    #[test]
+    fn test_create_rejects_overlap() {
+        let db = setup_db();
+        let conn = db.conn.lock().unwrap();
+        let repo = TimeEntryRepository::new(conn);
+        let id = repo.create(1000, "deep_work", 15, false, false, None, None, OverlapPolicy::Reject).unwrap();
+
+        match repo.create(1800, "admin", 15, false, false, None, None, OverlapPolicy::Reject) {
+            Err(RepositoryError::InvalidData(msg)) => {
+                assert_eq!(msg, format!("Overlaps entry {} at 1000", id))
+            }
+            other => panic!("expected InvalidData, got {:?}", other),
+        }
+        // Back to back is fine
+        repo.create(1900, "admin", 15, false, false, None, None, OverlapPolicy::Reject).unwrap();
+        assert_eq!(repo.find_by_date_range(0, 10000).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_create_replaces_overlapping_entries() {
+        let db = setup_db();
+        let conn = db.conn.lock().unwrap();
+        let repo = TimeEntryRepository::new(conn);
+        away_block(&repo);
+
+        repo.create(1800, "deep_work", 15, false, false, None, None, OverlapPolicy::Replace).unwrap();
+
+        assert_eq!(spans(&repo), vec![(1800, 15, "deep_work".to_string())]);
+    }
+
+    #[test]
+    fn test_create_splits_straddling_entry() {
+        let db = setup_db();
+        let conn = db.conn.lock().unwrap();
+        let repo = TimeEntryRepository::new(conn);
+        away_block(&repo);
+
+        repo.create(1800, "deep_work", 15, false, false, None, None, OverlapPolicy::Split).unwrap();
+
+        assert_eq!(
+            spans(&repo),
+            vec![
+                (900, 15, "away".to_string()),
+                (1800, 15, "deep_work".to_string()),
+                (2700, 15, "away".to_string()),
+            ]
+        );
+        let tail = &repo.find_by_date_range(2700, 2701).unwrap()[0];
+        assert!(tail.is_away);
+        assert_eq!(tail.away_reason, Some(AwayReason::Idle));
+    }
+
+    #[test]
+    fn test_create_split_keeps_entry_ids_where_possible() {
+        let db = setup_db();
+        let conn = db.conn.lock().unwrap();
+        let repo = TimeEntryRepository::new(conn);
+        let block = away_block(&repo);
+        let head = repo.create(0, "admin", 15, false, false, Some("#standup"), None, OverlapPolicy::Reject).unwrap();
+
+        // Covers the end of the admin entry and the start of the away block
+        repo.create(600, "meetings", 30, false, false, None, None, OverlapPolicy::Split).unwrap();
+
+        let entries = repo.find_by_date_range(0, 10000).unwrap();
+        let summary: Vec<(Option<i64>, i64, i32)> = entries
+            .iter()
+            .map(|e| (e.id, e.timestamp, e.duration_minutes))
+            .collect();
+        assert_eq!(summary[0], (Some(head), 0, 10));
+        assert_eq!(summary[2], (Some(block), 2400, 20));
+        assert_eq!(tags_for(&repo, head), vec!["standup"]);
+    }
+
+    #[test]
+    fn test_create_split_drops_parts_under_a_minute() {
+        let db = setup_db();
+        let conn = db.conn.lock().unwrap();
+        let repo = TimeEntryRepository::new(conn);
+        repo.create(1000, "admin", 30, false, false, None, None, OverlapPolicy::Reject).unwrap();
+
+        // 90s before and 13.5 minutes after: both sides round down
+        repo.create(1090, "deep_work", 15, false, false, None, None, OverlapPolicy::Split).unwrap();
+        assert_eq!(
+            spans(&repo),
+            vec![
+                (1000, 1, "admin".to_string()),
+                (1090, 15, "deep_work".to_string()),
+                (1990, 13, "admin".to_string()),
+            ]
+        );
+
+        // Leaves 30s of the one-minute head, which is not kept
+        repo.create(1030, "meetings", 1, false, false, None, None, OverlapPolicy::Split).unwrap();
+        assert_eq!(
+            spans(&repo),
+            vec![
+                (1030, 1, "meetings".to_string()),
+                (1090, 15, "deep_work".to_string()),
+                (1990, 13, "admin".to_string()),
+            ]
+        );
+        assert!(repo.find_overlaps(0, 10000).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_find_overlaps() {
+        let db = setup_db();
+        let conn = db.conn.lock().unwrap();
+        let repo = TimeEntryRepository::new(conn);
+        away_block(&repo);
+        repo.create(3600, "admin", 15, false, false, None, None, OverlapPolicy::Reject).unwrap();
+        // Written before overlaps were checked
+        repo.conn
+            .execute(
+                "INSERT INTO time_entries (timestamp, category, duration_minutes) VALUES (1800, 'deep_work', 15)",
+                [],
+            )
+            .unwrap();
+
+        let overlaps = repo.find_overlaps(0, 10000).unwrap();
+        assert_eq!(overlaps.len(), 1);
+        assert_eq!((overlaps[0].start, overlaps[0].end), (1800, 2700));
+        assert_eq!(overlaps[0].first.category, "away");
+        assert_eq!(overlaps[0].second.category, "deep_work");
+        assert!(repo.find_overlaps(3600, 10000).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_create_paused_entry() {
+        let db = setup_db();
+        let conn = db.conn.lock().unwrap();
+        let repo = TimeEntryRepository::new(conn);
+        repo.create_paused_entry(1000, 30).unwrap();
+
+        let entries = repo.find_by_date_range(0, 2000).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].category, PAUSED_CATEGORY);
+        assert!(!entries[0].is_away);
+        assert_eq!(entries[0].duration_minutes, 30);
+    }
+
+    #[test]
+    fn test_timer_entries_do_not_grow_into_other_entries() {
+        let db = setup_db();
+        let conn = db.conn.lock().unwrap();
+        let repo = TimeEntryRepository::new(conn);
+        repo.create_away_entry(900, 15, AwayReason::Idle).unwrap();
+        repo.create_paused_entry(3600, 15).unwrap();
+        repo.create(2100, "meetings", 15, false, false, None, None, OverlapPolicy::Reject).unwrap();
+        repo.create(4800, "admin", 15, false, false, None, None, OverlapPolicy::Reject).unwrap();
+
+        assert!(matches!(
+            repo.create_away_entry(1800, 15, AwayReason::Idle),
+            Err(RepositoryError::InvalidData(_))
+        ));
+        assert!(matches!(repo.create_paused_entry(4500, 15), Err(RepositoryError::InvalidData(_))));
+        assert_eq!(
+            spans(&repo),
+            vec![
+                (900, 15, "away".to_string()),
+                (2100, 15, "meetings".to_string()),
+                (3600, 15, PAUSED_CATEGORY.to_string()),
+                (4800, 15, "admin".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_create_paused_entry_extends_adjacent_block() {
+        let db = setup_db();
+        let conn = db.conn.lock().unwrap();
+        let repo = TimeEntryRepository::new(conn);
+        let first = repo.create_paused_entry(900, 15).unwrap();
+        assert_eq!(repo.create_paused_entry(1800, 15).unwrap(), first);
+        // A gap, or notes on the block, start a new one
+        let second = repo.create_paused_entry(3600, 15).unwrap();
+        assert_ne!(second, first);
+        repo.update_notes(second, "lunch").unwrap();
+        assert_ne!(repo.create_paused_entry(4500, 15).unwrap(), second);
+
+        let durations: Vec<i32> = repo
+            .find_by_date_range(0, 10000)
+            .unwrap()
+            .iter()
+            .map(|entry| entry.duration_minutes)
+            .collect();
+        assert_eq!(durations, vec![30, 15, 15]);
     }
 
     #[test]
@@ -205,17 +1307,55 @@ mod tests {
         let db = setup_db();
         let conn = db.conn.lock().unwrap();
         let repo = TimeEntryRepository::new(conn);
-        repo.create(1000, "deep_work", 15, false, true, Some("raw test")).unwrap();
+        repo.create(1000, "deep_work", 15, false, true, Some("raw test"), None, OverlapPolicy::Reject).unwrap();
 
-        let entries = repo.find_raw_by_date_range(0, 2000).unwrap();
+        let entries = repo.find_raw_by_date_range(0, 2000, &EntryFilter::default()).unwrap();
         assert_eq!(entries.len(), 1);
-        let (ts, cat, dur, away, retro, notes) = &entries[0];
-        assert_eq!(*ts, 1000);
-        assert_eq!(cat, "deep_work");
-        assert_eq!(*dur, 15);
-        assert!(!away);
-        assert!(retro);
-        assert_eq!(notes.as_deref(), Some("raw test"));
+        let row = &entries[0];
+        assert_eq!(row.timestamp, 1000);
+        assert_eq!(row.category, "deep_work");
+        assert_eq!(row.duration_minutes, 15);
+        assert!(!row.is_away);
+        assert!(row.is_retroactive);
+        assert_eq!(row.notes.as_deref(), Some("raw test"));
+        assert_eq!(row.project, None);
+        assert_eq!(row.client, None);
+    }
+
+    #[test]
+    fn test_count_by_date_range() {
+        let db = setup_db();
+        let conn = db.conn.lock().unwrap();
+        let repo = TimeEntryRepository::new(conn);
+        repo.create(1000, "deep_work", 15, false, false, None, None, OverlapPolicy::Reject).unwrap();
+        repo.create(1900, "meetings", 15, false, false, None, None, OverlapPolicy::Reject).unwrap();
+        repo.create(3000, "admin", 15, false, false, None, None, OverlapPolicy::Reject).unwrap();
+
+        assert_eq!(repo.count_by_date_range(0, 2000, &EntryFilter::default()).unwrap(), 2);
+        let filter = EntryFilter {
+            project_id: Some(1),
+            client_id: None,
+        };
+        assert_eq!(repo.count_by_date_range(0, 5000, &filter).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_export_page_continues_after_cursor() {
+        let db = setup_db();
+        let conn = db.conn.lock().unwrap();
+        let repo = TimeEntryRepository::new(conn);
+        repo.create(1000, "deep_work", 15, false, false, None, None, OverlapPolicy::Reject).unwrap();
+        repo.create(1900, "meetings", 15, false, false, None, None, OverlapPolicy::Reject).unwrap();
+        repo.create(3000, "admin", 15, false, false, None, None, OverlapPolicy::Reject).unwrap();
+        let filter = EntryFilter::default();
+
+        let first = repo.export_page(0, 5000, &filter, None, 2).unwrap();
+        assert_eq!(first.len(), 2);
+        let last = &first[1].entry;
+        let rest = repo.export_page(0, 5000, &filter, Some((last.timestamp, last.id.unwrap())), 2).unwrap();
+        assert_eq!(rest.len(), 1);
+        assert_eq!(rest[0].entry.category, "admin");
+        assert_eq!(rest[0].project, None);
     }
 
     #[test]
@@ -223,7 +1363,7 @@ mod tests {
         let db = setup_db();
         let conn = db.conn.lock().unwrap();
         let repo = TimeEntryRepository::new(conn);
-        let id = repo.create(1000, "deep_work", 15, false, false, None).unwrap();
+        let id = repo.create(1000, "deep_work", 15, false, false, None, None, OverlapPolicy::Reject).unwrap();
 
         repo.update_category(id, "meetings").unwrap();
 
@@ -236,7 +1376,7 @@ mod tests {
         let db = setup_db();
         let conn = db.conn.lock().unwrap();
         let repo = TimeEntryRepository::new(conn);
-        let id = repo.create(1000, "deep_work", 15, false, false, None).unwrap();
+        let id = repo.create(1000, "deep_work", 15, false, false, None, None, OverlapPolicy::Reject).unwrap();
 
         repo.update_notes(id, "updated notes").unwrap();
 
@@ -249,12 +1389,352 @@ mod tests {
         let db = setup_db();
         let conn = db.conn.lock().unwrap();
         let repo = TimeEntryRepository::new(conn);
-        let id = repo.create(1000, "deep_work", 15, false, false, None).unwrap();
+        let id = repo.create(1000, "deep_work", 15, false, false, None, None, OverlapPolicy::Reject).unwrap();
 
         repo.delete(id).unwrap();
 
         let entries = repo.find_by_date_range(0, 2000).unwrap();
         assert!(entries.is_empty());
+        let deleted = repo.find_deleted().unwrap();
+        assert_eq!(deleted.len(), 1);
+        assert!(deleted[0].deleted_at.is_some());
+        assert!(repo.delete(id).is_err(), "already in the trash");
+        assert!(repo.update_category(id, "meetings").is_err());
+    }
+
+    #[test]
+    fn test_changes_are_stamped_with_repository_time() {
+        let db = setup_db();
+        let conn = db.conn.lock().unwrap();
+        let repo = TimeEntryRepository::new(conn).at(5000);
+        let id = repo.create(1000, "deep_work", 15, false, false, None, None, OverlapPolicy::Reject).unwrap();
+        repo.delete(id).unwrap();
+        repo.undo_last_change().unwrap();
+
+        let entry = &repo.find_by_date_range(0, 2000).unwrap()[0];
+        assert_eq!(entry.created_at, Some(5000));
+        let history = repo.find_history(id).unwrap();
+        assert!(history.iter().all(|item| item.changed_at == 5000));
+        let stamps: Vec<(i64, Option<i64>)> = repo
+            .conn
+            .prepare("SELECT created_at, undone_at FROM entry_changes ORDER BY id")
+            .unwrap()
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap()
+            .collect::<std::result::Result<_, _>>()
+            .unwrap();
+        assert_eq!(stamps, vec![(5000, None), (5000, Some(5000))]);
+    }
+
+    #[test]
+    fn test_restore() {
+        let db = setup_db();
+        let conn = db.conn.lock().unwrap();
+        let repo = TimeEntryRepository::new(conn);
+        let id = repo.create(1000, "deep_work", 15, false, false, None, None, OverlapPolicy::Reject).unwrap();
+        repo.delete(id).unwrap();
+
+        repo.restore(id).unwrap();
+
+        let entries = repo.find_by_date_range(0, 2000).unwrap();
+        assert_eq!(entries[0].id, Some(id));
+        assert_eq!(entries[0].deleted_at, None);
+        assert!(matches!(repo.restore(id), Err(RepositoryError::NotFound(_))));
+    }
+
+    #[test]
+    fn test_restore_rejects_filled_time() {
+        let db = setup_db();
+        let conn = db.conn.lock().unwrap();
+        let repo = TimeEntryRepository::new(conn);
+        let id = repo.create(1000, "deep_work", 15, false, false, None, None, OverlapPolicy::Reject).unwrap();
+        repo.delete(id).unwrap();
+        repo.create(1300, "meetings", 15, false, false, None, None, OverlapPolicy::Reject).unwrap();
+
+        assert!(matches!(repo.restore(id), Err(RepositoryError::InvalidData(_))));
+        assert_eq!(repo.find_deleted().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_undo_walks_back_through_changes() {
+        let db = setup_db();
+        let conn = db.conn.lock().unwrap();
+        let repo = TimeEntryRepository::new(conn);
+        let id = repo
+            .create(1000, "deep_work", 15, false, false, Some("#oncall"), None, OverlapPolicy::Reject)
+            .unwrap();
+        repo.update(id, Some("meetings"), Some("#review")).unwrap();
+        repo.delete(id).unwrap();
+
+        let undone = repo.undo_last_change().unwrap().unwrap();
+        assert_eq!(undone.action, ChangeAction::Delete);
+        assert_eq!(undone.entry_ids, vec![id]);
+        let entries = repo.find_by_date_range(0, 2000).unwrap();
+        assert_eq!(entries[0].category, "meetings");
+
+        // Category and notes were edited together, so one undo reverts both
+        assert_eq!(repo.undo_last_change().unwrap().unwrap().action, ChangeAction::Update);
+        let entries = repo.find_by_date_range(0, 2000).unwrap();
+        assert_eq!(entries[0].category, "deep_work");
+        assert_eq!(entries[0].notes.as_deref(), Some("#oncall"));
+        assert_eq!(tags_for(&repo, id), vec!["oncall"]);
+
+        assert_eq!(repo.undo_last_change().unwrap().unwrap().action, ChangeAction::Create);
+        assert!(repo.find_by_date_range(0, 2000).unwrap().is_empty());
+        assert!(repo.find_deleted().unwrap().is_empty());
+        let history = repo.find_history(id).unwrap();
+        let last = history.last().unwrap();
+        assert_eq!(last.source, ChangeSource::Undo);
+        assert_eq!(last.new_value, None);
+        assert_eq!(repo.undo_last_change().unwrap(), None);
+    }
+
+    #[test]
+    fn test_undo_refuses_to_double_book() {
+        let db = setup_db();
+        let conn = db.conn.lock().unwrap();
+        let repo = TimeEntryRepository::new(conn);
+        let id = repo
+            .create(900, "deep_work", 15, false, false, None, None, OverlapPolicy::Reject)
+            .unwrap();
+        repo.delete(id).unwrap();
+        let away = repo.create_away_entry(900, 15, AwayReason::Idle).unwrap();
+
+        let err = repo.undo_last_change().unwrap_err();
+        assert!(matches!(err, RepositoryError::InvalidData(ref msg) if msg.contains(&away.to_string())));
+        assert_eq!(repo.find_deleted().unwrap().len(), 1);
+        assert_eq!(spans(&repo), vec![(900, 15, "away".to_string())]);
+
+        repo.delete(away).unwrap();
+        repo.undo_last_change().unwrap();
+        assert_eq!(repo.find_deleted().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_undo_refuses_entry_changed_outside_the_journal() {
+        let db = setup_db();
+        let conn = db.conn.lock().unwrap();
+        let repo = TimeEntryRepository::new(conn);
+        let id = repo
+            .create(1000, "deep_work", 15, false, false, None, None, OverlapPolicy::Reject)
+            .unwrap();
+        repo.update(id, Some("meetings"), None).unwrap();
+        // Tags added on their own are not journaled
+        crate::db::repositories::tag::attach_tag(&repo.conn, id, "billing").unwrap();
+
+        let err = repo.undo_last_change().unwrap_err();
+        assert!(matches!(err, RepositoryError::InvalidData(ref msg) if msg.contains("changed since")));
+        assert_eq!(spans(&repo), vec![(1000, 15, "meetings".to_string())]);
+        assert_eq!(tags_for(&repo, id), vec!["billing"]);
+    }
+
+    #[test]
+    fn test_undo_split_create_restores_trimmed_entry() {
+        let db = setup_db();
+        let conn = db.conn.lock().unwrap();
+        let repo = TimeEntryRepository::new(conn);
+        repo.create(0, "deep_work", 60, false, false, Some("#focus"), None, OverlapPolicy::Reject).unwrap();
+        repo.create(900, "meetings", 15, false, false, None, None, OverlapPolicy::Split).unwrap();
+        assert_eq!(spans(&repo).len(), 3);
+
+        repo.undo_last_change().unwrap();
+
+        assert_eq!(spans(&repo), vec![(0, 60, "deep_work".to_string())]);
+    }
+
+    #[test]
+    fn test_purge_deleted_respects_cutoff() {
+        let db = setup_db();
+        let conn = db.conn.lock().unwrap();
+        let repo = TimeEntryRepository::new(conn);
+        let old = repo.create(1000, "deep_work", 15, false, false, None, None, OverlapPolicy::Reject).unwrap();
+        let recent = repo.create(2000, "meetings", 15, false, false, None, None, OverlapPolicy::Reject).unwrap();
+        repo.delete(old).unwrap();
+        repo.delete(recent).unwrap();
+        repo.conn
+            .execute("UPDATE time_entries SET deleted_at = 100 WHERE id = ?1", [old])
+            .unwrap();
+
+        assert_eq!(repo.purge_deleted(200).unwrap(), 1);
+
+        let deleted = repo.find_deleted().unwrap();
+        assert_eq!(deleted.len(), 1);
+        assert_eq!(deleted[0].id, Some(recent));
+        let history = repo.find_history(old).unwrap();
+        let purged: Vec<&str> = history
+            .iter()
+            .filter(|item| item.source == ChangeSource::Purge)
+            .map(|item| item.field.as_str())
+            .collect();
+        assert!(purged.contains(&"timestamp") && purged.contains(&"deleted_at"));
+        assert!(history.iter().all(|item| item.source != ChangeSource::Purge || item.new_value.is_none()));
+        assert!(!repo.find_history(recent).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_match_query() {
+        assert_eq!(match_query("billing migration").as_deref(), Some("\"billing\" \"migration\""));
+        assert_eq!(match_query("\"billing migration\"").as_deref(), Some("\"billing migration\""));
+        assert_eq!(match_query("migra*").as_deref(), Some("\"migra\"*"));
+        assert_eq!(match_query("\"billing mig\"*").as_deref(), Some("\"billing mig\"*"));
+        assert_eq!(match_query("NOT (a OR b)").as_deref(), Some("\"NOT\" \"(a\" \"OR\" \"b)\""));
+        assert_eq!(match_query("\"unclosed phrase").as_deref(), Some("\"unclosed phrase\""));
+        assert_eq!(match_query("  * - \"\" "), None);
+    }
+
+    #[test]
+    fn test_snippet_parts() {
+        let parts = snippet_parts("fixed the \u{1}billing\u{2} job…");
+        assert_eq!(
+            parts,
+            vec![
+                SnippetPart { text: "fixed the ".to_string(), highlighted: false },
+                SnippetPart { text: "billing".to_string(), highlighted: true },
+                SnippetPart { text: " job…".to_string(), highlighted: false },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_search_matches_words_phrases_and_prefixes() {
+        let db = setup_db();
+        let conn = db.conn.lock().unwrap();
+        let repo = TimeEntryRepository::new(conn);
+        let migration = repo
+            .create(1000, "deep_work", 15, false, false, Some("Billing migration dry run"), None, OverlapPolicy::Reject)
+            .unwrap();
+        let review = repo
+            .create(2000, "meetings", 15, false, false, Some("Reviewed the migration plan for billing"), None, OverlapPolicy::Reject)
+            .unwrap();
+        repo.create(3000, "admin", 15, false, false, Some("Invoices"), None, OverlapPolicy::Reject).unwrap();
+
+        let ids = |query: &str| -> Vec<i64> {
+            let mut ids: Vec<i64> = repo
+                .search(query, None, None, None, 50)
+                .unwrap()
+                .into_iter()
+                .map(|hit| hit.entry.id.unwrap())
+                .collect();
+            ids.sort();
+            ids
+        };
+        assert_eq!(ids("billing migration"), vec![migration, review]);
+        assert_eq!(ids("\"billing migration\""), vec![migration]);
+        assert_eq!(ids("migr*"), vec![migration, review]);
+        assert_eq!(ids("invoice"), Vec::<i64>::new());
+        assert_eq!(ids("invoice*").len(), 1);
+
+        let hits = repo.search("billing", Some(1500), None, Some("meetings"), 50).unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].entry.id, Some(review));
+        assert!(hits[0]
+            .snippet
+            .iter()
+            .any(|part| part.highlighted && part.text == "billing"));
+    }
+
+    #[test]
+    fn test_search_follows_edits_and_deletes() {
+        let db = setup_db();
+        let conn = db.conn.lock().unwrap();
+        let repo = TimeEntryRepository::new(conn);
+        let id = repo
+            .create(1000, "deep_work", 15, false, false, Some("Billing migration"), None, OverlapPolicy::Reject)
+            .unwrap();
+
+        repo.update_notes(id, "Payroll export").unwrap();
+        assert!(repo.search("billing", None, None, None, 50).unwrap().is_empty());
+        assert_eq!(repo.search("payroll", None, None, None, 50).unwrap().len(), 1);
+
+        repo.delete(id).unwrap();
+        assert!(repo.search("payroll", None, None, None, 50).unwrap().is_empty());
+
+        repo.undo_last_change().unwrap();
+        repo.undo_last_change().unwrap();
+        assert_eq!(repo.search("billing", None, None, None, 50).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_history_records_each_changed_field() {
+        let db = setup_db();
+        let conn = db.conn.lock().unwrap();
+        let repo = TimeEntryRepository::new(conn);
+        let id = repo
+            .create(1000, "deep_work", 15, false, true, None, None, OverlapPolicy::Reject)
+            .unwrap();
+        repo.update(id, Some("meetings"), Some("#review")).unwrap();
+        repo.delete(id).unwrap();
+        repo.undo_last_change().unwrap();
+
+        let history = repo.find_history(id).unwrap();
+        let created: Vec<_> = history
+            .iter()
+            .filter(|item| item.source == ChangeSource::Retroactive)
+            .map(|item| (item.field.as_str(), item.old_value.as_deref(), item.new_value.as_deref()))
+            .collect();
+        assert_eq!(
+            created,
+            vec![
+                ("timestamp", None, Some("1000")),
+                ("category", None, Some("deep_work")),
+                ("duration_minutes", None, Some("15")),
+                ("is_away", None, Some("false")),
+                ("is_retroactive", None, Some("true")),
+            ]
+        );
+        let edited: Vec<_> = history
+            .iter()
+            .filter(|item| item.source == ChangeSource::Edit)
+            .map(|item| (item.field.as_str(), item.old_value.as_deref(), item.new_value.as_deref()))
+            .collect();
+        assert_eq!(
+            &edited[..3],
+            &[
+                ("category", Some("deep_work"), Some("meetings")),
+                ("notes", None, Some("#review")),
+                ("tags", None, Some("review")),
+            ]
+        );
+        assert_eq!(edited[3].0, "deleted_at");
+        assert!(edited[3].2.is_some());
+        let undone: Vec<_> = history
+            .iter()
+            .filter(|item| item.source == ChangeSource::Undo)
+            .map(|item| (item.field.as_str(), item.new_value.as_deref()))
+            .collect();
+        assert_eq!(undone, vec![("deleted_at", None)]);
+    }
+
+    #[test]
+    fn test_history_of_grown_away_entry() {
+        let db = setup_db();
+        let conn = db.conn.lock().unwrap();
+        let repo = TimeEntryRepository::new(conn);
+        let id = repo.create_away_entry(0, 15, AwayReason::Idle).unwrap();
+        repo.create_away_entry(900, 15, AwayReason::Idle).unwrap();
+
+        let history = repo.find_history(id).unwrap();
+        assert!(history.iter().all(|item| item.source == ChangeSource::Timer));
+        let grown = history.last().unwrap();
+        assert_eq!(grown.field, "duration_minutes");
+        assert_eq!(grown.old_value.as_deref(), Some("15"));
+        assert_eq!(grown.new_value.as_deref(), Some("30"));
+    }
+
+    #[test]
+    fn test_history_with_unknown_source_is_an_error() {
+        let db = setup_db();
+        let conn = db.conn.lock().unwrap();
+        let repo = TimeEntryRepository::new(conn);
+        let id = repo.create(1000, "deep_work", 15, false, false, None, None, OverlapPolicy::Reject).unwrap();
+        // The column's CHECK constraint normally keeps such values out
+        repo.conn.execute_batch("PRAGMA ignore_check_constraints = ON").unwrap();
+        repo.conn
+            .execute("UPDATE time_entry_history SET source = 'telepathy' WHERE entry_id = ?1", [id])
+            .unwrap();
+
+        let err = repo.find_history(id).unwrap_err();
+        assert!(err.to_string().contains("Unknown change source 'telepathy'"), "{}", err);
     }
 
     #[test]
@@ -262,9 +1742,9 @@ mod tests {
         let db = setup_db();
         let conn = db.conn.lock().unwrap();
         let repo = TimeEntryRepository::new(conn);
-        repo.create(3000, "break", 15, false, false, None).unwrap();
-        repo.create(1000, "deep_work", 15, false, false, None).unwrap();
-        repo.create(2000, "meetings", 15, false, false, None).unwrap();
+        repo.create(3000, "break", 15, false, false, None, None, OverlapPolicy::Reject).unwrap();
+        repo.create(1000, "deep_work", 15, false, false, None, None, OverlapPolicy::Reject).unwrap();
+        repo.create(2000, "meetings", 15, false, false, None, None, OverlapPolicy::Reject).unwrap();
 
         let entries = repo.find_by_date_range(0, 5000).unwrap();
         assert_eq!(entries.len(), 3);
@@ -272,4 +1752,275 @@ mod tests {
         assert_eq!(entries[1].timestamp, 2000);
         assert_eq!(entries[2].timestamp, 3000);
     }
+
+    #[test]
+    fn test_create_rejects_unknown_category() {
+        let db = setup_db();
+        let conn = db.conn.lock().unwrap();
+        let repo = TimeEntryRepository::new(conn);
+        let result = repo.create(1000, "gardening", 15, false, false, None, None, OverlapPolicy::Reject);
+        assert!(matches!(result, Err(RepositoryError::InvalidData(_))));
+        assert!(repo.find_by_date_range(0, 2000).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_create_rejects_archived_category() {
+        let db = setup_db();
+        let conn = db.conn.lock().unwrap();
+        conn.execute("UPDATE categories SET archived = 1 WHERE slug = 'admin'", [])
+            .unwrap();
+        let repo = TimeEntryRepository::new(conn);
+        let result = repo.create(1000, "admin", 15, false, false, None, None, OverlapPolicy::Reject);
+        assert!(matches!(result, Err(RepositoryError::InvalidData(_))));
+    }
+
+    #[test]
+    fn test_update_category_rejects_unknown_category() {
+        let db = setup_db();
+        let conn = db.conn.lock().unwrap();
+        let repo = TimeEntryRepository::new(conn);
+        let id = repo.create(1000, "deep_work", 15, false, false, None, None, OverlapPolicy::Reject).unwrap();
+
+        let result = repo.update_category(id, "gardening");
+
+        assert!(matches!(result, Err(RepositoryError::InvalidData(_))));
+        let entries = repo.find_by_date_range(0, 2000).unwrap();
+        assert_eq!(entries[0].category, "deep_work");
+    }
+
+    /// Seeds client "Acme" with projects "Website" (id 1) and "App" (id 2),
+    /// plus a client-less "Internal" project (id 3)
+    fn seed_projects(repo: &TimeEntryRepository) {
+        repo.conn
+            .execute_batch(
+                "INSERT INTO clients (id, name) VALUES (1, 'Acme');
+                 INSERT INTO projects (id, client_id, name) VALUES (1, 1, 'Website');
+                 INSERT INTO projects (id, client_id, name) VALUES (2, 1, 'App');
+                 INSERT INTO projects (id, client_id, name) VALUES (3, NULL, 'Internal');",
+            )
+            .unwrap();
+    }
+
+    #[test]
+    fn test_create_with_project() {
+        let db = setup_db();
+        let conn = db.conn.lock().unwrap();
+        let repo = TimeEntryRepository::new(conn);
+        seed_projects(&repo);
+        repo.create(1000, "deep_work", 15, false, false, None, Some(1), OverlapPolicy::Reject).unwrap();
+
+        let entries = repo.find_by_date_range(0, 2000).unwrap();
+        assert_eq!(entries[0].project_id, Some(1));
+    }
+
+    #[test]
+    fn test_create_rejects_unknown_or_archived_project() {
+        let db = setup_db();
+        let conn = db.conn.lock().unwrap();
+        let repo = TimeEntryRepository::new(conn);
+        seed_projects(&repo);
+        repo.conn
+            .execute("UPDATE projects SET archived = 1 WHERE id = 2", [])
+            .unwrap();
+
+        assert!(matches!(
+            repo.create(1000, "deep_work", 15, false, false, None, Some(99), OverlapPolicy::Reject),
+            Err(RepositoryError::InvalidData(_))
+        ));
+        assert!(matches!(
+            repo.create(1000, "deep_work", 15, false, false, None, Some(2), OverlapPolicy::Reject),
+            Err(RepositoryError::InvalidData(_))
+        ));
+    }
+
+    #[test]
+    fn test_update_project() {
+        let db = setup_db();
+        let conn = db.conn.lock().unwrap();
+        let repo = TimeEntryRepository::new(conn);
+        seed_projects(&repo);
+        let id = repo.create(1000, "deep_work", 15, false, false, None, None, OverlapPolicy::Reject).unwrap();
+
+        repo.update_project(id, Some(3)).unwrap();
+        assert_eq!(repo.find_by_date_range(0, 2000).unwrap()[0].project_id, Some(3));
+
+        repo.update_project(id, None).unwrap();
+        assert_eq!(repo.find_by_date_range(0, 2000).unwrap()[0].project_id, None);
+    }
+
+    #[test]
+    fn test_find_by_date_range_filtered() {
+        let db = setup_db();
+        let conn = db.conn.lock().unwrap();
+        let repo = TimeEntryRepository::new(conn);
+        seed_projects(&repo);
+        repo.create(1000, "deep_work", 15, false, false, None, Some(1), OverlapPolicy::Reject).unwrap();
+        repo.create(2000, "meetings", 15, false, false, None, Some(2), OverlapPolicy::Reject).unwrap();
+        repo.create(3000, "admin", 15, false, false, None, Some(3), OverlapPolicy::Reject).unwrap();
+        repo.create(4000, "admin", 15, false, false, None, None, OverlapPolicy::Reject).unwrap();
+
+        let by_project = EntryFilter { project_id: Some(2), ..Default::default() };
+        let entries = repo.find_by_date_range_filtered(0, 5000, &by_project).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].timestamp, 2000);
+
+        let by_client = EntryFilter { client_id: Some(1), ..Default::default() };
+        let entries = repo.find_by_date_range_filtered(0, 5000, &by_client).unwrap();
+        let timestamps: Vec<i64> = entries.iter().map(|e| e.timestamp).collect();
+        assert_eq!(timestamps, vec![1000, 2000]);
+
+        assert_eq!(repo.find_by_date_range(0, 5000).unwrap().len(), 4);
+    }
+
+    #[test]
+    fn test_find_raw_resolves_project_and_client() {
+        let db = setup_db();
+        let conn = db.conn.lock().unwrap();
+        let repo = TimeEntryRepository::new(conn);
+        seed_projects(&repo);
+        repo.create(1000, "deep_work", 15, false, false, None, Some(1), OverlapPolicy::Reject).unwrap();
+        repo.create(2000, "admin", 15, false, false, None, Some(3), OverlapPolicy::Reject).unwrap();
+
+        let rows = repo.find_raw_by_date_range(0, 5000, &EntryFilter::default()).unwrap();
+        assert_eq!(rows[0].project.as_deref(), Some("Website"));
+        assert_eq!(rows[0].client.as_deref(), Some("Acme"));
+        assert_eq!(rows[1].project.as_deref(), Some("Internal"));
+        assert_eq!(rows[1].client, None);
+    }
+
+    #[test]
+    fn test_totals_by_project_and_client() {
+        let db = setup_db();
+        let conn = db.conn.lock().unwrap();
+        let repo = TimeEntryRepository::new(conn);
+        seed_projects(&repo);
+        repo.create(1000, "deep_work", 30, false, false, None, Some(1), OverlapPolicy::Reject).unwrap();
+        repo.create(2800, "deep_work", 15, false, false, None, Some(1), OverlapPolicy::Reject).unwrap();
+        repo.create(3700, "meetings", 15, false, false, None, Some(2), OverlapPolicy::Reject).unwrap();
+        repo.create(4600, "admin", 15, false, false, None, Some(3), OverlapPolicy::Reject).unwrap();
+        repo.create(5500, "admin", 15, false, false, None, None, OverlapPolicy::Reject).unwrap();
+        repo.create(9000, "admin", 15, false, false, None, Some(1), OverlapPolicy::Reject).unwrap();
+
+        let projects = repo.total_minutes_by_project(0, 6000).unwrap();
+        assert_eq!(projects.len(), 4);
+        assert_eq!(projects[0].project_name.as_deref(), Some("Website"));
+        assert_eq!(projects[0].client_name.as_deref(), Some("Acme"));
+        assert_eq!(projects[0].total_minutes, 45);
+        let unassigned = projects.iter().find(|p| p.project_id.is_none()).unwrap();
+        assert_eq!(unassigned.total_minutes, 15);
+
+        let clients = repo.total_minutes_by_client(0, 6000).unwrap();
+        assert_eq!(clients.len(), 2);
+        assert_eq!(clients[0].client_id, Some(1));
+        assert_eq!(clients[0].total_minutes, 60);
+        assert_eq!(clients[1].client_id, None);
+        assert_eq!(clients[1].total_minutes, 30);
+    }
+
+    fn tags_for(repo: &TimeEntryRepository, id: i64) -> Vec<String> {
+        repo.conn
+            .prepare(
+                "SELECT t.name FROM entry_tags et JOIN tags t ON t.id = et.tag_id
+                 WHERE et.entry_id = ?1 ORDER BY t.name",
+            )
+            .unwrap()
+            .query_map([id], |row| row.get(0))
+            .unwrap()
+            .collect::<std::result::Result<Vec<String>, _>>()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_create_extracts_hashtags() {
+        let db = setup_db();
+        let conn = db.conn.lock().unwrap();
+        let repo = TimeEntryRepository::new(conn);
+        let id = repo
+            .create(1000, "deep_work", 15, false, false, Some("Fixed alerts #oncall #Infra"), None, OverlapPolicy::Reject)
+            .unwrap();
+        assert_eq!(tags_for(&repo, id), vec!["infra", "oncall"]);
+    }
+
+    #[test]
+    fn test_update_notes_resyncs_hashtags() {
+        let db = setup_db();
+        let conn = db.conn.lock().unwrap();
+        let repo = TimeEntryRepository::new(conn);
+        let id = repo
+            .create(1000, "deep_work", 15, false, false, Some("#oncall #review"), None, OverlapPolicy::Reject)
+            .unwrap();
+
+        repo.update_notes(id, "just #review now").unwrap();
+
+        assert_eq!(tags_for(&repo, id), vec!["review"]);
+    }
+
+    #[test]
+    fn test_delete_keeps_tag_links_until_purged() {
+        let db = setup_db();
+        let conn = db.conn.lock().unwrap();
+        let repo = TimeEntryRepository::new(conn);
+        let id = repo.create(1000, "deep_work", 15, false, false, Some("#oncall"), None, OverlapPolicy::Reject).unwrap();
+        let oncall = vec!["oncall".to_string()];
+
+        repo.delete(id).unwrap();
+        assert_eq!(tags_for(&repo, id), oncall);
+        assert!(repo.find_by_tags(&oncall, TagMatch::Any, 0, 5000).unwrap().is_empty());
+
+        repo.purge_deleted(i64::MAX).unwrap();
+        assert!(tags_for(&repo, id).is_empty());
+    }
+
+    #[test]
+    fn test_find_and_total_by_tags() {
+        let db = setup_db();
+        let conn = db.conn.lock().unwrap();
+        let repo = TimeEntryRepository::new(conn);
+        repo.create(1000, "deep_work", 15, false, false, Some("#oncall"), None, OverlapPolicy::Reject).unwrap();
+        repo.create(2000, "meetings", 30, false, false, Some("#oncall #review"), None, OverlapPolicy::Reject).unwrap();
+        repo.create(3800, "admin", 15, false, false, Some("#review"), None, OverlapPolicy::Reject).unwrap();
+        repo.create(9000, "admin", 15, false, false, Some("#oncall #review"), None, OverlapPolicy::Reject).unwrap();
+        let tags = vec!["oncall".to_string(), "#Review".to_string()];
+
+        let any = repo.find_by_tags(&tags, TagMatch::Any, 0, 5000).unwrap();
+        let timestamps: Vec<i64> = any.iter().map(|e| e.timestamp).collect();
+        assert_eq!(timestamps, vec![1000, 2000, 3800]);
+
+        let all = repo.find_by_tags(&tags, TagMatch::All, 0, 5000).unwrap();
+        assert_eq!(all.len(), 1);
+        assert_eq!(all[0].timestamp, 2000);
+
+        assert_eq!(repo.total_minutes_by_tags(&tags, TagMatch::Any, 0, 5000).unwrap(), 60);
+        assert_eq!(repo.total_minutes_by_tags(&tags, TagMatch::All, 0, 5000).unwrap(), 30);
+        assert_eq!(repo.total_minutes_by_tags(&tags, TagMatch::All, 0, 500).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_find_by_tags_requires_tags() {
+        let db = setup_db();
+        let conn = db.conn.lock().unwrap();
+        let repo = TimeEntryRepository::new(conn);
+        assert!(matches!(
+            repo.find_by_tags(&[], TagMatch::Any, 0, 5000),
+            Err(RepositoryError::InvalidData(_))
+        ));
+    }
+
+    #[test]
+    fn test_each_by_date_range_stops_on_error() {
+        let db = setup_db();
+        let conn = db.conn.lock().unwrap();
+        let repo = TimeEntryRepository::new(conn);
+        repo.create(1000, "deep_work", 15, false, false, None, None, OverlapPolicy::Reject).unwrap();
+        repo.create(2000, "meetings", 15, false, false, None, None, OverlapPolicy::Reject).unwrap();
+
+        let mut seen = Vec::new();
+        let result = repo.each_by_date_range(0, 5000, &EntryFilter::default(), |entry| {
+            seen.push(entry.timestamp);
+            Err::<(), String>("stop".to_string())
+        });
+
+        assert_eq!(result, Err("stop".to_string()));
+        assert_eq!(seen, vec![1000]);
+    }
 }
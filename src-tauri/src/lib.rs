@@ -11,18 +11,84 @@ mod commands;
 mod db;
 mod services;
 
-use db::{migrations, Database, SettingsRepository};
-use services::TimerCommand;
+use commands::DatabaseLocation;
+use db::backup::BackupStore;
+use db::connection::{self, DbPathSource, DB_PATH_ENV};
+use db::{Database, SettingsRepository};
+use services::{Clock, SettingsService, SystemClock, TimerCommand, TimerStateStore};
+use std::path::Path;
+use std::sync::Arc;
 use tauri::{
-    menu::{Menu, MenuItem},
+    menu::{Menu, MenuItem, PredefinedMenuItem},
     tray::{MouseButton, MouseButtonState, TrayIconBuilder, TrayIconEvent},
     Manager,
 };
 use tokio::sync::mpsc;
 
+/// Length of the snooze offered in the tray menu
+const TRAY_SNOOZE_MINUTES: u64 = 15;
+
+/// Maps a timer item in the tray menu, picked at `now`, to the command it sends
+fn tray_timer_command(id: &str, now: i64) -> Option<TimerCommand> {
+    match id {
+        "pause" => Some(TimerCommand::Pause { until: None }),
+        "pause_hour" => Some(TimerCommand::Pause {
+            until: Some(now + 3600),
+        }),
+        "snooze" => Some(TimerCommand::Snooze(TRAY_SNOOZE_MINUTES)),
+        "resume" => Some(TimerCommand::Resume),
+        _ => None,
+    }
+}
+
+/// Finds and opens the database. A database the user has just moved is
+/// copied to its new location first. With no override in place, a database
+/// left at the old macOS-only location by earlier builds is moved into the
+/// app data directory. Backups are kept in the app data directory wherever
+/// the database itself lives.
+fn open_database(
+    app: &tauri::App,
+) -> Result<(Database, DatabaseLocation, BackupStore), Box<dyn std::error::Error>> {
+    let data_dir = app.path().app_data_dir()?;
+    if let Some((from, to)) = connection::copy_pending_database(&data_dir)? {
+        log::info!("Copied database from {} to {}", from.display(), to.display());
+    }
+    let active = connection::resolve_db_location(std::env::var_os(DB_PATH_ENV), &data_dir);
+    if active.source == DbPathSource::Default {
+        if let Some(home) = std::env::var_os("HOME") {
+            let legacy = connection::legacy_db_path(Path::new(&home));
+            if connection::migrate_legacy_database(&legacy, &active.path)? {
+                log::info!(
+                    "Moved database from {} to {}",
+                    legacy.display(),
+                    active.path.display()
+                );
+            }
+        }
+    }
+    let backups = BackupStore::new(data_dir.join("backups"));
+    let database = Database::open(&active.path, &backups)?;
+    Ok((database, DatabaseLocation { data_dir, active }, backups))
+}
+
+/// Tells the user why the app is about to quit. A menu bar app started from
+/// Finder or a launcher has no terminal to print to, and setup runs on the
+/// main thread, so this uses a synchronous native dialog.
+fn show_startup_error(error: &dyn std::error::Error) {
+    rfd::MessageDialog::new()
+        .set_level(rfd::MessageLevel::Error)
+        .set_title("Time Tracker could not start")
+        .set_description(format!(
+            "{}\n\nTo use a different database file, set {} to its path.",
+            error, DB_PATH_ENV
+        ))
+        .set_buttons(rfd::MessageButtons::Ok)
+        .show();
+}
+
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
-    tauri::Builder::default()
+    let result = tauri::Builder::default()
         .plugin(tauri_plugin_opener::init())
         .plugin(tauri_plugin_notification::init())
         .plugin(tauri_plugin_dialog::init())
@@ -33,35 +99,69 @@ pub fn run() {
             app.set_activation_policy(tauri::ActivationPolicy::Accessory);
 
             // Initialize database
-            let database = Database::new().expect("Failed to initialize database");
-            {
-                let conn = database.conn.lock().unwrap();
-                migrations::run_migrations(&conn).expect("Failed to run migrations");
-            }
+            let (database, location, backups) = open_database(app).inspect_err(|e| {
+                log::error!("{}", e);
+                show_startup_error(&**e);
+            })?;
             app.manage(database);
+            app.manage(location);
+            app.manage(backups);
+
+            // Everything that schedules work or stamps changes reads this clock
+            let clock: Arc<dyn Clock> = Arc::new(SystemClock);
 
             // Create tray menu
             let open_item =
                 MenuItem::with_id(app, "open", "Open Time Tracker", true, None::<&str>)?;
+            let pause_item = MenuItem::with_id(app, "pause", "Pause", true, None::<&str>)?;
+            let pause_hour_item =
+                MenuItem::with_id(app, "pause_hour", "Pause for 1 Hour", true, None::<&str>)?;
+            let snooze_item = MenuItem::with_id(
+                app,
+                "snooze",
+                format!("Snooze {} Minutes", TRAY_SNOOZE_MINUTES),
+                true,
+                None::<&str>,
+            )?;
+            let resume_item = MenuItem::with_id(app, "resume", "Resume", true, None::<&str>)?;
             let quit_item = MenuItem::with_id(app, "quit", "Quit", true, None::<&str>)?;
-            let menu = Menu::with_items(app, &[&open_item, &quit_item])?;
+            let menu = Menu::with_items(
+                app,
+                &[
+                    &open_item,
+                    &PredefinedMenuItem::separator(app)?,
+                    &pause_item,
+                    &pause_hour_item,
+                    &snooze_item,
+                    &resume_item,
+                    &PredefinedMenuItem::separator(app)?,
+                    &quit_item,
+                ],
+            )?;
 
             // Build tray icon
             let _tray = TrayIconBuilder::new()
                 .icon(app.default_window_icon().unwrap().clone())
                 .menu(&menu)
                 .show_menu_on_left_click(false)
-                .on_menu_event(|app, event| match event.id.as_ref() {
-                    "open" => {
-                        if let Some(window) = app.get_webview_window("main") {
-                            let _ = window.show();
-                            let _ = window.set_focus();
+                .on_menu_event({
+                    let clock = clock.clone();
+                    move |app, event| match event.id.as_ref() {
+                        "open" => {
+                            if let Some(window) = app.get_webview_window("main") {
+                                let _ = window.show();
+                                let _ = window.set_focus();
+                            }
+                        }
+                        "quit" => {
+                            app.exit(0);
+                        }
+                        id => {
+                            if let Some(cmd) = tray_timer_command(id, clock.now()) {
+                                let _ = app.state::<mpsc::Sender<TimerCommand>>().try_send(cmd);
+                            }
                         }
                     }
-                    "quit" => {
-                        app.exit(0);
-                    }
-                    _ => {}
                 })
                 .on_tray_icon_event(|tray, event| {
                     if let TrayIconEvent::Click {
@@ -95,7 +195,8 @@ pub fn run() {
             // Start background timer
             let app_handle = app.handle().clone();
             let (tx, rx) = mpsc::channel::<TimerCommand>(10);
-            app.manage(tx);
+            app.manage(tx.clone());
+            app.manage(TimerStateStore::default());
 
             // Get interval from settings using repository
             let db = app.state::<Database>();
@@ -105,8 +206,32 @@ pub fn run() {
                 settings_repo.get_interval_minutes()
             };
 
+            // Settings writes reach the timer through the settings service
+            let settings = SettingsService::new();
+            let settings_changes = settings.subscribe();
+            app.manage(settings);
+            tauri::async_runtime::spawn(services::settings::forward_to_timer(
+                settings_changes,
+                tx,
+            ));
+
+            let timer_clock = clock.clone();
+            tauri::async_runtime::spawn(async move {
+                services::tauri_timer::start_timer(app_handle, timer_clock, interval, rx).await;
+            });
+
+            let backup_handle = app.handle().clone();
+            let backup_clock = clock.clone();
+            tauri::async_runtime::spawn(async move {
+                let db = backup_handle.state::<Database>();
+                let backups = backup_handle.state::<BackupStore>();
+                services::backup::run_daily(backup_clock.as_ref(), db.inner(), backups.inner()).await;
+            });
+
+            let trash_handle = app.handle().clone();
             tauri::async_runtime::spawn(async move {
-                services::timer::start_timer(app_handle, interval, rx).await;
+                let db = trash_handle.state::<Database>();
+                services::trash::run_daily(clock.as_ref(), db.inner()).await;
             });
 
             Ok(())
@@ -115,16 +240,67 @@ pub fn run() {
             commands::create_time_entry,
             commands::get_entries_for_date,
             commands::update_time_entry,
+            commands::set_time_entry_project,
             commands::delete_time_entry,
+            commands::restore_time_entry,
+            commands::get_deleted_entries,
+            commands::undo_last_change,
+            commands::get_entry_history,
+            commands::search_entries,
+            commands::find_overlapping_entries,
             commands::create_missed_prompt,
             commands::get_missed_prompts,
             commands::delete_missed_prompt,
             commands::get_setting,
             commands::set_setting,
             commands::get_all_settings,
+            commands::get_settings,
+            commands::update_settings,
             commands::test_notification,
+            commands::export_entries,
             commands::export_entries_to_csv,
+            commands::export_entries_to_file,
+            commands::import_entries,
+            commands::get_categories,
+            commands::create_category,
+            commands::rename_category,
+            commands::archive_category,
+            commands::merge_categories,
+            commands::get_clients,
+            commands::create_client,
+            commands::rename_client,
+            commands::archive_client,
+            commands::get_projects,
+            commands::create_project,
+            commands::rename_project,
+            commands::archive_project,
+            commands::get_project_totals,
+            commands::get_client_totals,
+            commands::get_tags,
+            commands::get_entry_tags,
+            commands::add_tag_to_entry,
+            commands::remove_tag_from_entry,
+            commands::get_entries_by_tags,
+            commands::get_minutes_by_tags,
+            commands::get_tag_totals,
+            commands::get_category_report,
+            commands::get_work_schedule,
+            commands::set_work_hours,
+            commands::add_day_off,
+            commands::remove_day_off,
+            commands::get_database_location,
+            commands::list_backups,
+            commands::restore_backup,
+            commands::set_database_location,
+            commands::get_timer_state,
+            commands::pause_timer,
+            commands::resume_timer,
+            commands::snooze_timer,
         ])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .run(tauri::generate_context!());
+
+    if let Err(e) = result {
+        log::error!("Time Tracker could not start: {}", e);
+        std::process::exit(1);
+    }
 }
@@ -0,0 +1,101 @@
+use crate::db::backup::{BackupInfo, BackupReason, BackupStore};
+use crate::db::{Database, SettingsRepository};
+use crate::services::Clock;
+use std::time::Duration;
+
+/// Time between scheduled backups
+pub const BACKUP_INTERVAL_SECONDS: i64 = 24 * 60 * 60;
+
+/// When the next scheduled backup is due, given when the newest backup of
+/// any kind was taken
+pub fn next_backup_at(latest: Option<i64>, now: i64) -> i64 {
+    match latest {
+        // A backup stamped in the future means the clock went back; don't
+        // wait longer than one interval for it
+        Some(latest) => (latest + BACKUP_INTERVAL_SECONDS).min(now + BACKUP_INTERVAL_SECONDS),
+        None => now,
+    }
+}
+
+/// Backs up the live database, keeping as many copies as the `backup_count`
+/// setting allows
+pub fn back_up(
+    db: &Database,
+    store: &BackupStore,
+    reason: BackupReason,
+    now: i64,
+) -> Result<BackupInfo, String> {
+    let keep = SettingsRepository::new(db.conn.lock().map_err(|e| e.to_string())?)
+        .get_backup_count();
+    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+    store
+        .create(&conn, reason, now, keep)
+        .map_err(|e| e.to_string())
+}
+
+/// Takes a backup once a day for as long as the app runs. The first one is
+/// taken right away unless a backup from the last day already exists.
+pub async fn run_daily(clock: &dyn Clock, db: &Database, store: &BackupStore) {
+    loop {
+        let now = clock.now();
+        let latest = match store.latest() {
+            Ok(latest) => latest.map(|b| b.created_at),
+            Err(e) => {
+                log::warn!("Cannot list backups: {}", e);
+                None
+            }
+        };
+
+        let due = next_backup_at(latest, now);
+        let wait = if due <= now {
+            match back_up(db, store, BackupReason::Daily, now) {
+                Ok(backup) => log::info!("Backed up database to {}", backup.path.display()),
+                // Try again next interval rather than hammering a full disk
+                Err(e) => log::warn!("Daily backup failed: {}", e),
+            }
+            BACKUP_INTERVAL_SECONDS
+        } else {
+            due - now
+        };
+        tokio::time::sleep(Duration::from_secs(wait as u64)).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::migrations;
+    use crate::services::clock::TokioClock;
+
+    const DAY: i64 = BACKUP_INTERVAL_SECONDS;
+
+    #[test]
+    fn test_next_backup_at() {
+        assert_eq!(next_backup_at(None, 5_000), 5_000);
+        assert_eq!(next_backup_at(Some(1_000), 5_000), 1_000 + DAY);
+        assert_eq!(next_backup_at(Some(1_000), 1_000 + 2 * DAY), 1_000 + DAY);
+        assert_eq!(next_backup_at(Some(10 * DAY), 0), DAY);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_run_daily_backs_up_once_a_day() {
+        let dir = std::env::temp_dir().join(format!("daily-backup-test-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let store = BackupStore::new(dir.clone());
+        let db = Database::new_in_memory().unwrap();
+        migrations::run_migrations(&db.conn.lock().unwrap()).unwrap();
+        let start = 1_700_000_000;
+        let clock = TokioClock::starting_at(start);
+
+        let ran = tokio::time::timeout(
+            Duration::from_secs((3 * DAY - 3600) as u64),
+            run_daily(&clock, &db, &store),
+        )
+        .await;
+
+        assert!(ran.is_err(), "runs until the app exits");
+        let times: Vec<i64> = store.list().unwrap().iter().map(|b| b.created_at).collect();
+        assert_eq!(times, vec![start + 2 * DAY, start + DAY, start]);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
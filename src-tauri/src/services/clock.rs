@@ -0,0 +1,53 @@
+/// Source of the current time for the timer, so tests can run a day's worth of
+/// ticks without waiting for it
+pub trait Clock: Send + Sync {
+    /// Current unix timestamp in seconds
+    fn now(&self) -> i64;
+}
+
+/// The system's wall clock
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> i64 {
+        chrono::Utc::now().timestamp()
+    }
+}
+
+/// Wall clock that follows tokio's clock from a fixed starting timestamp, so it
+/// advances together with `tokio::time::pause`d timers
+#[cfg(test)]
+pub struct TokioClock {
+    start: i64,
+    origin: tokio::time::Instant,
+}
+
+#[cfg(test)]
+impl TokioClock {
+    pub fn starting_at(start: i64) -> Self {
+        Self {
+            start,
+            origin: tokio::time::Instant::now(),
+        }
+    }
+}
+
+#[cfg(test)]
+impl Clock for TokioClock {
+    fn now(&self) -> i64 {
+        self.start + self.origin.elapsed().as_secs() as i64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn test_tokio_clock_follows_paused_time() {
+        let clock = TokioClock::starting_at(1_000);
+        assert_eq!(clock.now(), 1_000);
+        tokio::time::sleep(std::time::Duration::from_secs(90)).await;
+        assert_eq!(clock.now(), 1_090);
+    }
+}
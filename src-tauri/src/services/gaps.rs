@@ -0,0 +1,195 @@
+use crate::db::repositories::{RepositoryError, Result};
+use crate::db::{Database, HeartbeatRepository, MissedPromptRepository, ScheduleRepository, WorkSchedule};
+use crate::services::schedule;
+use chrono::TimeZone;
+
+/// How far back a single gap is backfilled, so a laptop left in a drawer for
+/// a month doesn't come back to thousands of missed prompts
+pub const MAX_BACKFILL_SECONDS: i64 = 7 * 24 * 60 * 60;
+
+/// Why a run of intervals went unprompted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GapReason {
+    /// The timer was running but didn't tick, typically because the machine slept
+    Sleep,
+    /// The app was quit or had not been started
+    AppNotRunning,
+}
+
+impl GapReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            GapReason::Sleep => "sleep",
+            GapReason::AppNotRunning => "app_not_running",
+        }
+    }
+}
+
+/// Starts of the intervals strictly between the one containing `last_tick`,
+/// which was already handled, and the one containing `until`, which is about
+/// to be, oldest first. Only working-hours intervals in `tz` are returned.
+pub fn missed_intervals<Tz: TimeZone>(
+    last_tick: i64,
+    until: i64,
+    interval_minutes: i64,
+    work_schedule: &WorkSchedule,
+    tz: &Tz,
+) -> Vec<i64> {
+    let step = interval_minutes * 60;
+    if step <= 0 {
+        return Vec::new();
+    }
+    let end = align(until, step);
+    let first = (align(last_tick, step) + step).max(align(end - MAX_BACKFILL_SECONDS, step));
+
+    (0..)
+        .map(|i| first + i * step)
+        .take_while(|&start| start < end)
+        .filter(|&start| schedule::is_tracked_interval(work_schedule, start, tz))
+        .collect()
+}
+
+/// Records a missed prompt for every tracked interval between the last
+/// heartbeat and `until` that has no entry yet, then moves the heartbeat to
+/// `until`. Returns how many prompts were added. Nothing is backfilled on the
+/// very first run, when there is no heartbeat to measure from.
+pub fn backfill<Tz: TimeZone>(
+    db: &Database,
+    until: i64,
+    interval_minutes: i64,
+    reason: GapReason,
+    tz: &Tz,
+) -> Result<usize> {
+    let conn = || {
+        db.conn
+            .lock()
+            .map_err(|e| RepositoryError::LockError(e.to_string()))
+    };
+
+    let last_tick = HeartbeatRepository::new(conn()?).last_tick()?;
+    let added = match last_tick {
+        Some(last_tick) if last_tick < until => {
+            let work_schedule = ScheduleRepository::new(conn()?).load()?;
+            let missed = missed_intervals(last_tick, until, interval_minutes, &work_schedule, tz);
+            MissedPromptRepository::new(conn()?).create_for_gaps(&missed, reason.as_str())?
+        }
+        _ => 0,
+    };
+
+    HeartbeatRepository::new(conn()?).record_tick(until)?;
+    Ok(added)
+}
+
+fn align(timestamp: i64, step: i64) -> i64 {
+    timestamp.div_euclid(step) * step
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{migrations, AwayReason, TimeEntryRepository, WorkBlock};
+    use chrono::Utc;
+
+    const HOUR: i64 = 3600;
+
+    fn setup_db() -> Database {
+        let db = Database::new_in_memory().unwrap();
+        {
+            let conn = db.conn.lock().unwrap();
+            migrations::run_migrations(&conn).unwrap();
+        }
+        db
+    }
+
+    fn missed(db: &Database) -> Vec<(i64, Option<String>)> {
+        let conn = db.conn.lock().unwrap();
+        MissedPromptRepository::new(conn)
+            .find_by_date_range(0, i64::MAX)
+            .unwrap()
+            .into_iter()
+            .map(|prompt| (prompt.timestamp, prompt.reason))
+            .collect()
+    }
+
+    #[test]
+    fn test_no_gap_between_consecutive_ticks() {
+        // 10:00 was handled and 10:15 is being handled now
+        let ticks = missed_intervals(10 * HOUR, 10 * HOUR + 900, 15, &WorkSchedule::default(), &Utc);
+        assert!(ticks.is_empty());
+    }
+
+    #[test]
+    fn test_gap_after_sleep() {
+        // 10:00 was handled, then the machine slept until 11:00 was due
+        let missed = missed_intervals(10 * HOUR, 11 * HOUR, 15, &WorkSchedule::default(), &Utc);
+        assert_eq!(missed, vec![10 * HOUR + 900, 10 * HOUR + 1800, 10 * HOUR + 2700]);
+    }
+
+    #[test]
+    fn test_startup_covers_intervals_up_to_now() {
+        // Quit at 09:05 after handling 08:45, restarted at 09:40; 09:30 is still running
+        let missed = missed_intervals(9 * HOUR - 900, 9 * HOUR + 2400, 15, &WorkSchedule::default(), &Utc);
+        assert_eq!(missed, vec![9 * HOUR, 9 * HOUR + 900]);
+    }
+
+    #[test]
+    fn test_only_working_hours_count() {
+        let work_schedule = WorkSchedule {
+            // 1970-01-01 was a Thursday; work 09:00-10:00
+            blocks: vec![WorkBlock {
+                weekday: 3,
+                start_minute: 540,
+                end_minute: 600,
+            }],
+            days_off: Vec::new(),
+        };
+        let missed = missed_intervals(0, 24 * HOUR, 30, &work_schedule, &Utc);
+        assert_eq!(missed, vec![9 * HOUR, 9 * HOUR + 1800]);
+    }
+
+    #[test]
+    fn test_backfill_is_capped() {
+        let missed = missed_intervals(0, 30 * 24 * HOUR, 60, &WorkSchedule::default(), &Utc);
+        assert_eq!(missed.len(), 7 * 24);
+        assert_eq!(missed[0], 23 * 24 * HOUR);
+    }
+
+    #[test]
+    fn test_reason_names() {
+        assert_eq!(GapReason::Sleep.as_str(), "sleep");
+        assert_eq!(GapReason::AppNotRunning.as_str(), "app_not_running");
+    }
+
+    #[test]
+    fn test_backfill_first_run_only_records_heartbeat() {
+        let db = setup_db();
+
+        assert_eq!(backfill(&db, 10 * HOUR, 15, GapReason::AppNotRunning, &Utc).unwrap(), 0);
+
+        let conn = db.conn.lock().unwrap();
+        assert_eq!(HeartbeatRepository::new(conn).last_tick().unwrap(), Some(10 * HOUR));
+    }
+
+    #[test]
+    fn test_backfill_skips_intervals_with_entries() {
+        let db = setup_db();
+        backfill(&db, 10 * HOUR, 30, GapReason::Sleep, &Utc).unwrap();
+        {
+            let conn = db.conn.lock().unwrap();
+            TimeEntryRepository::new(conn)
+                .create_away_entry(10 * HOUR + 1800, 30, AwayReason::Idle)
+                .unwrap();
+        }
+
+        let added = backfill(&db, 12 * HOUR, 30, GapReason::Sleep, &Utc).unwrap();
+
+        assert_eq!(added, 2);
+        assert_eq!(
+            missed(&db),
+            vec![
+                (11 * HOUR, Some("sleep".to_string())),
+                (11 * HOUR + 1800, Some("sleep".to_string())),
+            ]
+        );
+    }
+}
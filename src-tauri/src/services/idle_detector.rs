@@ -1,29 +1,907 @@
+use crate::db::{AwayReason, IdleBackend};
+use std::io::Read;
+use std::process::{Command, Stdio};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// A suspend shows up as the wall clock running ahead of a monotonic clock
+/// that stops while the machine sleeps. Smaller gaps are scheduling noise.
+const SLEEP_GAP_SECONDS: i64 = 60;
+
+/// How long a tick waits for the idle state before going on without it
+const QUERY_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How long a helper such as gdbus may run before it is killed
+const COMMAND_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// How often `Auto` probes the backends again while none of them answers
+const REPROBE_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+/// What the desktop reports about the user at one moment
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum IdleState {
+    Active,
+    /// No keyboard or mouse input for this many seconds; the user may still
+    /// be there, e.g. reading
+    Idle { seconds: f64 },
+    Locked,
+    /// The system is about to sleep, or slept since the last check
+    Suspended,
+}
+
+impl IdleState {
+    pub fn from_idle_seconds(seconds: f64) -> Self {
+        if seconds > 0.0 {
+            IdleState::Idle { seconds }
+        } else {
+            IdleState::Active
+        }
+    }
+}
+
+/// A way of asking the desktop whether the user is around
+pub trait IdleSource: Send + Sync {
+    fn backend(&self) -> IdleBackend;
+
+    /// The current state, or `None` when the backend can't answer (not
+    /// running under that desktop, tool missing)
+    fn state(&self) -> Option<IdleState>;
+}
+
+/// Asks `source` for the current state on the blocking thread pool, since
+/// backends make system calls or run helpers. Gives up with `None` after
+/// `QUERY_TIMEOUT` so a hung desktop service can't stall the timer.
+pub async fn query(source: Arc<dyn IdleSource>) -> Option<IdleState> {
+    query_within(source, QUERY_TIMEOUT).await
+}
+
+async fn query_within(source: Arc<dyn IdleSource>, timeout: Duration) -> Option<IdleState> {
+    let backend = source.backend();
+    let state = tokio::task::spawn_blocking(move || source.state());
+    match tokio::time::timeout(timeout, state).await {
+        Ok(Ok(state)) => state,
+        Ok(Err(e)) => {
+            log::warn!("{} idle detection failed: {}", backend.as_str(), e);
+            None
+        }
+        Err(_) => {
+            log::warn!("{} idle detection did not answer in time", backend.as_str());
+            None
+        }
+    }
+}
+
+/// Why an interval counts as away, if it does. A locked screen or suspend is
+/// away straight away; plain inactivity only once it passes the threshold.
+pub fn away_reason(state: IdleState, threshold_minutes: u32) -> Option<AwayReason> {
+    match state {
+        IdleState::Active => None,
+        IdleState::Idle { seconds } if seconds >= threshold_minutes as f64 * 60.0 => {
+            Some(AwayReason::Idle)
+        }
+        IdleState::Idle { .. } => None,
+        IdleState::Locked => Some(AwayReason::Locked),
+        IdleState::Suspended => Some(AwayReason::Suspended),
+    }
+}
+
+/// Notices that the machine slept between two checks
+#[derive(Debug, Default)]
+pub struct SleepDetector {
+    last: Option<(Duration, i64)>,
+}
+
+impl SleepDetector {
+    /// `uptime` comes from a monotonic clock that stops during sleep, `wall`
+    /// is the unix time. The first call only sets the baseline.
+    pub fn slept(&mut self, uptime: Duration, wall: i64) -> bool {
+        let slept = self.last.is_some_and(|(last_uptime, last_wall)| {
+            let awake = uptime.saturating_sub(last_uptime).as_secs() as i64;
+            wall - last_wall - awake >= SLEEP_GAP_SECONDS
+        });
+        self.last = Some((uptime, wall));
+        slept
+    }
+}
+
+/// The clocks `SleepDetector` compares
+pub trait SleepClock: Send + Sync {
+    /// Time since some fixed point, not counting sleep, and the unix time
+    fn clocks(&self) -> (Duration, i64);
+}
+
+/// `Instant` follows CLOCK_MONOTONIC on Linux and the uptime clock on macOS,
+/// and neither counts time spent asleep
+pub struct SystemSleepClock {
+    origin: Instant,
+}
+
+impl SystemSleepClock {
+    pub fn new() -> Self {
+        Self {
+            origin: Instant::now(),
+        }
+    }
+}
+
+impl Default for SystemSleepClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SleepClock for SystemSleepClock {
+    fn clocks(&self) -> (Duration, i64) {
+        (self.origin.elapsed(), chrono::Utc::now().timestamp())
+    }
+}
+
+/// Reports `Suspended` once after the machine slept, whatever the backend
+/// underneath says. Backends see a suspend coming at best, so the first
+/// check after waking up would otherwise miss it.
+pub struct SleepAwareSource {
+    inner: Box<dyn IdleSource>,
+    clock: Box<dyn SleepClock>,
+    sleep: Mutex<SleepDetector>,
+}
+
+impl SleepAwareSource {
+    pub fn new(inner: Box<dyn IdleSource>) -> Self {
+        Self::with_clock(inner, Box::new(SystemSleepClock::new()))
+    }
+
+    pub fn with_clock(inner: Box<dyn IdleSource>, clock: Box<dyn SleepClock>) -> Self {
+        Self {
+            inner,
+            clock,
+            sleep: Mutex::new(SleepDetector::default()),
+        }
+    }
+}
+
+impl IdleSource for SleepAwareSource {
+    fn backend(&self) -> IdleBackend {
+        self.inner.backend()
+    }
+
+    fn state(&self) -> Option<IdleState> {
+        let (uptime, wall) = self.clock.clocks();
+        if self.sleep.lock().unwrap().slept(uptime, wall) {
+            return Some(IdleState::Suspended);
+        }
+        self.inner.state()
+    }
+}
+
+/// What macOS reports about the login session
+pub trait SessionProbe: Send + Sync {
+    fn seconds_since_input(&self) -> f64;
+
+    fn screen_locked(&self) -> bool;
+}
+
+/// The Quartz session: input idle time and the screen lock flag
+pub struct MacOsIdleSource {
+    probe: Box<dyn SessionProbe>,
+}
+
+impl MacOsIdleSource {
+    #[cfg(target_os = "macos")]
+    pub fn new() -> Self {
+        Self::with_probe(Box::new(quartz::QuartzProbe))
+    }
+
+    pub fn with_probe(probe: Box<dyn SessionProbe>) -> Self {
+        Self { probe }
+    }
+}
+
 #[cfg(target_os = "macos")]
-pub fn get_idle_time_seconds() -> f64 {
-    // Use raw FFI call to get idle time from macOS
+impl Default for MacOsIdleSource {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl IdleSource for MacOsIdleSource {
+    fn backend(&self) -> IdleBackend {
+        IdleBackend::MacOs
+    }
+
+    fn state(&self) -> Option<IdleState> {
+        if self.probe.screen_locked() {
+            return Some(IdleState::Locked);
+        }
+        Some(IdleState::from_idle_seconds(self.probe.seconds_since_input()))
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod quartz {
+    use super::SessionProbe;
+    use std::ffi::c_void;
+    use std::os::raw::{c_char, c_ulong};
+    use std::ptr;
+
+    type CFTypeRef = *const c_void;
+
+    #[link(name = "CoreGraphics", kind = "framework")]
     extern "C" {
-        fn CGEventSourceSecondsSinceLastEventType(
-            stateID: u32,
-            eventType: u32,
-        ) -> f64;
+        fn CGEventSourceSecondsSinceLastEventType(state_id: u32, event_type: u32) -> f64;
+        fn CGSessionCopyCurrentDictionary() -> CFTypeRef;
+    }
+
+    #[link(name = "CoreFoundation", kind = "framework")]
+    extern "C" {
+        fn CFStringCreateWithCString(
+            allocator: CFTypeRef,
+            c_str: *const c_char,
+            encoding: u32,
+        ) -> CFTypeRef;
+        fn CFDictionaryGetValue(dict: CFTypeRef, key: CFTypeRef) -> CFTypeRef;
+        fn CFGetTypeID(cf: CFTypeRef) -> c_ulong;
+        fn CFBooleanGetTypeID() -> c_ulong;
+        fn CFBooleanGetValue(boolean: CFTypeRef) -> u8;
+        fn CFRelease(cf: CFTypeRef);
     }
 
     // kCGEventSourceStateCombinedSessionState = 0
     // kCGAnyInputEventType = 0xFFFFFFFF (~0u32)
     const COMBINED_SESSION_STATE: u32 = 0;
     const ANY_INPUT_EVENT_TYPE: u32 = 0xFFFFFFFF;
+    const STRING_ENCODING_UTF8: u32 = 0x0800_0100;
+    /// Only present in the session dictionary while the screen is locked
+    const SCREEN_IS_LOCKED_KEY: &[u8] = b"CGSSessionScreenIsLocked\0";
+
+    pub struct QuartzProbe;
+
+    impl SessionProbe for QuartzProbe {
+        fn seconds_since_input(&self) -> f64 {
+            unsafe {
+                CGEventSourceSecondsSinceLastEventType(COMBINED_SESSION_STATE, ANY_INPUT_EVENT_TYPE)
+            }
+        }
+
+        fn screen_locked(&self) -> bool {
+            unsafe {
+                let session = CGSessionCopyCurrentDictionary();
+                if session.is_null() {
+                    return false;
+                }
+                let key = CFStringCreateWithCString(
+                    ptr::null(),
+                    SCREEN_IS_LOCKED_KEY.as_ptr() as *const c_char,
+                    STRING_ENCODING_UTF8,
+                );
+                let value = CFDictionaryGetValue(session, key);
+                let locked = !value.is_null()
+                    && CFGetTypeID(value) == CFBooleanGetTypeID()
+                    && CFBooleanGetValue(value) != 0;
+                CFRelease(key);
+                CFRelease(session);
+                locked
+            }
+        }
+    }
+}
+
+/// X11 screensaver extension (XScreenSaver), queried over Xlib
+pub struct X11IdleSource {
+    #[cfg(all(unix, not(target_os = "macos")))]
+    screensaver: Option<xss::ScreenSaver>,
+}
+
+impl X11IdleSource {
+    pub fn new() -> Self {
+        Self {
+            #[cfg(all(unix, not(target_os = "macos")))]
+            screensaver: xss::ScreenSaver::open(),
+        }
+    }
+}
+
+impl Default for X11IdleSource {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl IdleSource for X11IdleSource {
+    fn backend(&self) -> IdleBackend {
+        IdleBackend::X11
+    }
+
+    #[cfg(all(unix, not(target_os = "macos")))]
+    fn state(&self) -> Option<IdleState> {
+        self.screensaver.as_ref()?.state()
+    }
+
+    #[cfg(not(all(unix, not(target_os = "macos"))))]
+    fn state(&self) -> Option<IdleState> {
+        None
+    }
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+mod xss {
+    use super::IdleState;
+    use std::os::raw::c_int;
+    use x11_dl::xlib::Xlib;
+    use x11_dl::xss::{ScreenSaverOn, Xss};
+
+    /// Xlib and libXss, loaded at runtime so the app still starts on systems
+    /// without them
+    pub(super) struct ScreenSaver {
+        xlib: Xlib,
+        xss: Xss,
+    }
+
+    impl ScreenSaver {
+        pub(super) fn open() -> Option<Self> {
+            Some(Self {
+                xlib: Xlib::open().ok()?,
+                xss: Xss::open().ok()?,
+            })
+        }
+
+        /// Asks the server in `$DISPLAY`; an active screensaver counts as
+        /// locked. `None` without a server or the extension.
+        pub(super) fn state(&self) -> Option<IdleState> {
+            unsafe {
+                let display = (self.xlib.XOpenDisplay)(std::ptr::null());
+                if display.is_null() {
+                    return None;
+                }
+                let state = self.query(display);
+                (self.xlib.XCloseDisplay)(display);
+                state
+            }
+        }
+
+        unsafe fn query(&self, display: *mut x11_dl::xlib::Display) -> Option<IdleState> {
+            let (mut event_base, mut error_base): (c_int, c_int) = (0, 0);
+            if (self.xss.XScreenSaverQueryExtension)(display, &mut event_base, &mut error_base) == 0 {
+                return None;
+            }
+            let info = (self.xss.XScreenSaverAllocInfo)();
+            if info.is_null() {
+                return None;
+            }
+            let root = (self.xlib.XDefaultRootWindow)(display);
+            let answered = (self.xss.XScreenSaverQueryInfo)(display, root, info) != 0;
+            let (saver_state, idle_millis) = ((*info).state, (*info).idle);
+            (self.xlib.XFree)(info.cast());
+
+            if !answered {
+                None
+            } else if saver_state == ScreenSaverOn {
+                Some(IdleState::Locked)
+            } else {
+                Some(IdleState::from_idle_seconds(idle_millis as f64 / 1000.0))
+            }
+        }
+    }
+}
+
+/// `org.freedesktop.ScreenSaver` on the session bus; an active screensaver
+/// counts as locked
+pub struct ScreenSaverIdleSource;
+
+impl ScreenSaverIdleSource {
+    fn call(method: &str) -> Option<String> {
+        run(
+            "gdbus",
+            &[
+                "call",
+                "--session",
+                "--dest",
+                "org.freedesktop.ScreenSaver",
+                "--object-path",
+                "/org/freedesktop/ScreenSaver",
+                "--method",
+                &format!("org.freedesktop.ScreenSaver.{}", method),
+            ],
+        )
+    }
+}
+
+impl IdleSource for ScreenSaverIdleSource {
+    fn backend(&self) -> IdleBackend {
+        IdleBackend::ScreenSaver
+    }
+
+    fn state(&self) -> Option<IdleState> {
+        if parse_gdbus_value(&Self::call("GetActive")?) == Some(true) {
+            return Some(IdleState::Locked);
+        }
+        let millis: u64 = parse_gdbus_value(&Self::call("GetSessionIdleTime")?)?;
+        Some(IdleState::from_idle_seconds(millis as f64 / 1000.0))
+    }
+}
+
+/// systemd-logind hints for the current session, plus the manager's
+/// `PreparingForSleep` flag
+pub struct LogindIdleSource;
+
+impl LogindIdleSource {
+    fn property(object_path: &str, interface: &str, name: &str) -> Option<String> {
+        run(
+            "gdbus",
+            &[
+                "call",
+                "--system",
+                "--dest",
+                "org.freedesktop.login1",
+                "--object-path",
+                object_path,
+                "--method",
+                "org.freedesktop.DBus.Properties.Get",
+                interface,
+                name,
+            ],
+        )
+    }
+
+    fn session_property<T: std::str::FromStr>(name: &str) -> Option<T> {
+        let reply = Self::property(
+            "/org/freedesktop/login1/session/auto",
+            "org.freedesktop.login1.Session",
+            name,
+        )?;
+        parse_gdbus_value(&reply)
+    }
+}
+
+impl IdleSource for LogindIdleSource {
+    fn backend(&self) -> IdleBackend {
+        IdleBackend::Logind
+    }
+
+    fn state(&self) -> Option<IdleState> {
+        let preparing_for_sleep = Self::property(
+            "/org/freedesktop/login1",
+            "org.freedesktop.login1.Manager",
+            "PreparingForSleep",
+        )
+        .and_then(|reply| parse_gdbus_value(&reply));
+        if preparing_for_sleep == Some(true) {
+            return Some(IdleState::Suspended);
+        }
+        if Self::session_property("LockedHint") == Some(true) {
+            return Some(IdleState::Locked);
+        }
+        let idle: bool = Self::session_property("IdleHint")?;
+        if !idle {
+            return Some(IdleState::Active);
+        }
+        // Microseconds since the epoch at which the session went idle
+        let since: i64 = Self::session_property("IdleSinceHint")?;
+        let now = chrono::Utc::now().timestamp_micros();
+        Some(IdleState::from_idle_seconds((now - since).max(0) as f64 / 1_000_000.0))
+    }
+}
+
+/// Idle detection turned off: the user always counts as active
+pub struct NoIdleSource;
+
+impl IdleSource for NoIdleSource {
+    fn backend(&self) -> IdleBackend {
+        IdleBackend::None
+    }
 
-    unsafe {
-        CGEventSourceSecondsSinceLastEventType(COMBINED_SESSION_STATE, ANY_INPUT_EVENT_TYPE)
+    fn state(&self) -> Option<IdleState> {
+        Some(IdleState::Active)
     }
 }
 
-#[cfg(not(target_os = "macos"))]
-pub fn get_idle_time_seconds() -> f64 {
-    0.0
+/// `Auto`: the first backend that answers, in `candidates` order. Probing
+/// starts at the first query, runs again as soon as the chosen backend stops
+/// answering, and is retried every `REPROBE_INTERVAL` while none answers,
+/// which counts as active meanwhile.
+pub struct AutoIdleSource {
+    sources: Vec<Box<dyn IdleSource>>,
+    reprobe_interval: Duration,
+    probe: Mutex<Probe>,
+}
+
+#[derive(Default)]
+struct Probe {
+    /// Index into `sources` of the backend in use
+    chosen: Option<usize>,
+    /// When to probe again after no backend answered
+    retry_at: Option<Instant>,
+}
+
+impl AutoIdleSource {
+    pub fn new(sources: Vec<Box<dyn IdleSource>>, reprobe_interval: Duration) -> Self {
+        Self {
+            sources,
+            reprobe_interval,
+            probe: Mutex::new(Probe::default()),
+        }
+    }
 }
 
-pub fn is_user_idle(threshold_minutes: u32) -> bool {
-    let idle_seconds = get_idle_time_seconds();
-    idle_seconds >= (threshold_minutes as f64 * 60.0)
+impl IdleSource for AutoIdleSource {
+    fn backend(&self) -> IdleBackend {
+        IdleBackend::Auto
+    }
+
+    fn state(&self) -> Option<IdleState> {
+        let mut probe = self.probe.lock().unwrap();
+        if let Some(index) = probe.chosen {
+            let source = &self.sources[index];
+            if let Some(state) = source.state() {
+                return Some(state);
+            }
+            log::info!("{} idle detection stopped answering", source.backend().as_str());
+            probe.chosen = None;
+        } else if probe.retry_at.is_some_and(|at| Instant::now() < at) {
+            return Some(IdleState::Active);
+        }
+
+        for (index, source) in self.sources.iter().enumerate() {
+            if let Some(state) = source.state() {
+                log::info!("Using {} idle detection", source.backend().as_str());
+                *probe = Probe {
+                    chosen: Some(index),
+                    retry_at: None,
+                };
+                return Some(state);
+            }
+        }
+        if probe.retry_at.is_none() {
+            log::warn!("No idle detection backend available; away time will not be detected");
+        }
+        probe.retry_at = Some(Instant::now() + self.reprobe_interval);
+        Some(IdleState::Active)
+    }
+}
+
+/// The source for an explicitly chosen backend, or one that picks the first
+/// backend that answers when `Auto` is selected. Backends that don't exist
+/// on this platform fall back to never reporting idle. Unless detection is
+/// turned off, waking from sleep is reported whichever backend is in use.
+pub fn source_for(backend: IdleBackend) -> Box<dyn IdleSource> {
+    match backend {
+        IdleBackend::None => Box::new(NoIdleSource),
+        backend => Box::new(SleepAwareSource::new(backend_source(backend))),
+    }
+}
+
+fn backend_source(backend: IdleBackend) -> Box<dyn IdleSource> {
+    match backend {
+        IdleBackend::Auto => {
+            let wayland = std::env::var_os("WAYLAND_DISPLAY").is_some();
+            let x11 = std::env::var_os("DISPLAY").is_some();
+            let sources = candidates(cfg!(target_os = "macos"), wayland, x11)
+                .into_iter()
+                .map(backend_source)
+                .collect();
+            Box::new(AutoIdleSource::new(sources, REPROBE_INTERVAL))
+        }
+        #[cfg(target_os = "macos")]
+        IdleBackend::MacOs => Box::new(MacOsIdleSource::new()),
+        #[cfg(not(target_os = "macos"))]
+        IdleBackend::MacOs => Box::new(NoIdleSource),
+        IdleBackend::X11 => Box::new(X11IdleSource::new()),
+        IdleBackend::ScreenSaver => Box::new(ScreenSaverIdleSource),
+        IdleBackend::Logind => Box::new(LogindIdleSource),
+        IdleBackend::None => Box::new(NoIdleSource),
+    }
+}
+
+/// Backends worth probing, most precise first. Under Wayland the X11 query
+/// only sees XWayland clients, so it comes last there.
+fn candidates(macos: bool, wayland: bool, x11: bool) -> Vec<IdleBackend> {
+    if macos {
+        return vec![IdleBackend::MacOs];
+    }
+    let mut backends = Vec::new();
+    if x11 && !wayland {
+        backends.push(IdleBackend::X11);
+    }
+    backends.push(IdleBackend::ScreenSaver);
+    backends.push(IdleBackend::Logind);
+    if x11 && wayland {
+        backends.push(IdleBackend::X11);
+    }
+    backends
+}
+
+/// Runs a helper and returns what it printed, or `None` if it failed or was
+/// still running after `COMMAND_TIMEOUT`, in which case it is killed
+fn run(program: &str, args: &[&str]) -> Option<String> {
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .ok()?;
+    let deadline = Instant::now() + COMMAND_TIMEOUT;
+    let status = loop {
+        match child.try_wait() {
+            Ok(Some(status)) => break status,
+            Ok(None) if Instant::now() < deadline => std::thread::sleep(Duration::from_millis(10)),
+            _ => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return None;
+            }
+        }
+    };
+    if !status.success() {
+        return None;
+    }
+    let mut output = String::new();
+    child.stdout.take()?.read_to_string(&mut output).ok()?;
+    Some(output)
+}
+
+/// Pulls the single value out of a gdbus reply such as `(uint32 1500,)`,
+/// `(<uint64 1700000000000000>,)` or `(<true>,)`
+fn parse_gdbus_value<T: std::str::FromStr>(reply: &str) -> Option<T> {
+    let inner = reply
+        .trim()
+        .strip_prefix('(')?
+        .strip_suffix(')')?
+        .trim_end_matches(',')
+        .trim()
+        .trim_start_matches('<')
+        .trim_end_matches('>');
+    inner.rsplit(' ').next()?.parse().ok()
+}
+
+/// Plays back a fixed sequence of states, one per query, then keeps
+/// repeating the last one
+#[cfg(test)]
+pub struct ScriptedIdleSource(std::sync::Mutex<std::collections::VecDeque<IdleState>>);
+
+#[cfg(test)]
+impl ScriptedIdleSource {
+    pub fn new(states: impl IntoIterator<Item = IdleState>) -> Self {
+        Self(std::sync::Mutex::new(states.into_iter().collect()))
+    }
+}
+
+#[cfg(test)]
+impl IdleSource for ScriptedIdleSource {
+    fn backend(&self) -> IdleBackend {
+        IdleBackend::None
+    }
+
+    fn state(&self) -> Option<IdleState> {
+        let mut states = self.0.lock().unwrap();
+        if states.len() > 1 {
+            states.pop_front()
+        } else {
+            states.front().copied()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_away_reason_by_state() {
+        assert_eq!(away_reason(IdleState::Active, 5), None);
+        assert_eq!(away_reason(IdleState::Idle { seconds: 299.0 }, 5), None);
+        assert_eq!(
+            away_reason(IdleState::Idle { seconds: 300.0 }, 5),
+            Some(AwayReason::Idle)
+        );
+        assert_eq!(away_reason(IdleState::Locked, 5), Some(AwayReason::Locked));
+        assert_eq!(away_reason(IdleState::Suspended, 5), Some(AwayReason::Suspended));
+    }
+
+    #[test]
+    fn test_from_idle_seconds() {
+        assert_eq!(IdleState::from_idle_seconds(0.0), IdleState::Active);
+        assert_eq!(IdleState::from_idle_seconds(12.5), IdleState::Idle { seconds: 12.5 });
+    }
+
+    #[test]
+    fn test_scripted_source_repeats_last_state() {
+        let source = ScriptedIdleSource::new([IdleState::Active, IdleState::Locked]);
+        assert_eq!(source.state(), Some(IdleState::Active));
+        assert_eq!(source.state(), Some(IdleState::Locked));
+        assert_eq!(source.state(), Some(IdleState::Locked));
+        assert_eq!(ScriptedIdleSource::new([]).state(), None);
+    }
+
+    /// Session state a test can change between queries
+    #[derive(Default)]
+    struct FakeSession {
+        idle_seconds: f64,
+        locked: bool,
+    }
+
+    struct FakeProbe(std::sync::Arc<Mutex<FakeSession>>);
+
+    impl SessionProbe for FakeProbe {
+        fn seconds_since_input(&self) -> f64 {
+            self.0.lock().unwrap().idle_seconds
+        }
+
+        fn screen_locked(&self) -> bool {
+            self.0.lock().unwrap().locked
+        }
+    }
+
+    struct FakeClock(Arc<Mutex<(Duration, i64)>>);
+
+    impl SleepClock for FakeClock {
+        fn clocks(&self) -> (Duration, i64) {
+            *self.0.lock().unwrap()
+        }
+    }
+
+    fn fake_macos_source() -> (MacOsIdleSource, std::sync::Arc<Mutex<FakeSession>>) {
+        let session = std::sync::Arc::new(Mutex::new(FakeSession::default()));
+        let source = MacOsIdleSource::with_probe(Box::new(FakeProbe(session.clone())));
+        (source, session)
+    }
+
+    #[test]
+    fn test_sleep_detector_needs_a_wall_clock_jump() {
+        let mut detector = SleepDetector::default();
+        assert!(!detector.slept(Duration::from_secs(100), 1_000));
+        assert!(!detector.slept(Duration::from_secs(1_000), 1_905));
+        assert!(detector.slept(Duration::from_secs(1_010), 5_000));
+        assert!(!detector.slept(Duration::from_secs(1_910), 5_900));
+    }
+
+    #[test]
+    fn test_macos_source_reports_lock() {
+        let (source, session) = fake_macos_source();
+        session.lock().unwrap().idle_seconds = 30.0;
+        assert_eq!(source.state(), Some(IdleState::Idle { seconds: 30.0 }));
+
+        session.lock().unwrap().locked = true;
+        assert_eq!(source.state(), Some(IdleState::Locked));
+
+        let mut fake = session.lock().unwrap();
+        fake.locked = false;
+        fake.idle_seconds = 0.0;
+        drop(fake);
+        assert_eq!(source.state(), Some(IdleState::Active));
+    }
+
+    #[test]
+    fn test_sleep_aware_source_reports_wake_from_sleep_once() {
+        let clocks = Arc::new(Mutex::new((Duration::from_secs(100), 1_000)));
+        let source = SleepAwareSource::with_clock(
+            Box::new(ScriptedIdleSource::new([IdleState::Locked])),
+            Box::new(FakeClock(clocks.clone())),
+        );
+        assert_eq!(source.state(), Some(IdleState::Locked));
+
+        // Ten seconds awake, an hour on the wall clock
+        *clocks.lock().unwrap() = (Duration::from_secs(110), 4_610);
+        assert_eq!(source.state(), Some(IdleState::Suspended));
+
+        *clocks.lock().unwrap() = (Duration::from_secs(1_010), 5_510);
+        assert_eq!(source.state(), Some(IdleState::Locked));
+    }
+
+    #[test]
+    fn test_no_idle_source_is_always_active() {
+        assert_eq!(NoIdleSource.state(), Some(IdleState::Active));
+    }
+
+    #[test]
+    fn test_parse_gdbus_value() {
+        assert_eq!(parse_gdbus_value::<u64>("(uint32 1500,)\n"), Some(1500));
+        assert_eq!(
+            parse_gdbus_value::<i64>("(<uint64 1700000000000000>,)"),
+            Some(1_700_000_000_000_000)
+        );
+        assert_eq!(parse_gdbus_value::<bool>("(<true>,)"), Some(true));
+        assert_eq!(parse_gdbus_value::<bool>("(false,)"), Some(false));
+        assert_eq!(parse_gdbus_value::<u64>("Error: no such method"), None);
+    }
+
+    #[test]
+    fn test_candidates_by_session() {
+        assert_eq!(candidates(true, false, false), vec![IdleBackend::MacOs]);
+        assert_eq!(
+            candidates(false, false, true),
+            vec![IdleBackend::X11, IdleBackend::ScreenSaver, IdleBackend::Logind]
+        );
+        assert_eq!(
+            candidates(false, true, true),
+            vec![IdleBackend::ScreenSaver, IdleBackend::Logind, IdleBackend::X11]
+        );
+        assert_eq!(
+            candidates(false, false, false),
+            vec![IdleBackend::ScreenSaver, IdleBackend::Logind]
+        );
+    }
+
+    /// Answers with whatever the shared slot holds, `None` meaning unavailable
+    struct SwitchableSource(Arc<Mutex<Option<IdleState>>>, IdleBackend);
+
+    impl IdleSource for SwitchableSource {
+        fn backend(&self) -> IdleBackend {
+            self.1
+        }
+
+        fn state(&self) -> Option<IdleState> {
+            *self.0.lock().unwrap()
+        }
+    }
+
+    fn switchable(backend: IdleBackend) -> (Box<dyn IdleSource>, Arc<Mutex<Option<IdleState>>>) {
+        let answer = Arc::new(Mutex::new(None));
+        (Box::new(SwitchableSource(answer.clone(), backend)), answer)
+    }
+
+    #[test]
+    fn test_auto_source_falls_through_to_next_backend() {
+        let (screensaver, screensaver_answer) = switchable(IdleBackend::ScreenSaver);
+        let (logind, logind_answer) = switchable(IdleBackend::Logind);
+        *logind_answer.lock().unwrap() = Some(IdleState::Locked);
+        let source = AutoIdleSource::new(vec![screensaver, logind], Duration::ZERO);
+        assert_eq!(source.state(), Some(IdleState::Locked));
+
+        // The chosen backend going away triggers a new probe straight away
+        *logind_answer.lock().unwrap() = None;
+        *screensaver_answer.lock().unwrap() = Some(IdleState::Active);
+        assert_eq!(source.state(), Some(IdleState::Active));
+    }
+
+    #[test]
+    fn test_auto_source_reprobes_after_interval() {
+        let (screensaver, answer) = switchable(IdleBackend::ScreenSaver);
+        let source = AutoIdleSource::new(vec![screensaver], Duration::from_secs(3600));
+        assert_eq!(source.state(), Some(IdleState::Active));
+
+        // Not asked again before the interval is up
+        *answer.lock().unwrap() = Some(IdleState::Locked);
+        assert_eq!(source.state(), Some(IdleState::Active));
+
+        source.probe.lock().unwrap().retry_at = Some(Instant::now());
+        assert_eq!(source.state(), Some(IdleState::Locked));
+    }
+
+    #[tokio::test]
+    async fn test_query_runs_off_the_async_thread() {
+        let source: Arc<dyn IdleSource> = Arc::new(ScriptedIdleSource::new([IdleState::Locked]));
+        assert_eq!(query(source).await, Some(IdleState::Locked));
+    }
+
+    #[tokio::test]
+    async fn test_query_gives_up_on_a_hung_backend() {
+        struct Hung;
+        impl IdleSource for Hung {
+            fn backend(&self) -> IdleBackend {
+                IdleBackend::Logind
+            }
+
+            fn state(&self) -> Option<IdleState> {
+                std::thread::sleep(Duration::from_millis(500));
+                Some(IdleState::Active)
+            }
+        }
+        let answer = query_within(Arc::new(Hung), Duration::from_millis(20)).await;
+        assert_eq!(answer, None);
+    }
+
+    #[test]
+    fn test_run_kills_a_hung_helper() {
+        let started = Instant::now();
+        assert_eq!(run("sleep", &["30"]), None);
+        assert!(started.elapsed() < Duration::from_secs(10));
+        assert_eq!(run("echo", &["hi"]).as_deref(), Some("hi\n"));
+    }
+
+    #[test]
+    fn test_source_for_explicit_backend() {
+        assert_eq!(source_for(IdleBackend::Logind).backend(), IdleBackend::Logind);
+        assert_eq!(source_for(IdleBackend::None).backend(), IdleBackend::None);
+        assert_eq!(source_for(IdleBackend::Auto).backend(), IdleBackend::Auto);
+    }
 }
@@ -1,4 +1,15 @@
+pub mod backup;
+pub mod clock;
+pub mod gaps;
 pub mod idle_detector;
+pub mod schedule;
+pub mod settings;
+pub mod tauri_timer;
 pub mod timer;
+pub mod trash;
 
-pub use timer::TimerCommand;
+pub use clock::{Clock, SystemClock};
+pub use settings::SettingsService;
+pub use timer::{
+    Scheduler, TimerCommand, TimerSink, TimerState, TimerStateStore, MAX_SNOOZE_MINUTES,
+};
@@ -0,0 +1,117 @@
+use crate::db::WorkSchedule;
+use chrono::{DateTime, Datelike, NaiveDateTime, TimeZone, Timelike};
+
+/// Whether the wall-clock time `at` is working time. Days off never are.
+/// Without any working blocks there is no schedule and every other time is.
+pub fn is_working_time(schedule: &WorkSchedule, at: NaiveDateTime) -> bool {
+    let date = at.date().format("%Y-%m-%d").to_string();
+    if schedule.days_off.iter().any(|day| day.date == date) {
+        return false;
+    }
+    if schedule.blocks.is_empty() {
+        return true;
+    }
+
+    let weekday = at.weekday().num_days_from_monday();
+    let minute = at.hour() * 60 + at.minute();
+    schedule
+        .blocks
+        .iter()
+        .any(|b| b.weekday == weekday && b.start_minute <= minute && minute < b.end_minute)
+}
+
+/// Whether the interval starting at unix `timestamp` should be tracked, judged
+/// by the wall-clock time in `tz` at which it starts
+pub fn is_tracked_interval<Tz: TimeZone>(
+    schedule: &WorkSchedule,
+    timestamp: i64,
+    tz: &Tz,
+) -> bool {
+    match DateTime::from_timestamp(timestamp, 0) {
+        Some(utc) => is_working_time(schedule, utc.with_timezone(tz).naive_local()),
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{DayOff, WorkBlock};
+    use chrono::NaiveDate;
+    use chrono_tz::Europe::Berlin;
+
+    fn block(weekday: u32, start_minute: u32, end_minute: u32) -> WorkBlock {
+        WorkBlock {
+            weekday,
+            start_minute,
+            end_minute,
+        }
+    }
+
+    /// Monday to Friday, 09:00-12:00 and 13:00-17:30
+    fn office_hours() -> WorkSchedule {
+        WorkSchedule {
+            blocks: (0..5)
+                .flat_map(|day| [block(day, 540, 720), block(day, 780, 1050)])
+                .collect(),
+            days_off: vec![DayOff {
+                date: "2024-05-01".to_string(),
+                label: Some("Labour Day".to_string()),
+            }],
+        }
+    }
+
+    fn at(date: &str, hour: u32, minute: u32) -> NaiveDateTime {
+        NaiveDate::parse_from_str(date, "%Y-%m-%d")
+            .unwrap()
+            .and_hms_opt(hour, minute, 0)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_block_boundaries() {
+        let schedule = office_hours();
+        // 2024-05-06 is a Monday
+        assert!(!is_working_time(&schedule, at("2024-05-06", 8, 45)));
+        assert!(is_working_time(&schedule, at("2024-05-06", 9, 0)));
+        assert!(is_working_time(&schedule, at("2024-05-06", 11, 45)));
+        assert!(!is_working_time(&schedule, at("2024-05-06", 12, 0)));
+        assert!(!is_working_time(&schedule, at("2024-05-06", 12, 30)));
+        assert!(is_working_time(&schedule, at("2024-05-06", 13, 0)));
+        assert!(is_working_time(&schedule, at("2024-05-06", 17, 15)));
+        assert!(!is_working_time(&schedule, at("2024-05-06", 17, 30)));
+        assert!(!is_working_time(&schedule, at("2024-05-06", 23, 0)));
+    }
+
+    #[test]
+    fn test_weekend_and_days_off() {
+        let schedule = office_hours();
+        assert!(!is_working_time(&schedule, at("2024-05-04", 10, 0)));
+        assert!(!is_working_time(&schedule, at("2024-05-05", 10, 0)));
+        // 2024-05-01 is a Wednesday, listed as a day off
+        assert!(!is_working_time(&schedule, at("2024-05-01", 10, 0)));
+        assert!(is_working_time(&schedule, at("2024-05-02", 10, 0)));
+    }
+
+    #[test]
+    fn test_without_blocks_every_day_but_days_off_counts() {
+        let schedule = WorkSchedule {
+            blocks: Vec::new(),
+            ..office_hours()
+        };
+        assert!(is_working_time(&schedule, at("2024-05-04", 3, 0)));
+        assert!(!is_working_time(&schedule, at("2024-05-01", 10, 0)));
+        assert!(is_working_time(&WorkSchedule::default(), at("2024-05-01", 10, 0)));
+    }
+
+    #[test]
+    fn test_tracked_interval_uses_local_wall_clock() {
+        let schedule = office_hours();
+        // 2024-05-06 07:00 UTC is 09:00 in Berlin (CEST)
+        assert!(is_tracked_interval(&schedule, 1714978800, &Berlin));
+        assert!(!is_tracked_interval(&schedule, 1714978800, &chrono::Utc));
+        // 2024-01-08 08:00 UTC is 09:00 in Berlin (CET)
+        assert!(is_tracked_interval(&schedule, 1704700800, &Berlin));
+        assert!(!is_tracked_interval(&schedule, 1704697200, &Berlin));
+    }
+}
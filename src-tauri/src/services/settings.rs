@@ -0,0 +1,177 @@
+use crate::db::repositories::{RepositoryError, Result};
+use crate::db::{Database, SettingChange, Settings, SettingsRepository};
+use crate::services::TimerCommand;
+use tokio::sync::{broadcast, mpsc};
+
+/// Single entry point for settings writes. Values are validated before they
+/// are stored, and every change is published to subscribers such as the timer.
+pub struct SettingsService {
+    changes: broadcast::Sender<SettingChange>,
+}
+
+impl Default for SettingsService {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SettingsService {
+    pub fn new() -> Self {
+        let (changes, _) = broadcast::channel(16);
+        Self { changes }
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<SettingChange> {
+        self.changes.subscribe()
+    }
+
+    /// Validates and stores a setting. Subscribers are only notified when the
+    /// stored value actually changes; the returned change is always the
+    /// normalized value.
+    pub fn set(&self, db: &Database, key: &str, value: &str) -> Result<SettingChange> {
+        let (previous, change) = {
+            let repo = SettingsRepository::new(lock(db)?);
+            let previous = repo.get(key)?;
+            (previous, repo.set(key, value)?)
+        };
+
+        if previous.as_deref() != Some(change.value().as_str()) {
+            self.publish(change);
+        }
+        Ok(change)
+    }
+
+    /// Validates and stores every setting at once; nothing is written if any
+    /// value is invalid. Returns the settings that changed.
+    pub fn update(&self, db: &Database, settings: &Settings) -> Result<Vec<SettingChange>> {
+        let changed = SettingsRepository::new(lock(db)?).save(settings)?;
+        for change in &changed {
+            self.publish(*change);
+        }
+        Ok(changed)
+    }
+
+    fn publish(&self, change: SettingChange) {
+        // Nobody listening is fine
+        let _ = self.changes.send(change);
+    }
+}
+
+fn lock(db: &Database) -> Result<std::sync::MutexGuard<'_, rusqlite::Connection>> {
+    db.conn
+        .lock()
+        .map_err(|e| RepositoryError::LockError(e.to_string()))
+}
+
+/// Relays interval changes to the background timer until either side goes away
+pub async fn forward_to_timer(
+    mut changes: broadcast::Receiver<SettingChange>,
+    timer: mpsc::Sender<TimerCommand>,
+) {
+    loop {
+        match changes.recv().await {
+            Ok(SettingChange::IntervalMinutes(minutes)) => {
+                if timer.send(TimerCommand::UpdateInterval(minutes)).await.is_err() {
+                    break;
+                }
+            }
+            Ok(_) | Err(broadcast::error::RecvError::Lagged(_)) => {}
+            Err(broadcast::error::RecvError::Closed) => break,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::migrations;
+
+    fn setup_db() -> Database {
+        let db = Database::new_in_memory().unwrap();
+        {
+            let conn = db.conn.lock().unwrap();
+            migrations::run_migrations(&conn).unwrap();
+        }
+        db
+    }
+
+    fn stored(db: &Database, key: &str) -> Option<String> {
+        let conn = db.conn.lock().unwrap();
+        SettingsRepository::new(conn).get(key).unwrap()
+    }
+
+    #[test]
+    fn test_set_stores_and_publishes() {
+        let db = setup_db();
+        let service = SettingsService::new();
+        let mut rx = service.subscribe();
+
+        service.set(&db, "interval_minutes", "30").unwrap();
+
+        assert_eq!(stored(&db, "interval_minutes").as_deref(), Some("30"));
+        assert_eq!(rx.try_recv().unwrap(), SettingChange::IntervalMinutes(30));
+    }
+
+    #[test]
+    fn test_set_skips_publishing_unchanged_value() {
+        let db = setup_db();
+        let service = SettingsService::new();
+        let mut rx = service.subscribe();
+
+        service.set(&db, "notification_sound", "default").unwrap();
+
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_set_rejects_invalid_value_without_writing() {
+        let db = setup_db();
+        let service = SettingsService::new();
+        let mut rx = service.subscribe();
+
+        assert!(matches!(
+            service.set(&db, "interval_minutes", "0"),
+            Err(RepositoryError::InvalidData(_))
+        ));
+        assert_eq!(stored(&db, "interval_minutes").as_deref(), Some("15"));
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_update_publishes_each_change() {
+        let db = setup_db();
+        let service = SettingsService::new();
+        let mut rx = service.subscribe();
+        let settings = Settings {
+            idle_threshold_minutes: 10,
+            notification_enabled: false,
+            ..Settings::default()
+        };
+
+        let changed = service.update(&db, &settings).unwrap();
+
+        assert_eq!(changed.len(), 2);
+        assert_eq!(rx.try_recv().unwrap(), SettingChange::IdleThresholdMinutes(10));
+        assert_eq!(rx.try_recv().unwrap(), SettingChange::NotificationEnabled(false));
+        assert!(rx.try_recv().is_err());
+        assert_eq!(stored(&db, "notification_enabled").as_deref(), Some("false"));
+    }
+
+    #[tokio::test]
+    async fn test_forward_to_timer_sends_interval_updates() {
+        let db = setup_db();
+        let service = SettingsService::new();
+        let (tx, mut rx) = mpsc::channel(4);
+        let forwarder = tokio::spawn(forward_to_timer(service.subscribe(), tx));
+
+        service.set(&db, "notification_enabled", "false").unwrap();
+        service.set(&db, "interval_minutes", "5").unwrap();
+
+        match rx.recv().await {
+            Some(TimerCommand::UpdateInterval(minutes)) => assert_eq!(minutes, 5),
+            _ => panic!("expected UpdateInterval"),
+        }
+        drop(service);
+        forwarder.await.unwrap();
+    }
+}
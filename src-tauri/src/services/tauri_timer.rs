@@ -0,0 +1,109 @@
+use crate::db::{AwayReason, Database, NotificationSound};
+use crate::services::clock::Clock;
+use crate::services::gaps::GapReason;
+use crate::services::timer::{Scheduler, TimerCommand, TimerSink, TimerState, TimerStateStore};
+use chrono::Local;
+use std::sync::Arc;
+use tauri::{AppHandle, Emitter, Manager};
+use tauri_plugin_notification::NotificationExt;
+use tokio::sync::mpsc;
+
+/// Runs the timer for the app, reporting to the window and system notifications
+pub async fn start_timer(
+    app_handle: AppHandle,
+    clock: Arc<dyn Clock>,
+    initial_interval_minutes: u64,
+    rx: mpsc::Receiver<TimerCommand>,
+) {
+    let db = app_handle.state::<Database>();
+    let sink = TauriSink {
+        app_handle: app_handle.clone(),
+    };
+    Scheduler::new(&db, sink)
+        .run(clock.as_ref(), initial_interval_minutes, rx)
+        .await;
+}
+
+/// Forwards timer events to the webview as Tauri events
+pub struct TauriSink {
+    app_handle: AppHandle,
+}
+
+impl TimerSink for TauriSink {
+    fn state_changed(&mut self, state: TimerState) {
+        if let Some(store) = self.app_handle.try_state::<TimerStateStore>() {
+            store.set(state);
+        }
+        let _ = self.app_handle.emit("timer-state-changed", state);
+    }
+
+    fn prompt(&mut self, timestamp: i64, _interval_minutes: u64) {
+        let _ = self.app_handle.emit("prompt-time-entry", serde_json::json!({
+            "timestamp": timestamp,
+        }));
+
+        // Show the window
+        if let Some(window) = self.app_handle.get_webview_window("main") {
+            let _ = window.show();
+            let _ = window.set_focus();
+        }
+    }
+
+    fn notify(&mut self, timestamp: i64, interval_minutes: u64, sound: NotificationSound) {
+        let interval_start = chrono::DateTime::from_timestamp(timestamp, 0)
+            .map(|dt| dt.with_timezone(&Local).format("%-I:%M %p").to_string())
+            .unwrap_or_default();
+        let interval_end = chrono::DateTime::from_timestamp(timestamp + (interval_minutes as i64 * 60), 0)
+            .map(|dt| dt.with_timezone(&Local).format("%-I:%M %p").to_string())
+            .unwrap_or_default();
+        let notification_body = format!("What did you work on {} - {}?", interval_start, interval_end);
+
+        let _ = self
+            .app_handle
+            .notification()
+            .builder()
+            .title("Time Tracker")
+            .body(&notification_body)
+            .show();
+
+        // Play sound using afplay on macOS (notify_rust sound support is limited)
+        #[cfg(target_os = "macos")]
+        {
+            let sound_name = if sound == NotificationSound::Default {
+                "Ping".to_string()
+            } else {
+                // Capitalize first letter to match macOS sound file names
+                let name = sound.as_str();
+                name[..1].to_uppercase() + &name[1..]
+            };
+            let sound_path = format!("/System/Library/Sounds/{}.aiff", sound_name);
+            let _ = std::process::Command::new("afplay")
+                .arg(&sound_path)
+                .spawn();
+        }
+        #[cfg(not(target_os = "macos"))]
+        let _ = sound;
+    }
+
+    fn away_recorded(&mut self, timestamp: i64, reason: AwayReason) {
+        let _ = self.app_handle.emit("away-recorded", serde_json::json!({
+            "timestamp": timestamp,
+            "reason": reason,
+        }));
+    }
+
+    fn returned_from_away(&mut self, away_start: i64, away_end: i64) {
+        // Emit event to prompt for missed time
+        let _ = self.app_handle.emit("return-from-away", serde_json::json!({
+            "away_start": away_start,
+            "away_end": away_end,
+        }));
+    }
+
+    fn missed_prompts_added(&mut self, count: usize, reason: GapReason) {
+        let _ = self.app_handle.emit("missed-prompts-added", serde_json::json!({
+            "count": count,
+            "reason": reason.as_str(),
+        }));
+    }
+}
@@ -1,135 +1,457 @@
-use crate::db::{Database, SettingsRepository, TimeEntryRepository};
-use crate::services::idle_detector;
-use chrono::{Local, Timelike};
-use tauri::{AppHandle, Emitter, Manager};
-use tauri_plugin_notification::NotificationExt;
+use crate::db::{
+    AwayReason, Database, IdleBackend, NotificationSound, ScheduleRepository, SettingsRepository,
+    TimeEntryRepository,
+};
+use crate::services::clock::Clock;
+use crate::services::gaps::{self, GapReason};
+use crate::services::idle_detector::{self, IdleSource, IdleState};
+use crate::services::schedule;
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use std::sync::{Arc, Mutex};
 use tokio::sync::mpsc;
-use tokio::time::{interval_at, Duration as TokioDuration, Instant};
+use tokio::time::{interval_at, sleep_until, Duration as TokioDuration, Instant};
+
+/// Longest snooze accepted; longer breaks are what pausing is for
+pub const MAX_SNOOZE_MINUTES: u64 = 24 * 60;
 
 pub enum TimerCommand {
     UpdateInterval(u64),
+    /// Stop prompting until the given unix timestamp, or until resumed
+    Pause { until: Option<i64> },
+    Resume,
+    /// Stop prompting for the given number of minutes, at most
+    /// `MAX_SNOOZE_MINUTES`
+    Snooze(u64),
     Stop,
 }
 
-pub async fn start_timer(
-    app_handle: AppHandle,
-    initial_interval_minutes: u64,
-    mut rx: mpsc::Receiver<TimerCommand>,
-) {
-    let mut interval_minutes = initial_interval_minutes;
+/// Whether the timer is prompting, as reported by `timer-state-changed` events
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "state", rename_all = "lowercase")]
+pub enum TimerState {
+    #[default]
+    Running,
+    Paused { until: Option<i64> },
+    Snoozed { until: i64 },
+}
 
-    // Create timer aligned to clock boundaries using interval_at
-    let seconds_until_next = seconds_until_next_boundary(interval_minutes);
-    let start = Instant::now() + TokioDuration::from_secs(seconds_until_next);
-    let mut timer = interval_at(start, TokioDuration::from_secs(interval_minutes * 60));
+impl TimerState {
+    pub fn is_running(&self) -> bool {
+        *self == TimerState::Running
+    }
 
-    let mut idle_start: Option<i64> = None;
+    /// When a pause or snooze ends on its own
+    pub fn resumes_at(&self) -> Option<i64> {
+        match *self {
+            TimerState::Running => None,
+            TimerState::Paused { until } => until,
+            TimerState::Snoozed { until } => Some(until),
+        }
+    }
 
-    loop {
-        tokio::select! {
-            _ = timer.tick() => {
-                let db = app_handle.state::<Database>();
-
-                // Get settings using repository
-                let (idle_threshold, notification_enabled, notification_sound) = {
-                    let conn = db.conn.lock().unwrap();
-                    let settings_repo = SettingsRepository::new(conn);
-                    (
-                        settings_repo.get_idle_threshold_minutes(),
-                        settings_repo.is_notification_enabled(),
-                        settings_repo.get_notification_sound(),
-                    )
-                };
-
-                let is_idle = idle_detector::is_user_idle(idle_threshold);
-                let now = Local::now().timestamp();
-                // Prompt at END of interval: use previous interval's timestamp
-                let aligned_timestamp = align_timestamp(now, interval_minutes as i64) - (interval_minutes as i64 * 60);
-
-                if is_idle {
-                    if idle_start.is_none() {
-                        idle_start = Some(aligned_timestamp);
-                    }
-                    // Auto-create away entry using repository
-                    let conn = db.conn.lock().unwrap();
-                    let time_entry_repo = TimeEntryRepository::new(conn);
-                    let _ = time_entry_repo.create_away_entry(aligned_timestamp, interval_minutes as i32);
-                } else {
-                    // User is active
-                    if let Some(away_start) = idle_start {
-                        // User returned from being away
-                        idle_start = None;
-
-                        // Emit event to prompt for missed time
-                        let _ = app_handle.emit("return-from-away", serde_json::json!({
-                            "away_start": away_start,
-                            "away_end": aligned_timestamp,
-                        }));
-                    }
+    /// State after a pause, resume or snooze issued at `now`. Other commands
+    /// leave the state as it is.
+    pub fn apply(self, cmd: &TimerCommand, now: i64) -> Self {
+        match *cmd {
+            TimerCommand::Pause { until } => TimerState::Paused { until },
+            TimerCommand::Resume => TimerState::Running,
+            TimerCommand::Snooze(minutes) => TimerState::Snoozed {
+                until: now.saturating_add(minutes.min(MAX_SNOOZE_MINUTES) as i64 * 60),
+            },
+            TimerCommand::UpdateInterval(_) | TimerCommand::Stop => self,
+        }
+    }
 
-                    // Send notification if enabled
-                    if notification_enabled {
-                        let interval_start = chrono::DateTime::from_timestamp(aligned_timestamp, 0)
-                            .map(|dt| dt.with_timezone(&Local).format("%-I:%M %p").to_string())
-                            .unwrap_or_default();
-                        let interval_end = chrono::DateTime::from_timestamp(aligned_timestamp + (interval_minutes as i64 * 60), 0)
-                            .map(|dt| dt.with_timezone(&Local).format("%-I:%M %p").to_string())
-                            .unwrap_or_default();
-                        let notification_body = format!("What did you work on {} - {}?", interval_start, interval_end);
-
-                        let _ = app_handle
-                            .notification()
-                            .builder()
-                            .title("Time Tracker")
-                            .body(&notification_body)
-                            .show();
-
-                        // Play sound using afplay on macOS (notify_rust sound support is limited)
-                        #[cfg(target_os = "macos")]
-                        {
-                            let sound_name = if notification_sound == "default" {
-                                "Ping".to_string()
-                            } else {
-                                // Capitalize first letter to match macOS sound file names
-                                let mut chars = notification_sound.chars();
-                                match chars.next() {
-                                    Some(c) => c.to_uppercase().collect::<String>() + chars.as_str(),
-                                    None => notification_sound.clone(),
-                                }
-                            };
-                            let sound_path = format!("/System/Library/Sounds/{}.aiff", sound_name);
-                            let _ = std::process::Command::new("afplay")
-                                .arg(&sound_path)
-                                .spawn();
-                        }
-                    }
+    /// Drops a pause or snooze whose end has passed
+    pub fn expire(self, now: i64) -> Self {
+        match self.resumes_at() {
+            Some(until) if until <= now => TimerState::Running,
+            _ => self,
+        }
+    }
+}
 
-                    // Emit prompt event
-                    let _ = app_handle.emit("prompt-time-entry", serde_json::json!({
-                        "timestamp": aligned_timestamp,
-                    }));
+/// Latest timer state, managed by the app so commands can read it
+#[derive(Default)]
+pub struct TimerStateStore(Mutex<TimerState>);
 
-                    // Show the window
-                    if let Some(window) = app_handle.get_webview_window("main") {
-                        let _ = window.show();
-                        let _ = window.set_focus();
-                    }
+impl TimerStateStore {
+    pub fn get(&self) -> TimerState {
+        *self.0.lock().unwrap()
+    }
+
+    pub(crate) fn set(&self, state: TimerState) {
+        *self.0.lock().unwrap() = state;
+    }
+}
+
+/// What the outside world reports about an interval when it ends
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TickContext {
+    /// Whether the interval falls inside working hours
+    pub tracked: bool,
+    /// `None` when idle detection isn't available
+    pub idle: Option<IdleState>,
+    pub idle_threshold_minutes: u32,
+}
+
+/// Side effects the timer asks for, carried out by whoever runs the loop
+#[derive(Debug, Clone, PartialEq)]
+pub enum TimerAction {
+    StateChanged(TimerState),
+    /// Record missed prompts for intervals that passed without a tick
+    Backfill {
+        until: i64,
+        interval_minutes: u64,
+        reason: GapReason,
+    },
+    RecordPaused {
+        timestamp: i64,
+        duration_minutes: i32,
+    },
+    RecordAway {
+        timestamp: i64,
+        duration_minutes: i32,
+        reason: AwayReason,
+    },
+    ReturnFromAway {
+        away_start: i64,
+        away_end: i64,
+    },
+    Prompt {
+        timestamp: i64,
+        interval_minutes: u64,
+    },
+}
+
+/// The timer's decisions, free of clocks, channels and I/O
+#[derive(Debug, Default)]
+pub struct TimerMachine {
+    state: TimerState,
+    /// Start of the current run of away intervals
+    idle_start: Option<i64>,
+}
+
+impl TimerMachine {
+    pub fn state(&self) -> TimerState {
+        self.state
+    }
+
+    /// Handles the end of an interval at `now`
+    pub fn on_tick(&mut self, now: i64, interval_minutes: u64, context: &TickContext) -> Vec<TimerAction> {
+        let mut actions = Vec::new();
+
+        let expired = self.state.expire(now);
+        if expired != self.state {
+            self.state = expired;
+            actions.push(TimerAction::StateChanged(expired));
+        }
+
+        let timestamp = interval_start(now, interval_minutes);
+        let duration_minutes = interval_minutes as i32;
+
+        // Intervals skipped since the last tick, e.g. while the machine slept
+        actions.push(TimerAction::Backfill {
+            until: timestamp,
+            interval_minutes,
+            reason: GapReason::Sleep,
+        });
+
+        // Outside working hours: no prompt and nothing recorded
+        if !context.tracked {
+            self.idle_start = None;
+            return actions;
+        }
+
+        if !self.state.is_running() {
+            // Record the interval instead of silently skipping it
+            actions.push(TimerAction::RecordPaused {
+                timestamp,
+                duration_minutes,
+            });
+            return actions;
+        }
+
+        let away = context
+            .idle
+            .and_then(|idle| idle_detector::away_reason(idle, context.idle_threshold_minutes));
+        match away {
+            Some(reason) => {
+                self.idle_start.get_or_insert(timestamp);
+                actions.push(TimerAction::RecordAway {
+                    timestamp,
+                    duration_minutes,
+                    reason,
+                });
+            }
+            None => {
+                if let Some(away_start) = self.idle_start.take() {
+                    actions.push(TimerAction::ReturnFromAway {
+                        away_start,
+                        away_end: timestamp,
+                    });
                 }
+                actions.push(TimerAction::Prompt {
+                    timestamp,
+                    interval_minutes,
+                });
+            }
+        }
+        actions
+    }
+
+    /// Handles a pause, resume or snooze issued at `now`
+    pub fn on_command(&mut self, cmd: &TimerCommand, now: i64) -> Vec<TimerAction> {
+        let next = self.state.apply(cmd, now);
+        if next == self.state {
+            return Vec::new();
+        }
+        // Time spent paused is not away time
+        self.idle_start = None;
+        self.state = next;
+        vec![TimerAction::StateChanged(next)]
+    }
+
+    /// Handles a timed pause or snooze running out
+    pub fn on_resume_due(&mut self) -> Vec<TimerAction> {
+        if self.state.is_running() {
+            return Vec::new();
+        }
+        self.state = TimerState::Running;
+        vec![TimerAction::StateChanged(TimerState::Running)]
+    }
+}
+
+/// Where the timer reports what it did. The app forwards these to the window
+/// and system notifications; a headless runner can log them instead.
+pub trait TimerSink {
+    fn state_changed(&mut self, state: TimerState);
+
+    /// Ask the user what they did during the interval starting at `timestamp`
+    fn prompt(&mut self, timestamp: i64, interval_minutes: u64);
+
+    /// Show a system notification for the prompt; only called when enabled
+    fn notify(&mut self, timestamp: i64, interval_minutes: u64, sound: NotificationSound);
+
+    fn away_recorded(&mut self, timestamp: i64, reason: AwayReason);
+
+    fn returned_from_away(&mut self, away_start: i64, away_end: i64);
+
+    fn missed_prompts_added(&mut self, count: usize, reason: GapReason);
+}
+
+/// The timer's database side: reads the schedule and settings for each tick,
+/// records paused, away and missed intervals, and tells a `TimerSink` about
+/// everything the user should see
+pub struct Scheduler<'a, S: TimerSink> {
+    db: &'a Database,
+    sink: S,
+    idle: IdleSlot,
+}
+
+/// The idle source in use, re-resolved when the idle_backend setting changes
+/// unless one was supplied up front
+#[derive(Default)]
+struct IdleSlot {
+    resolved: Mutex<Option<(IdleBackend, Arc<dyn IdleSource>)>>,
+    pinned: Option<Arc<dyn IdleSource>>,
+}
+
+impl IdleSlot {
+    fn source(&self, backend: IdleBackend) -> Arc<dyn IdleSource> {
+        if let Some(source) = &self.pinned {
+            return source.clone();
+        }
+        let mut resolved = self.resolved.lock().unwrap();
+        match &*resolved {
+            Some((current, source)) if *current == backend => source.clone(),
+            _ => {
+                let source: Arc<dyn IdleSource> = Arc::from(idle_detector::source_for(backend));
+                *resolved = Some((backend, source.clone()));
+                source
+            }
+        }
+    }
+
+    async fn state(&self, backend: IdleBackend) -> Option<IdleState> {
+        idle_detector::query(self.source(backend)).await
+    }
+}
+
+impl<'a, S: TimerSink> Scheduler<'a, S> {
+    pub fn new(db: &'a Database, sink: S) -> Self {
+        Self {
+            db,
+            sink,
+            idle: IdleSlot::default(),
+        }
+    }
+
+    /// Uses `source` for idle detection instead of the configured backend
+    pub fn with_idle_source(mut self, source: Box<dyn IdleSource>) -> Self {
+        self.idle = IdleSlot {
+            resolved: Mutex::new(None),
+            pinned: Some(Arc::from(source)),
+        };
+        self
+    }
+
+    /// Runs until a `Stop` command arrives, returning the sink
+    pub async fn run(
+        mut self,
+        clock: &dyn Clock,
+        initial_interval_minutes: u64,
+        rx: mpsc::Receiver<TimerCommand>,
+    ) -> S {
+        let db = self.db;
+        let idle = &self.idle;
+        let sink = &mut self.sink;
+        run_timer(
+            clock,
+            initial_interval_minutes,
+            rx,
+            |timestamp| observe(db, idle, timestamp),
+            |action| perform(db, sink, clock.now(), action),
+        )
+        .await;
+        self.sink
+    }
+}
+
+async fn observe(db: &Database, idle: &IdleSlot, timestamp: i64) -> TickContext {
+    let work_schedule = {
+        let conn = db.conn.lock().unwrap();
+        ScheduleRepository::new(conn).load().unwrap_or_default()
+    };
+    let (idle_threshold_minutes, idle_backend) = {
+        let conn = db.conn.lock().unwrap();
+        let settings_repo = SettingsRepository::new(conn);
+        (settings_repo.get_idle_threshold_minutes(), settings_repo.get_idle_backend())
+    };
+
+    TickContext {
+        tracked: schedule::is_tracked_interval(&work_schedule, timestamp, &Local),
+        idle: idle.state(idle_backend).await,
+        idle_threshold_minutes,
+    }
+}
+
+fn perform<S: TimerSink>(db: &Database, sink: &mut S, now: i64, action: TimerAction) {
+    match action {
+        TimerAction::StateChanged(state) => sink.state_changed(state),
+        TimerAction::Backfill {
+            until,
+            interval_minutes,
+            reason,
+        } => match gaps::backfill(db, until, interval_minutes as i64, reason, &Local) {
+            Ok(0) => {}
+            Ok(added) => sink.missed_prompts_added(added, reason),
+            Err(e) => log::warn!("Failed to backfill missed prompts: {}", e),
+        },
+        TimerAction::RecordPaused {
+            timestamp,
+            duration_minutes,
+        } => {
+            let conn = db.conn.lock().unwrap();
+            let _ = TimeEntryRepository::new(conn)
+                .at(now)
+                .create_paused_entry(timestamp, duration_minutes);
+        }
+        TimerAction::RecordAway {
+            timestamp,
+            duration_minutes,
+            reason,
+        } => {
+            let recorded = {
+                let conn = db.conn.lock().unwrap();
+                TimeEntryRepository::new(conn)
+                    .at(now)
+                    .create_away_entry(timestamp, duration_minutes, reason)
+            };
+            if recorded.is_ok() {
+                sink.away_recorded(timestamp, reason);
+            }
+        }
+        TimerAction::ReturnFromAway { away_start, away_end } => {
+            sink.returned_from_away(away_start, away_end)
+        }
+        TimerAction::Prompt {
+            timestamp,
+            interval_minutes,
+        } => {
+            let settings = {
+                let conn = db.conn.lock().unwrap();
+                SettingsRepository::new(conn).load_or_default()
+            };
+            if settings.notification_enabled {
+                sink.notify(timestamp, interval_minutes, settings.notification_sound);
+            }
+            sink.prompt(timestamp, interval_minutes);
+        }
+    }
+}
+
+/// Drives a `TimerMachine` from clock-aligned ticks, timed resumes and
+/// commands until `Stop`. `observe` is asked about each interval as it ends;
+/// `perform` carries out the resulting actions.
+pub async fn run_timer<O, F, P>(
+    clock: &dyn Clock,
+    initial_interval_minutes: u64,
+    mut rx: mpsc::Receiver<TimerCommand>,
+    mut observe: O,
+    mut perform: P,
+) where
+    O: FnMut(i64) -> F,
+    F: Future<Output = TickContext>,
+    P: FnMut(TimerAction),
+{
+    let mut interval_minutes = initial_interval_minutes;
+    let mut timer = aligned_interval(clock.now(), interval_minutes);
+    let mut machine = TimerMachine::default();
+
+    // Intervals that ended while the app was closed
+    perform(TimerAction::Backfill {
+        until: clock.now(),
+        interval_minutes,
+        reason: GapReason::AppNotRunning,
+    });
+
+    loop {
+        // Wake up when a timed pause or snooze runs out so the UI hears about it
+        let resume_at = machine.state().resumes_at().map(|until| {
+            let remaining = (until - clock.now()).max(0) as u64;
+            Instant::now() + TokioDuration::from_secs(remaining)
+        });
+
+        let actions = tokio::select! {
+            _ = sleep_until(resume_at.unwrap_or_else(Instant::now)), if resume_at.is_some() => {
+                machine.on_resume_due()
+            }
+            _ = timer.tick() => {
+                let now = clock.now();
+                let context = observe(interval_start(now, interval_minutes)).await;
+                machine.on_tick(now, interval_minutes, &context)
             }
             Some(cmd) = rx.recv() => {
                 match cmd {
                     TimerCommand::UpdateInterval(new_interval) => {
                         interval_minutes = new_interval;
                         // Realign timer to new interval boundaries
-                        let seconds_until_next = seconds_until_next_boundary(interval_minutes);
-                        let start = Instant::now() + TokioDuration::from_secs(seconds_until_next);
-                        timer = interval_at(start, TokioDuration::from_secs(interval_minutes * 60));
+                        timer = aligned_interval(clock.now(), interval_minutes);
+                        Vec::new()
                     }
                     TimerCommand::Stop => {
                         break;
                     }
+                    _ => machine.on_command(&cmd, clock.now()),
                 }
             }
+        };
+
+        for action in actions {
+            perform(action);
         }
     }
 }
@@ -139,27 +461,100 @@ fn align_timestamp(timestamp: i64, interval_minutes: i64) -> i64 {
     (timestamp / interval_seconds) * interval_seconds
 }
 
-/// Calculate seconds until the next aligned interval boundary
-fn seconds_until_next_boundary(interval_minutes: u64) -> u64 {
-    let now = Local::now();
-    let minutes = now.minute();
-    let seconds = now.second();
-    let minutes_until_next =
-        (interval_minutes as u32 - (minutes % interval_minutes as u32)) % interval_minutes as u32;
+/// Start of the interval that a tick at `now` closes. Prompts come at the END
+/// of an interval, so this is the previous interval's timestamp.
+fn interval_start(now: i64, interval_minutes: u64) -> i64 {
+    align_timestamp(now, interval_minutes as i64) - (interval_minutes as i64 * 60)
+}
 
-    if minutes_until_next > 0 {
-        (minutes_until_next * 60) as u64 - seconds as u64
-    } else if seconds > 0 {
-        // At the minute mark but seconds > 0, wait until next full interval
-        (interval_minutes * 60) - seconds as u64
-    } else {
-        0
-    }
+/// A tokio interval whose first tick lands on the next boundary after `now`
+fn aligned_interval(now: i64, interval_minutes: u64) -> tokio::time::Interval {
+    let start = Instant::now() + TokioDuration::from_secs(seconds_until_next_boundary(now, interval_minutes));
+    interval_at(start, TokioDuration::from_secs(interval_minutes * 60))
+}
+
+/// Calculate seconds until the next aligned interval boundary
+fn seconds_until_next_boundary(now: i64, interval_minutes: u64) -> u64 {
+    let interval_seconds = interval_minutes as i64 * 60;
+    ((interval_seconds - now.rem_euclid(interval_seconds)) % interval_seconds) as u64
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::db::{migrations, HeartbeatRepository, WorkBlock, WorkSchedule};
+    use crate::services::clock::TokioClock;
+    use crate::services::idle_detector::ScriptedIdleSource;
+    use chrono::Utc;
+
+    const HOUR: i64 = 3600;
+    /// Monday 2024-03-04 00:00 UTC
+    const MONDAY: i64 = 1_709_510_400;
+
+    fn context(idle: IdleState) -> TickContext {
+        TickContext {
+            tracked: true,
+            idle: Some(idle),
+            idle_threshold_minutes: 15,
+        }
+    }
+
+    /// What a headless run reported, in order
+    #[derive(Debug, PartialEq)]
+    enum Event {
+        State(TimerState),
+        Prompt(i64),
+        Notify(i64, NotificationSound),
+        Away(i64, AwayReason),
+        Returned(i64, i64),
+        Missed(usize, GapReason),
+    }
+
+    #[derive(Default)]
+    struct RecordingSink(Vec<Event>);
+
+    impl TimerSink for RecordingSink {
+        fn state_changed(&mut self, state: TimerState) {
+            self.0.push(Event::State(state));
+        }
+
+        fn prompt(&mut self, timestamp: i64, _interval_minutes: u64) {
+            self.0.push(Event::Prompt(timestamp));
+        }
+
+        fn notify(&mut self, timestamp: i64, _interval_minutes: u64, sound: NotificationSound) {
+            self.0.push(Event::Notify(timestamp, sound));
+        }
+
+        fn away_recorded(&mut self, timestamp: i64, reason: AwayReason) {
+            self.0.push(Event::Away(timestamp, reason));
+        }
+
+        fn returned_from_away(&mut self, away_start: i64, away_end: i64) {
+            self.0.push(Event::Returned(away_start, away_end));
+        }
+
+        fn missed_prompts_added(&mut self, count: usize, reason: GapReason) {
+            self.0.push(Event::Missed(count, reason));
+        }
+    }
+
+    fn setup_db() -> Database {
+        let db = Database::new_in_memory().unwrap();
+        {
+            let conn = db.conn.lock().unwrap();
+            migrations::run_migrations(&conn).unwrap();
+        }
+        db
+    }
+
+    /// Everything except the per-tick backfill requests
+    fn without_backfill(actions: Vec<TimerAction>) -> Vec<TimerAction> {
+        actions
+            .into_iter()
+            .filter(|action| !matches!(action, TimerAction::Backfill { .. }))
+            .collect()
+    }
 
     #[test]
     fn test_align_timestamp_on_boundary() {
@@ -187,8 +582,327 @@ mod tests {
     }
 
     #[test]
-    fn test_seconds_until_next_boundary_range() {
-        let result = seconds_until_next_boundary(15);
-        assert!(result < 15 * 60, "Expected < 900, got {}", result);
+    fn test_timer_state_apply() {
+        let running = TimerState::Running;
+        assert_eq!(
+            running.apply(&TimerCommand::Pause { until: None }, 1000),
+            TimerState::Paused { until: None }
+        );
+        assert_eq!(
+            running.apply(&TimerCommand::Snooze(15), 1000),
+            TimerState::Snoozed { until: 1900 }
+        );
+        assert_eq!(
+            running.apply(&TimerCommand::Snooze(u64::MAX), 1000),
+            TimerState::Snoozed {
+                until: 1000 + MAX_SNOOZE_MINUTES as i64 * 60
+            }
+        );
+        assert_eq!(
+            TimerState::Paused { until: None }.apply(&TimerCommand::Resume, 1000),
+            TimerState::Running
+        );
+        assert_eq!(
+            TimerState::Snoozed { until: 1900 }.apply(&TimerCommand::UpdateInterval(30), 1000),
+            TimerState::Snoozed { until: 1900 }
+        );
+    }
+
+    #[test]
+    fn test_timer_state_expire() {
+        let paused = TimerState::Paused { until: Some(2000) };
+        assert_eq!(paused.expire(1999), paused);
+        assert_eq!(paused.expire(2000), TimerState::Running);
+        let indefinite = TimerState::Paused { until: None };
+        assert_eq!(indefinite.expire(i64::MAX), indefinite);
+        assert_eq!(TimerState::Snoozed { until: 1900 }.expire(1900), TimerState::Running);
+    }
+
+    #[test]
+    fn test_timer_state_serializes_with_tag() {
+        assert_eq!(
+            serde_json::to_value(TimerState::Paused { until: Some(2000) }).unwrap(),
+            serde_json::json!({ "state": "paused", "until": 2000 })
+        );
+        assert_eq!(
+            serde_json::to_value(TimerState::Running).unwrap(),
+            serde_json::json!({ "state": "running" })
+        );
+    }
+
+    #[test]
+    fn test_seconds_until_next_boundary() {
+        assert_eq!(seconds_until_next_boundary(900, 15), 0);
+        assert_eq!(seconds_until_next_boundary(901, 15), 899);
+        assert_eq!(seconds_until_next_boundary(1799, 15), 1);
+        assert_eq!(seconds_until_next_boundary(4000, 60), 3200);
+    }
+
+    #[test]
+    fn test_on_tick_prompts_for_previous_interval() {
+        let mut machine = TimerMachine::default();
+        let actions = machine.on_tick(10 * HOUR + 2, 15, &context(IdleState::Active));
+        assert_eq!(
+            actions,
+            vec![
+                TimerAction::Backfill {
+                    until: 10 * HOUR - 900,
+                    interval_minutes: 15,
+                    reason: GapReason::Sleep,
+                },
+                TimerAction::Prompt {
+                    timestamp: 10 * HOUR - 900,
+                    interval_minutes: 15,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_on_tick_outside_working_hours_forgets_away_run() {
+        let mut machine = TimerMachine::default();
+        machine.on_tick(HOUR, 60, &context(IdleState::Locked));
+
+        let evening = TickContext {
+            tracked: false,
+            ..context(IdleState::Active)
+        };
+        assert!(without_backfill(machine.on_tick(2 * HOUR, 60, &evening)).is_empty());
+        assert_eq!(
+            without_backfill(machine.on_tick(3 * HOUR, 60, &context(IdleState::Active))),
+            vec![TimerAction::Prompt {
+                timestamp: 2 * HOUR,
+                interval_minutes: 60,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_on_tick_expires_snooze_before_handling_interval() {
+        let mut machine = TimerMachine::default();
+        machine.on_command(&TimerCommand::Snooze(30), 0);
+
+        let actions = without_backfill(machine.on_tick(HOUR, 30, &context(IdleState::Active)));
+
+        assert_eq!(
+            actions,
+            vec![
+                TimerAction::StateChanged(TimerState::Running),
+                TimerAction::Prompt {
+                    timestamp: 1800,
+                    interval_minutes: 30,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_on_command_only_reports_real_changes() {
+        let mut machine = TimerMachine::default();
+        assert!(machine.on_command(&TimerCommand::Resume, 0).is_empty());
+        assert_eq!(
+            machine.on_command(&TimerCommand::Pause { until: None }, 0),
+            vec![TimerAction::StateChanged(TimerState::Paused { until: None })]
+        );
+        assert_eq!(
+            machine.on_resume_due(),
+            vec![TimerAction::StateChanged(TimerState::Running)]
+        );
+        assert!(machine.on_resume_due().is_empty());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_simulated_day() {
+        // Launched just after midnight; works 09:00-17:00
+        let clock = TokioClock::starting_at(MONDAY + 30);
+        let work_schedule = WorkSchedule {
+            blocks: vec![WorkBlock {
+                weekday: 0,
+                start_minute: 9 * 60,
+                end_minute: 17 * 60,
+            }],
+            days_off: Vec::new(),
+        };
+        // One state per hourly tick: out to lunch at noon, reading at 13:00,
+        // screen locked at 15:00
+        let idle = ScriptedIdleSource::new((0..24).map(|hour| match hour {
+            12 => IdleState::Idle { seconds: 3600.0 },
+            13 => IdleState::Idle { seconds: 600.0 },
+            15 => IdleState::Locked,
+            _ => IdleState::Active,
+        }));
+        let (tx, rx) = mpsc::channel(4);
+        let mut actions = Vec::new();
+
+        let observe = |timestamp: i64| {
+            std::future::ready(TickContext {
+                tracked: schedule::is_tracked_interval(&work_schedule, timestamp, &Utc),
+                idle: idle.state(),
+                idle_threshold_minutes: 15,
+            })
+        };
+        let script = async {
+            tokio::time::sleep(TokioDuration::from_secs(10 * 3600 + 1800)).await;
+            tx.send(TimerCommand::Snooze(60)).await.unwrap();
+            tokio::time::sleep(TokioDuration::from_secs(13 * 3600 + 1800)).await;
+            tx.send(TimerCommand::Stop).await.unwrap();
+        };
+        tokio::join!(
+            run_timer(&clock, 60, rx, observe, |action| actions.push(action)),
+            script
+        );
+
+        assert_eq!(
+            actions[0],
+            TimerAction::Backfill {
+                until: MONDAY + 30,
+                interval_minutes: 60,
+                reason: GapReason::AppNotRunning,
+            }
+        );
+        let sleep_backfills = actions
+            .iter()
+            .filter(|action| matches!(action, TimerAction::Backfill { reason: GapReason::Sleep, .. }))
+            .count();
+        assert_eq!(sleep_backfills, 24);
+
+        let at = |hour: i64| MONDAY + hour * HOUR;
+        let prompt = |hour: i64| TimerAction::Prompt {
+            timestamp: at(hour),
+            interval_minutes: 60,
+        };
+        assert_eq!(
+            without_backfill(actions),
+            vec![
+                prompt(9),
+                TimerAction::StateChanged(TimerState::Snoozed {
+                    until: at(11) + 1800 + 30,
+                }),
+                TimerAction::RecordPaused {
+                    timestamp: at(10),
+                    duration_minutes: 60,
+                },
+                TimerAction::StateChanged(TimerState::Running),
+                prompt(11),
+                TimerAction::RecordAway {
+                    timestamp: at(12),
+                    duration_minutes: 60,
+                    reason: AwayReason::Idle,
+                },
+                TimerAction::ReturnFromAway {
+                    away_start: at(12),
+                    away_end: at(13),
+                },
+                prompt(13),
+                prompt(14),
+                TimerAction::RecordAway {
+                    timestamp: at(15),
+                    duration_minutes: 60,
+                    reason: AwayReason::Locked,
+                },
+                TimerAction::ReturnFromAway {
+                    away_start: at(15),
+                    away_end: at(16),
+                },
+                prompt(16),
+            ]
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_interval_change_realigns_ticks() {
+        let clock = TokioClock::starting_at(MONDAY + 10 * 60);
+        let (tx, rx) = mpsc::channel(4);
+        let mut prompts = Vec::new();
+
+        let script = async {
+            tx.send(TimerCommand::UpdateInterval(30)).await.unwrap();
+            tokio::time::sleep(TokioDuration::from_secs(HOUR as u64)).await;
+            tx.send(TimerCommand::Stop).await.unwrap();
+        };
+        tokio::join!(
+            run_timer(
+                &clock,
+                15,
+                rx,
+                |_| std::future::ready(context(IdleState::Active)),
+                |action| {
+                    if let TimerAction::Prompt { timestamp, .. } = action {
+                        prompts.push(timestamp);
+                    }
+                }
+            ),
+            script
+        );
+
+        // 00:10 + 1h: ticks at 00:30 and 01:00 close 00:00 and 00:30
+        assert_eq!(prompts, vec![MONDAY, MONDAY + 1800]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_scheduler_runs_headless() {
+        let db = setup_db();
+        {
+            let conn = db.conn.lock().unwrap();
+            let settings_repo = SettingsRepository::new(conn);
+            settings_repo.set("notification_sound", "glass").unwrap();
+        }
+        {
+            // Last handled the 07:00 interval; started again at 09:00:05, so
+            // only 08:00 was missed
+            let conn = db.conn.lock().unwrap();
+            HeartbeatRepository::new(conn).record_tick(MONDAY + 7 * HOUR).unwrap();
+        }
+        let clock = TokioClock::starting_at(MONDAY + 9 * HOUR + 5);
+        let idle = ScriptedIdleSource::new([IdleState::Active, IdleState::Locked, IdleState::Active]);
+        let (tx, rx) = mpsc::channel(4);
+
+        let scheduler = Scheduler::new(&db, RecordingSink::default()).with_idle_source(Box::new(idle));
+        let script = async {
+            tokio::time::sleep(TokioDuration::from_secs(3 * HOUR as u64)).await;
+            tx.send(TimerCommand::Stop).await.unwrap();
+        };
+        let (sink, _) = tokio::join!(scheduler.run(&clock, 60, rx), script);
+
+        let at = |hour: i64| MONDAY + hour * HOUR;
+        assert_eq!(
+            sink.0,
+            vec![
+                Event::Missed(1, GapReason::AppNotRunning),
+                Event::Notify(at(9), NotificationSound::Glass),
+                Event::Prompt(at(9)),
+                Event::Away(at(10), AwayReason::Locked),
+                Event::Returned(at(10), at(11)),
+                Event::Notify(at(11), NotificationSound::Glass),
+                Event::Prompt(at(11)),
+            ]
+        );
+        let conn = db.conn.lock().unwrap();
+        let entries = TimeEntryRepository::new(conn).find_by_date_range(at(0), at(24)).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].away_reason, Some(AwayReason::Locked));
+        // Stamped by the timer's clock, not the wall clock
+        assert_eq!(entries[0].created_at, Some(at(11)));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_scheduler_skips_notification_when_disabled() {
+        let db = setup_db();
+        {
+            let conn = db.conn.lock().unwrap();
+            SettingsRepository::new(conn).set("notification_enabled", "false").unwrap();
+        }
+        let clock = TokioClock::starting_at(MONDAY + 30);
+        let (tx, rx) = mpsc::channel(4);
+
+        let scheduler = Scheduler::new(&db, RecordingSink::default())
+            .with_idle_source(Box::new(ScriptedIdleSource::new([IdleState::Active])));
+        let script = async {
+            tokio::time::sleep(TokioDuration::from_secs(HOUR as u64)).await;
+            tx.send(TimerCommand::Stop).await.unwrap();
+        };
+        let (sink, _) = tokio::join!(scheduler.run(&clock, 60, rx), script);
+
+        assert_eq!(sink.0, vec![Event::Prompt(MONDAY)]);
     }
 }
@@ -0,0 +1,74 @@
+use crate::db::{Database, SettingsRepository, TimeEntryRepository};
+use crate::services::Clock;
+use std::time::Duration;
+
+/// Time between purges of the trash
+pub const PURGE_INTERVAL_SECONDS: i64 = 24 * 60 * 60;
+
+const SECONDS_PER_DAY: i64 = 24 * 60 * 60;
+
+/// Permanently removes entries that have been in the trash for longer than
+/// the `trash_retention_days` setting. Returns how many were removed.
+pub fn purge(db: &Database, now: i64) -> Result<usize, String> {
+    let days = SettingsRepository::new(db.conn.lock().map_err(|e| e.to_string())?)
+        .get_trash_retention_days();
+    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+    TimeEntryRepository::new(conn)
+        .at(now)
+        .purge_deleted(now - days as i64 * SECONDS_PER_DAY)
+        .map_err(|e| e.to_string())
+}
+
+/// Empties old entries from the trash at startup and then once a day for as
+/// long as the app runs
+pub async fn run_daily(clock: &dyn Clock, db: &Database) {
+    loop {
+        match purge(db, clock.now()) {
+            Ok(0) => {}
+            Ok(purged) => log::info!("Purged {} deleted entries", purged),
+            Err(e) => log::warn!("Purging deleted entries failed: {}", e),
+        }
+        tokio::time::sleep(Duration::from_secs(PURGE_INTERVAL_SECONDS as u64)).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{migrations, OverlapPolicy};
+
+    #[test]
+    fn test_purge_uses_retention_setting() {
+        let db = Database::new_in_memory().unwrap();
+        {
+            let conn = db.conn.lock().unwrap();
+            migrations::run_migrations(&conn).unwrap();
+            conn.execute(
+                "UPDATE settings SET value = '7' WHERE key = 'trash_retention_days'",
+                [],
+            )
+            .unwrap();
+        }
+        let now = 100 * SECONDS_PER_DAY;
+        for (timestamp, deleted_days_ago) in [(1000, 8), (2000, 6)] {
+            let conn = db.conn.lock().unwrap();
+            let repo = TimeEntryRepository::new(conn);
+            let id = repo
+                .create(timestamp, "deep_work", 15, false, false, None, None, OverlapPolicy::Reject)
+                .unwrap();
+            repo.delete(id).unwrap();
+            drop(repo);
+            db.conn
+                .lock()
+                .unwrap()
+                .execute(
+                    "UPDATE time_entries SET deleted_at = ?1 WHERE id = ?2",
+                    (now - deleted_days_ago * SECONDS_PER_DAY, id),
+                )
+                .unwrap();
+        }
+
+        assert_eq!(purge(&db, now).unwrap(), 1);
+        assert_eq!(purge(&db, now).unwrap(), 0);
+    }
+}
@@ -4,11 +4,31 @@ import { useAppStore } from "./stores/appStore";
 import { CalendarView } from "./components/calendar/CalendarView";
 import { PromptDialog } from "./components/tracker/PromptDialog";
 import { SettingsPanel } from "./components/settings/SettingsPanel";
+import { TimerStatus } from "./components/tracker/TimerStatus";
 import "./App.css";
 
 function App() {
-  const { currentView, setCurrentView, setPendingTimestamp, loadSettings } =
-    useAppStore();
+  const {
+    currentView,
+    setCurrentView,
+    setPendingTimestamp,
+    loadSettings,
+    loadMissedPrompts,
+    undoLastChange,
+  } = useAppStore();
+
+  useEffect(() => {
+    const handleKeyDown = (event: KeyboardEvent) => {
+      const target = event.target as HTMLElement;
+      const editing = target.closest("input, textarea, select");
+      if ((event.metaKey || event.ctrlKey) && event.key === "z" && !event.shiftKey && !editing) {
+        event.preventDefault();
+        undoLastChange();
+      }
+    };
+    window.addEventListener("keydown", handleKeyDown);
+    return () => window.removeEventListener("keydown", handleKeyDown);
+  }, [undoLastChange]);
 
   useEffect(() => {
     loadSettings();
@@ -30,17 +50,30 @@ function App() {
       }
     );
 
+    // Listen for intervals backfilled after sleep or while the app was closed
+    const unlistenMissed = listen<{ count: number; reason: string }>(
+      "missed-prompts-added",
+      () => {
+        loadMissedPrompts(useAppStore.getState().selectedDate);
+      }
+    );
+
     return () => {
       unlistenPrompt.then((unlisten) => unlisten());
       unlistenAway.then((unlisten) => unlisten());
+      unlistenMissed.then((unlisten) => unlisten());
     };
-  }, [loadSettings, setPendingTimestamp]);
+  }, [loadSettings, loadMissedPrompts, setPendingTimestamp]);
 
   return (
     <div className="app">
       <header className="app-header">
         <h1>Time Tracker</h1>
+        <TimerStatus />
         <nav className="app-nav">
+          <button onClick={() => undoLastChange()} title="Undo last change (Ctrl+Z)">
+            Undo
+          </button>
           <button
             className={currentView === "calendar" ? "active" : ""}
             onClick={() => setCurrentView("calendar")}
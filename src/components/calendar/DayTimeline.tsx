@@ -22,7 +22,14 @@ export function DayTimeline() {
       const slotTime = addMinutes(dayStart, i * 15);
       const timestamp = Math.floor(slotTime.getTime() / 1000);
 
-      const entry = entries.find((e) => e.timestamp === timestamp);
+      // Away time is stored as one block spanning several slots
+      const entry =
+        entries.find((e) => e.timestamp === timestamp) ??
+        entries.find(
+          (e) =>
+            e.timestamp < timestamp &&
+            timestamp < e.timestamp + (e.duration_minutes || 15) * 60
+        );
 
       slots.push({
         timestamp,
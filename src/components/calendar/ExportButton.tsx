@@ -1,13 +1,14 @@
 import { useState, useRef, useEffect } from "react";
 import { startOfWeek, subDays, format } from "date-fns";
+import { listen } from "@tauri-apps/api/event";
 import { save } from "@tauri-apps/plugin-dialog";
-import { writeTextFile } from "@tauri-apps/plugin-fs";
-import { exportEntriesToCsv } from "../../services/api";
-import { ExportDateRange, EXPORT_DATE_RANGES } from "../../types";
+import { exportEntriesToFile } from "../../services/api";
+import { ExportDateRange, ExportProgress, EXPORT_DATE_RANGES } from "../../types";
 
 export function ExportButton() {
   const [isOpen, setIsOpen] = useState(false);
   const [isExporting, setIsExporting] = useState(false);
+  const [progress, setProgress] = useState<ExportProgress | null>(null);
   const dropdownRef = useRef<HTMLDivElement>(null);
 
   useEffect(() => {
@@ -63,35 +64,45 @@ export function ExportButton() {
 
   const handleExport = async (range: ExportDateRange) => {
     setIsOpen(false);
-    setIsExporting(true);
 
-    try {
-      const { start, end } = getDateRange(range);
-      const csvContent = await exportEntriesToCsv(start, end);
+    const rangeLabel = EXPORT_DATE_RANGES.find((r) => r.value === range)?.label || range;
+    const defaultFileName = `time-tracker-${rangeLabel.toLowerCase().replace(/\s+/g, "-")}-${format(new Date(), "yyyy-MM-dd")}.csv`;
 
-      const rangeLabel = EXPORT_DATE_RANGES.find((r) => r.value === range)?.label || range;
-      const defaultFileName = `time-tracker-${rangeLabel.toLowerCase().replace(/\s+/g, "-")}-${format(new Date(), "yyyy-MM-dd")}.csv`;
+    const filePath = await save({
+      defaultPath: defaultFileName,
+      filters: [
+        {
+          name: "CSV",
+          extensions: ["csv"],
+        },
+      ],
+    });
+    if (!filePath) {
+      return;
+    }
 
-      const filePath = await save({
-        defaultPath: defaultFileName,
-        filters: [
-          {
-            name: "CSV",
-            extensions: ["csv"],
-          },
-        ],
-      });
+    setIsExporting(true);
+    const unlisten = await listen<ExportProgress>("export-progress", (event) => {
+      setProgress(event.payload);
+    });
 
-      if (filePath) {
-        await writeTextFile(filePath, csvContent);
-      }
+    try {
+      const { start, end } = getDateRange(range);
+      await exportEntriesToFile(filePath, start, end, "csv");
     } catch (error) {
       console.error("Export failed:", error);
     } finally {
+      unlisten();
+      setProgress(null);
       setIsExporting(false);
     }
   };
 
+  const exportingLabel =
+    progress && progress.total > 0
+      ? `Exporting ${Math.floor((progress.written / progress.total) * 100)}%`
+      : "Exporting...";
+
   return (
     <div className="export-dropdown" ref={dropdownRef}>
       <button
@@ -99,7 +110,7 @@ export function ExportButton() {
         onClick={() => setIsOpen(!isOpen)}
         disabled={isExporting}
       >
-        {isExporting ? "Exporting..." : "Export"}
+        {isExporting ? exportingLabel : "Export"}
       </button>
       {isOpen && (
         <div className="export-menu">
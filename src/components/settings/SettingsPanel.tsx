@@ -1,18 +1,53 @@
-import { useEffect } from "react";
+import { useEffect, useState } from "react";
+import { ask, save } from "@tauri-apps/plugin-dialog";
 import { useAppStore } from "../../stores/appStore";
-import { testNotification } from "../../services/api";
+import {
+  getDatabaseLocation,
+  listBackups,
+  restoreBackup,
+  setDatabaseLocation,
+  testNotification,
+} from "../../services/api";
+import { BackupInfo, DbLocation } from "../../types";
+
+const BACKUP_REASON_LABELS: Record<BackupInfo["reason"], string> = {
+  daily: "Daily",
+  pre_migration: "Before upgrade",
+  pre_restore: "Before restore",
+};
 
 export function SettingsPanel() {
-  const { settings, loadSettings, updateSetting, setCurrentView } = useAppStore();
+  const {
+    settings,
+    selectedDate,
+    loadSettings,
+    loadEntriesForDate,
+    updateSetting,
+    setCurrentView,
+  } = useAppStore();
+
+  const [dbLocation, setDbLocation] = useState<DbLocation | null>(null);
+  const [nextDbLocation, setNextDbLocation] = useState<DbLocation | null>(null);
+  const [dbLocationError, setDbLocationError] = useState<string | null>(null);
+  const [backups, setBackups] = useState<BackupInfo[]>([]);
+  const [backupError, setBackupError] = useState<string | null>(null);
 
   useEffect(() => {
     loadSettings();
   }, [loadSettings]);
 
+  useEffect(() => {
+    getDatabaseLocation().then(setDbLocation);
+    listBackups().then(setBackups);
+  }, []);
+
   const intervalMinutes = settings.interval_minutes || "15";
   const idleThreshold = settings.idle_threshold_minutes || "5";
   const notificationEnabled = settings.notification_enabled !== "false";
   const notificationSound = settings.notification_sound || "default";
+  const idleBackend = settings.idle_backend || "auto";
+  const backupCount = settings.backup_count || "7";
+  const trashRetentionDays = settings.trash_retention_days || "30";
 
   const handleIntervalChange = (e: React.ChangeEvent<HTMLSelectElement>) => {
     updateSetting("interval_minutes", e.target.value);
@@ -22,6 +57,41 @@ export function SettingsPanel() {
     updateSetting("idle_threshold_minutes", e.target.value);
   };
 
+  const handleIdleBackendChange = (e: React.ChangeEvent<HTMLSelectElement>) => {
+    updateSetting("idle_backend", e.target.value);
+  };
+
+  const handleTrashRetentionChange = (e: React.ChangeEvent<HTMLSelectElement>) => {
+    updateSetting("trash_retention_days", e.target.value);
+  };
+
+  const handleBackupCountChange = (e: React.ChangeEvent<HTMLSelectElement>) => {
+    updateSetting("backup_count", e.target.value);
+  };
+
+  const handleRestoreBackup = async (backup: BackupInfo) => {
+    try {
+      const preview = await restoreBackup(backup.file_name, true);
+      const latest = preview.latest_entry_at
+        ? `, the latest from ${new Date(preview.latest_entry_at * 1000).toLocaleString()}`
+        : "";
+      const confirmed = await ask(
+        `Replace all current entries with the ${preview.entry_count} entries in this backup${latest}? Your current data is backed up first.`,
+        { title: "Restore Backup", kind: "warning" }
+      );
+      if (!confirmed) {
+        return;
+      }
+      await restoreBackup(backup.file_name, false);
+      setBackupError(null);
+      setBackups(await listBackups());
+      loadSettings();
+      loadEntriesForDate(selectedDate);
+    } catch (error) {
+      setBackupError(String(error));
+    }
+  };
+
   const handleNotificationEnabledChange = (e: React.ChangeEvent<HTMLInputElement>) => {
     updateSetting("notification_enabled", e.target.checked ? "true" : "false");
   };
@@ -30,6 +100,25 @@ export function SettingsPanel() {
     updateSetting("notification_sound", e.target.value);
   };
 
+  const changeDatabaseLocation = async (path?: string) => {
+    try {
+      setNextDbLocation(await setDatabaseLocation(path));
+      setDbLocationError(null);
+    } catch (error) {
+      setDbLocationError(String(error));
+    }
+  };
+
+  const handleChooseDatabase = async () => {
+    const path = await save({
+      defaultPath: "time_tracker.db",
+      filters: [{ name: "Database", extensions: ["db"] }],
+    });
+    if (path) {
+      changeDatabaseLocation(path);
+    }
+  };
+
   const handleTestNotification = () => {
     testNotification(notificationSound);
   };
@@ -81,6 +170,25 @@ export function SettingsPanel() {
           </p>
         </div>
 
+        <div className="setting-item">
+          <label htmlFor="idle-backend">Idle Detection Method</label>
+          <select
+            id="idle-backend"
+            value={idleBackend}
+            onChange={handleIdleBackendChange}
+          >
+            <option value="auto">Automatic</option>
+            <option value="macos">macOS</option>
+            <option value="x11">X11 (XScreenSaver)</option>
+            <option value="screensaver">Screen saver service (Wayland, KDE)</option>
+            <option value="logind">systemd-logind</option>
+            <option value="none">Off</option>
+          </select>
+          <p className="setting-description">
+            Where to read idle time from; Automatic picks the first that works
+          </p>
+        </div>
+
         <div className="setting-item">
           <label htmlFor="notification-enabled">
             <input
@@ -128,6 +236,89 @@ export function SettingsPanel() {
             Sound to play with notifications (macOS sounds)
           </p>
         </div>
+
+        <div className="setting-item">
+          <label htmlFor="trash-retention">Keep Deleted Entries</label>
+          <select
+            id="trash-retention"
+            value={trashRetentionDays}
+            onChange={handleTrashRetentionChange}
+          >
+            <option value="7">7 days</option>
+            <option value="30">30 days</option>
+            <option value="90">90 days</option>
+            <option value="365">1 year</option>
+          </select>
+          <p className="setting-description">
+            Deleted entries can be restored or undone until then
+          </p>
+        </div>
+
+        <div className="setting-item">
+          <label htmlFor="backup-count">Backups to Keep</label>
+          <select
+            id="backup-count"
+            value={backupCount}
+            onChange={handleBackupCountChange}
+          >
+            <option value="3">3</option>
+            <option value="7">7</option>
+            <option value="14">14</option>
+            <option value="30">30</option>
+          </select>
+          <p className="setting-description">
+            A backup is taken daily and before upgrades; the oldest are removed
+          </p>
+          {backups.map((backup) => (
+            <div className="sound-select-row" key={backup.file_name}>
+              <span className="database-path">
+                {new Date(backup.created_at * 1000).toLocaleString()} ·{" "}
+                {BACKUP_REASON_LABELS[backup.reason]}
+              </span>
+              <button
+                className="test-sound-btn"
+                onClick={() => handleRestoreBackup(backup)}
+              >
+                Restore
+              </button>
+            </div>
+          ))}
+          {backupError && <p className="setting-description">{backupError}</p>}
+        </div>
+
+        <div className="setting-item">
+          <label>Database Location</label>
+          <div className="sound-select-row">
+            <code className="database-path">{dbLocation?.path}</code>
+            <button
+              className="test-sound-btn"
+              onClick={handleChooseDatabase}
+              disabled={dbLocation?.source === "environment"}
+            >
+              Change
+            </button>
+            <button
+              className="test-sound-btn"
+              onClick={() => changeDatabaseLocation()}
+              disabled={dbLocation?.source !== "setting"}
+            >
+              Use Default
+            </button>
+          </div>
+          <p className="setting-description">
+            {dbLocation?.source === "environment"
+              ? "Set by the TIME_TRACKER_DB environment variable"
+              : "A new file gets a copy of your entries"}
+          </p>
+          {nextDbLocation && (
+            <p className="setting-description">
+              Restart Time Tracker to use {nextDbLocation.path}
+            </p>
+          )}
+          {dbLocationError && (
+            <p className="setting-description">{dbLocationError}</p>
+          )}
+        </div>
       </div>
 
       <div className="settings-footer">
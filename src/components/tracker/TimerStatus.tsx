@@ -0,0 +1,61 @@
+import { useEffect, useState } from "react";
+import { listen } from "@tauri-apps/api/event";
+import { format } from "date-fns";
+import {
+  getTimerState,
+  pauseTimer,
+  resumeTimer,
+  snoozeTimer,
+} from "../../services/api";
+import { TimerState } from "../../types";
+
+const SNOOZE_MINUTES = 15;
+
+function describe(timer: TimerState): string {
+  switch (timer.state) {
+    case "running":
+      return "Tracking";
+    case "paused":
+      return timer.until
+        ? `Paused until ${format(new Date(timer.until * 1000), "h:mm a")}`
+        : "Paused";
+    case "snoozed":
+      return `Snoozed until ${format(new Date(timer.until * 1000), "h:mm a")}`;
+  }
+}
+
+export function TimerStatus() {
+  const [timer, setTimer] = useState<TimerState>({ state: "running" });
+
+  useEffect(() => {
+    getTimerState().then(setTimer).catch(console.error);
+
+    const unlisten = listen<TimerState>("timer-state-changed", (event) => {
+      setTimer(event.payload);
+    });
+
+    return () => {
+      unlisten.then((unlisten) => unlisten());
+    };
+  }, []);
+
+  const run = (action: () => Promise<void>) => {
+    action().catch((error) => console.error("Timer update failed:", error));
+  };
+
+  return (
+    <div className={`timer-status timer-status-${timer.state}`}>
+      <span className="timer-status-label">{describe(timer)}</span>
+      {timer.state === "running" ? (
+        <>
+          <button onClick={() => run(() => snoozeTimer(SNOOZE_MINUTES))}>
+            Snooze {SNOOZE_MINUTES}m
+          </button>
+          <button onClick={() => run(() => pauseTimer())}>Pause</button>
+        </>
+      ) : (
+        <button onClick={() => run(resumeTimer)}>Resume</button>
+      )}
+    </div>
+  );
+}
@@ -1,5 +1,37 @@
 import { invoke } from "@tauri-apps/api/core";
-import { TimeEntry, MissedPrompt, Setting, Category } from "../types";
+import {
+  TimeEntry,
+  BackupInfo,
+  MissedPrompt,
+  Setting,
+  Category,
+  CategoryRecord,
+  Client,
+  ClientTotal,
+  ConflictPolicy,
+  DbLocation,
+  EntryChange,
+  EntryFilter,
+  EntryHistoryItem,
+  EntryOverlap,
+  EntrySearchHit,
+  ExportFormat,
+  ExportSummary,
+  ImportReport,
+  OverlapPolicy,
+  Project,
+  ProjectTotal,
+  ReportBucket,
+  ReportPeriod,
+  RestorePreview,
+  Tag,
+  TagMatch,
+  TagTotal,
+  Settings,
+  TimerState,
+  WorkBlock,
+  WorkSchedule,
+} from "../types";
 
 export async function createTimeEntry(
   timestamp: number,
@@ -9,6 +41,8 @@ export async function createTimeEntry(
     is_away?: boolean;
     is_retroactive?: boolean;
     notes?: string;
+    project_id?: number;
+    on_overlap?: OverlapPolicy;
   }
 ): Promise<number> {
   return invoke<number>("create_time_entry", {
@@ -18,16 +52,31 @@ export async function createTimeEntry(
     isAway: options?.is_away,
     isRetroactive: options?.is_retroactive,
     notes: options?.notes,
+    projectId: options?.project_id,
+    onOverlap: options?.on_overlap,
   });
 }
 
-export async function getEntriesForDate(
+export async function findOverlappingEntries(
   startTimestamp: number,
   endTimestamp: number
+): Promise<EntryOverlap[]> {
+  return invoke<EntryOverlap[]>("find_overlapping_entries", {
+    startTimestamp,
+    endTimestamp,
+  });
+}
+
+export async function getEntriesForDate(
+  startTimestamp: number,
+  endTimestamp: number,
+  filter?: EntryFilter
 ): Promise<TimeEntry[]> {
   return invoke<TimeEntry[]>("get_entries_for_date", {
     startTimestamp,
     endTimestamp,
+    projectId: filter?.project_id,
+    clientId: filter?.client_id,
   });
 }
 
@@ -42,10 +91,60 @@ export async function updateTimeEntry(
   });
 }
 
+export async function setTimeEntryProject(
+  id: number,
+  projectId: number | null
+): Promise<void> {
+  return invoke("set_time_entry_project", { id, projectId });
+}
+
+// Moves the entry to the trash; see restoreTimeEntry and undoLastChange
 export async function deleteTimeEntry(id: number): Promise<void> {
   return invoke("delete_time_entry", { id });
 }
 
+export async function restoreTimeEntry(id: number): Promise<void> {
+  return invoke("restore_time_entry", { id });
+}
+
+// Entries in the trash, most recently deleted first
+export async function getDeletedEntries(): Promise<TimeEntry[]> {
+  return invoke<TimeEntry[]>("get_deleted_entries");
+}
+
+// Searches entry notes, best match first. Words match anywhere, "quoted
+// words" as a phrase and a trailing * as a prefix.
+export async function searchEntries(
+  query: string,
+  options?: {
+    start_timestamp?: number;
+    end_timestamp?: number;
+    category?: Category;
+    limit?: number;
+  }
+): Promise<EntrySearchHit[]> {
+  return invoke<EntrySearchHit[]>("search_entries", {
+    query,
+    startTimestamp: options?.start_timestamp,
+    endTimestamp: options?.end_timestamp,
+    category: options?.category,
+    limit: options?.limit,
+  });
+}
+
+// Every recorded change to the entry, oldest first
+export async function getEntryHistory(
+  entryId: number
+): Promise<EntryHistoryItem[]> {
+  return invoke<EntryHistoryItem[]>("get_entry_history", { entryId });
+}
+
+// Reverts the latest create, edit, delete or restore; null when there is
+// nothing left to undo
+export async function undoLastChange(): Promise<EntryChange | null> {
+  return invoke<EntryChange | null>("undo_last_change");
+}
+
 export async function createMissedPrompt(
   timestamp: number,
   reason?: string
@@ -71,6 +170,15 @@ export async function getSetting(key: string): Promise<string | null> {
   return invoke<string | null>("get_setting", { key });
 }
 
+export async function getSettings(): Promise<Settings> {
+  return invoke<Settings>("get_settings");
+}
+
+// Saves every setting at once; nothing changes if any value is rejected
+export async function updateSettings(values: Settings): Promise<Settings> {
+  return invoke<Settings>("update_settings", { values });
+}
+
 export async function setSetting(key: string, value: string): Promise<void> {
   return invoke("set_setting", { key, value });
 }
@@ -83,12 +191,295 @@ export async function testNotification(sound: string): Promise<void> {
   return invoke("test_notification", { sound });
 }
 
+export async function getDatabaseLocation(): Promise<DbLocation> {
+  return invoke<DbLocation>("get_database_location");
+}
+
+// Takes effect on the next start; omit `path` to go back to the default.
+// Returns the location the next start will use.
+export async function setDatabaseLocation(path?: string): Promise<DbLocation> {
+  return invoke<DbLocation>("set_database_location", { path });
+}
+
+export async function listBackups(): Promise<BackupInfo[]> {
+  return invoke<BackupInfo[]>("list_backups");
+}
+
+// Run with `dryRun` first to check the backup and show what comes back, then
+// again to replace every entry with it. The current data is backed up first.
+export async function restoreBackup(
+  fileName: string,
+  dryRun: boolean
+): Promise<RestorePreview> {
+  return invoke<RestorePreview>("restore_backup", { fileName, dryRun });
+}
+
+export async function exportEntries(
+  startTimestamp: number,
+  endTimestamp: number,
+  format: ExportFormat,
+  options?: { timezone?: string; filter?: EntryFilter }
+): Promise<string> {
+  return invoke<string>("export_entries", {
+    startTimestamp,
+    endTimestamp,
+    format,
+    timezone: options?.timezone,
+    projectId: options?.filter?.project_id,
+    clientId: options?.filter?.client_id,
+  });
+}
+
+// Streams the export straight to `path`; listen for "export-progress"
+// events to follow along on large ranges
+export async function exportEntriesToFile(
+  path: string,
+  startTimestamp: number,
+  endTimestamp: number,
+  format: ExportFormat,
+  options?: { timezone?: string; filter?: EntryFilter }
+): Promise<ExportSummary> {
+  return invoke<ExportSummary>("export_entries_to_file", {
+    path,
+    startTimestamp,
+    endTimestamp,
+    format,
+    timezone: options?.timezone,
+    projectId: options?.filter?.project_id,
+    clientId: options?.filter?.client_id,
+  });
+}
+
+// `timezone` is an IANA name ("Europe/Berlin") or UTC offset ("+05:30");
+// times are rendered in the local zone when omitted
 export async function exportEntriesToCsv(
   startTimestamp: number,
-  endTimestamp: number
+  endTimestamp: number,
+  filter?: EntryFilter,
+  timezone?: string
 ): Promise<string> {
   return invoke<string>("export_entries_to_csv", {
     startTimestamp,
     endTimestamp,
+    projectId: filter?.project_id,
+    clientId: filter?.client_id,
+    timezone,
+  });
+}
+
+// Run with `dryRun` first to preview the changes, then again to apply them.
+// `timezone` only applies to CSV files without a UTC Offset column.
+export async function importEntries(
+  content: string,
+  format: ExportFormat,
+  onConflict: ConflictPolicy,
+  dryRun: boolean,
+  timezone?: string
+): Promise<ImportReport> {
+  return invoke<ImportReport>("import_entries", {
+    content,
+    format,
+    onConflict,
+    dryRun,
+    timezone,
+  });
+}
+
+export async function getCategories(
+  includeArchived?: boolean
+): Promise<CategoryRecord[]> {
+  return invoke<CategoryRecord[]>("get_categories", { includeArchived });
+}
+
+export async function createCategory(
+  name: string,
+  color: string,
+  options?: { icon?: string; is_productive?: boolean }
+): Promise<number> {
+  return invoke<number>("create_category", {
+    name,
+    color,
+    icon: options?.icon,
+    isProductive: options?.is_productive,
   });
 }
+
+export async function renameCategory(id: number, name: string): Promise<void> {
+  return invoke("rename_category", { id, name });
+}
+
+export async function archiveCategory(
+  id: number,
+  archived = true
+): Promise<void> {
+  return invoke("archive_category", { id, archived });
+}
+
+export async function mergeCategories(
+  sourceId: number,
+  targetId: number
+): Promise<number> {
+  return invoke<number>("merge_categories", { sourceId, targetId });
+}
+
+export async function getClients(includeArchived?: boolean): Promise<Client[]> {
+  return invoke<Client[]>("get_clients", { includeArchived });
+}
+
+export async function createClient(name: string): Promise<number> {
+  return invoke<number>("create_client", { name });
+}
+
+export async function renameClient(id: number, name: string): Promise<void> {
+  return invoke("rename_client", { id, name });
+}
+
+export async function archiveClient(id: number, archived = true): Promise<void> {
+  return invoke("archive_client", { id, archived });
+}
+
+export async function getProjects(
+  clientId?: number,
+  includeArchived?: boolean
+): Promise<Project[]> {
+  return invoke<Project[]>("get_projects", { clientId, includeArchived });
+}
+
+export async function createProject(
+  name: string,
+  clientId?: number
+): Promise<number> {
+  return invoke<number>("create_project", { name, clientId });
+}
+
+export async function renameProject(id: number, name: string): Promise<void> {
+  return invoke("rename_project", { id, name });
+}
+
+export async function archiveProject(
+  id: number,
+  archived = true
+): Promise<void> {
+  return invoke("archive_project", { id, archived });
+}
+
+export async function getProjectTotals(
+  startTimestamp: number,
+  endTimestamp: number
+): Promise<ProjectTotal[]> {
+  return invoke<ProjectTotal[]>("get_project_totals", {
+    startTimestamp,
+    endTimestamp,
+  });
+}
+
+export async function getClientTotals(
+  startTimestamp: number,
+  endTimestamp: number
+): Promise<ClientTotal[]> {
+  return invoke<ClientTotal[]>("get_client_totals", {
+    startTimestamp,
+    endTimestamp,
+  });
+}
+
+export async function getTags(): Promise<Tag[]> {
+  return invoke<Tag[]>("get_tags");
+}
+
+export async function getEntryTags(entryId: number): Promise<string[]> {
+  return invoke<string[]>("get_entry_tags", { entryId });
+}
+
+export async function addTagToEntry(entryId: number, tag: string): Promise<void> {
+  return invoke("add_tag_to_entry", { entryId, tag });
+}
+
+export async function removeTagFromEntry(
+  entryId: number,
+  tag: string
+): Promise<void> {
+  return invoke("remove_tag_from_entry", { entryId, tag });
+}
+
+export async function getEntriesByTags(
+  tags: string[],
+  matchMode: TagMatch,
+  startTimestamp: number,
+  endTimestamp: number
+): Promise<TimeEntry[]> {
+  return invoke<TimeEntry[]>("get_entries_by_tags", {
+    tags,
+    matchMode,
+    startTimestamp,
+    endTimestamp,
+  });
+}
+
+export async function getMinutesByTags(
+  tags: string[],
+  matchMode: TagMatch,
+  startTimestamp: number,
+  endTimestamp: number
+): Promise<number> {
+  return invoke<number>("get_minutes_by_tags", {
+    tags,
+    matchMode,
+    startTimestamp,
+    endTimestamp,
+  });
+}
+
+export async function getTagTotals(
+  startTimestamp: number,
+  endTimestamp: number
+): Promise<TagTotal[]> {
+  return invoke<TagTotal[]>("get_tag_totals", { startTimestamp, endTimestamp });
+}
+
+export async function getCategoryReport(
+  startTimestamp: number,
+  endTimestamp: number,
+  period: ReportPeriod
+): Promise<ReportBucket[]> {
+  return invoke<ReportBucket[]>("get_category_report", {
+    startTimestamp,
+    endTimestamp,
+    period,
+  });
+}
+
+export async function getTimerState(): Promise<TimerState> {
+  return invoke<TimerState>("get_timer_state");
+}
+
+// Pauses until `until` (unix seconds), or until resumed when omitted
+export async function pauseTimer(until?: number): Promise<void> {
+  return invoke("pause_timer", { until });
+}
+
+export async function resumeTimer(): Promise<void> {
+  return invoke("resume_timer");
+}
+
+export async function snoozeTimer(minutes: number): Promise<void> {
+  return invoke("snooze_timer", { minutes });
+}
+
+export async function getWorkSchedule(): Promise<WorkSchedule> {
+  return invoke<WorkSchedule>("get_work_schedule");
+}
+
+// An empty list turns the schedule off and prompts around the clock
+export async function setWorkHours(blocks: WorkBlock[]): Promise<void> {
+  return invoke("set_work_hours", { blocks });
+}
+
+// `date` is YYYY-MM-DD
+export async function addDayOff(date: string, label?: string): Promise<void> {
+  return invoke("add_day_off", { date, label });
+}
+
+export async function removeDayOff(date: string): Promise<void> {
+  return invoke("remove_day_off", { date });
+}
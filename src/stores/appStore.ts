@@ -32,6 +32,7 @@ interface AppState {
   createEntry: (timestamp: number, category: Category, notes: string, isRetroactive?: boolean) => Promise<void>;
   deleteEntry: (id: number) => Promise<void>;
   updateEntry: (id: number, category?: Category, notes?: string) => Promise<void>;
+  undoLastChange: () => Promise<void>;
 
   updateSetting: (key: string, value: string) => Promise<void>;
 
@@ -97,9 +98,11 @@ export const useAppStore = create<AppState>((set, get) => ({
 
   createEntry: async (timestamp, category, notes, isRetroactive = false) => {
     try {
+      // An answer takes the slot over from whatever was recorded there
       await api.createTimeEntry(timestamp, category, {
         notes,
         is_retroactive: isRetroactive,
+        on_overlap: "split",
       });
       await get().loadEntriesForDate(get().selectedDate);
       set({ pendingTimestamp: null, currentView: "calendar" });
@@ -126,6 +129,17 @@ export const useAppStore = create<AppState>((set, get) => ({
     }
   },
 
+  undoLastChange: async () => {
+    try {
+      const change = await api.undoLastChange();
+      if (change) {
+        await get().loadEntriesForDate(get().selectedDate);
+      }
+    } catch (error) {
+      console.error("Failed to undo last change:", error);
+    }
+  },
+
   updateSetting: async (key, value) => {
     try {
       await api.setSetting(key, value);
@@ -142,6 +156,7 @@ export const useAppStore = create<AppState>((set, get) => ({
       await api.createTimeEntry(timestamp, category, {
         notes,
         is_retroactive: true,
+        on_overlap: "split",
       });
       await api.deleteMissedPrompt(timestamp);
       await get().loadEntriesForDate(get().selectedDate);
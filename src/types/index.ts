@@ -6,7 +6,84 @@ export interface TimeEntry {
   is_away: boolean;
   is_retroactive: boolean;
   notes: string | null;
+  project_id: number | null;
   created_at: number | null;
+  away_reason: AwayReason | null;
+  // Set while the entry is in the trash
+  deleted_at: number | null;
+}
+
+export type AwayReason = "idle" | "locked" | "suspended";
+
+export type OverlapPolicy = "reject" | "replace" | "split";
+
+export interface EntryOverlap {
+  start: number;
+  end: number;
+  first: TimeEntry;
+  second: TimeEntry;
+}
+
+export interface EntryFilter {
+  project_id?: number;
+  client_id?: number;
+}
+
+export interface Client {
+  id: number | null;
+  name: string;
+  archived: boolean;
+  created_at: number | null;
+}
+
+export interface Project {
+  id: number | null;
+  client_id: number | null;
+  name: string;
+  archived: boolean;
+  created_at: number | null;
+}
+
+export interface ProjectTotal {
+  project_id: number | null;
+  project_name: string | null;
+  client_id: number | null;
+  client_name: string | null;
+  total_minutes: number;
+}
+
+export interface ClientTotal {
+  client_id: number | null;
+  client_name: string | null;
+  total_minutes: number;
+}
+
+export interface Tag {
+  id: number | null;
+  name: string;
+  created_at: number | null;
+}
+
+export type TagMatch = "any" | "all";
+
+export interface TagTotal {
+  tag: string;
+  total_minutes: number;
+}
+
+export type ReportPeriod = "day" | "week" | "month";
+
+export interface CategoryMinutes {
+  category: Category;
+  total_minutes: number;
+}
+
+export interface ReportBucket {
+  start: number;
+  end: number;
+  label: string;
+  total_minutes: number;
+  categories: CategoryMinutes[];
 }
 
 export interface MissedPrompt {
@@ -21,12 +98,21 @@ export interface Setting {
   value: string;
 }
 
-export type Category =
-  | "deep_work"
-  | "meetings"
-  | "admin"
-  | "break"
-  | "away";
+// Category slug; built-in slugs are listed in CATEGORIES, user-defined ones
+// come from getCategories()
+export type Category = string;
+
+export interface CategoryRecord {
+  id: number | null;
+  name: string;
+  slug: Category;
+  color: string;
+  icon: string | null;
+  is_productive: boolean;
+  archived: boolean;
+  sort_order: number;
+  created_at: number | null;
+}
 
 export const CATEGORIES: { value: Category; label: string; color: string }[] = [
   { value: "deep_work", label: "Deep Work", color: "#4F46E5" },
@@ -38,6 +124,50 @@ export const CATEGORIES: { value: Category; label: string; color: string }[] = [
 
 export type ViewMode = "summary" | "timeline";
 
+export type ExportFormat = "csv" | "json" | "ndjson";
+
+export interface ExportMetadata {
+  format: ExportFormat;
+  start_timestamp: number;
+  end_timestamp: number;
+  timezone: string;
+  app_version: string;
+  exported_at: number;
+}
+
+export interface ExportSummary {
+  path: string;
+  rows: number;
+  bytes: number;
+}
+
+export interface ExportProgress {
+  written: number;
+  total: number;
+}
+
+export type ConflictPolicy = "skip" | "overwrite";
+
+export type ImportAction = "insert" | "skip" | "overwrite" | "invalid";
+
+export interface ImportRowResult {
+  row: number;
+  timestamp: number | null;
+  category: string | null;
+  action: ImportAction;
+  existing_id: number | null;
+  message: string | null;
+}
+
+export interface ImportReport {
+  dry_run: boolean;
+  inserted: number;
+  overwritten: number;
+  skipped: number;
+  invalid: number;
+  rows: ImportRowResult[];
+}
+
 export type ExportDateRange = "this_week" | "last_7_days" | "last_30_days" | "all_time";
 
 export const EXPORT_DATE_RANGES: { value: ExportDateRange; label: string }[] = [
@@ -56,3 +186,127 @@ export interface AppState {
   missedPrompts: MissedPrompt[];
   settings: Record<string, string>;
 }
+
+export type TimerState =
+  | { state: "running" }
+  | { state: "paused"; until: number | null }
+  | { state: "snoozed"; until: number };
+
+// Minutes since local midnight; weekday counts from Monday = 0
+export interface WorkBlock {
+  weekday: number;
+  start_minute: number;
+  end_minute: number;
+}
+
+export interface DayOff {
+  date: string;
+  label: string | null;
+}
+
+export interface WorkSchedule {
+  blocks: WorkBlock[];
+  days_off: DayOff[];
+}
+
+export type NotificationSound =
+  | "default"
+  | "glass"
+  | "hero"
+  | "morse"
+  | "ping"
+  | "pop"
+  | "purr"
+  | "sosumi"
+  | "submarine"
+  | "tink";
+
+export type IdleBackend =
+  | "auto"
+  | "macos"
+  | "x11"
+  | "screensaver"
+  | "logind"
+  | "none";
+
+export interface Settings {
+  interval_minutes: number;
+  idle_threshold_minutes: number;
+  notification_enabled: boolean;
+  notification_sound: NotificationSound;
+  idle_backend: IdleBackend;
+  backup_count: number;
+  trash_retention_days: number;
+}
+
+// Which rule picked the database file: the TIME_TRACKER_DB environment
+// variable, the location chosen in settings, or the app data directory
+export type DbPathSource = "environment" | "setting" | "default";
+
+export interface DbLocation {
+  path: string;
+  source: DbPathSource;
+}
+
+export type BackupReason = "daily" | "pre_migration" | "pre_restore";
+
+export interface BackupInfo {
+  file_name: string;
+  path: string;
+  created_at: number;
+  reason: BackupReason;
+  size_bytes: number;
+}
+
+// What restoring a backup would bring back
+export interface RestorePreview {
+  backup: BackupInfo;
+  schema_version: number;
+  entry_count: number;
+  latest_entry_at: number | null;
+}
+
+export type ChangeAction = "create" | "update" | "delete" | "restore";
+
+// A journaled change to entries, as reported after undoing it
+export interface EntryChange {
+  id: number;
+  action: ChangeAction;
+  entry_ids: number[];
+  created_at: number;
+}
+
+// A run of snippet text; highlighted runs matched the search
+export interface SnippetPart {
+  text: string;
+  highlighted: boolean;
+}
+
+// An entry whose notes matched a search; lower ranks are better matches
+export interface EntrySearchHit {
+  entry: TimeEntry;
+  rank: number;
+  snippet: SnippetPart[];
+}
+
+export type ChangeSource =
+  | "prompt"
+  | "retroactive"
+  | "edit"
+  | "timer"
+  | "import"
+  | "bulk_edit"
+  | "undo"
+  | "purge";
+
+// One field of an entry changing value; old_value is null when the entry
+// was created, new_value when the field was cleared
+export interface EntryHistoryItem {
+  id: number;
+  entry_id: number;
+  field: string;
+  old_value: string | null;
+  new_value: string | null;
+  source: ChangeSource;
+  changed_at: number;
+}